@@ -0,0 +1,73 @@
+//! Browser side of the admin JSON API under [`endpoints::ADMIN_ROOMS`].
+
+use common::{
+    api::{NoticeRequest, RoomStatus},
+    endpoints,
+};
+use leptos::window;
+use wasm_bindgen::{JsCast, JsValue};
+use wasm_bindgen_futures::JsFuture;
+
+async fn request(
+    base: &str,
+    method: &str,
+    path: &str,
+    token: &str,
+    body: Option<String>,
+) -> Result<web_sys::Response, JsValue> {
+    let headers = web_sys::Headers::new()?;
+    headers.set("Authorization", &format!("Bearer {token}"))?;
+    let init = web_sys::RequestInit::new();
+    init.set_method(method);
+    if let Some(body) = body {
+        headers.set("Content-Type", "application/json")?;
+        init.set_body(&JsValue::from_str(&body));
+    }
+    init.set_headers(&headers);
+    let response =
+        JsFuture::from(window().fetch_with_str_and_init(&format!("{base}{path}"), &init)).await?;
+    let response: web_sys::Response = response.dyn_into()?;
+    match response.status() {
+        401 => Err(JsValue::from_str("Wrong admin token")),
+        404 if path == endpoints::ADMIN_ROOMS => {
+            Err(JsValue::from_str("Admin API is disabled on this server"))
+        }
+        _ if !response.ok() => Err(JsValue::from_str(&format!(
+            "Server responded with {}",
+            response.status()
+        ))),
+        _ => Ok(response),
+    }
+}
+
+pub async fn list_rooms(base: &str, token: &str) -> Result<Vec<RoomStatus>, JsValue> {
+    let response = request(base, "GET", endpoints::ADMIN_ROOMS, token, None).await?;
+    let body = JsFuture::from(response.text()?).await?;
+    let body = body
+        .as_string()
+        .ok_or(JsValue::from_str("Invalid rooms response"))?;
+    serde_json::from_str(&body).map_err(|err| JsValue::from_str(&err.to_string()))
+}
+
+pub async fn close_room(base: &str, token: &str, room_id: &str) -> Result<(), JsValue> {
+    let path = endpoints::ADMIN_ROOM.replace(":id", room_id);
+    request(base, "DELETE", &path, token, None).await?;
+    Ok(())
+}
+
+/// Sends to every room when `room_id` is `None`.
+pub async fn send_notice(
+    base: &str,
+    token: &str,
+    room_id: Option<&str>,
+    message: String,
+) -> Result<(), JsValue> {
+    let path = match room_id {
+        Some(room_id) => endpoints::ADMIN_ROOM_NOTICE.replace(":id", room_id),
+        None => endpoints::ADMIN_NOTICE.to_string(),
+    };
+    let body = serde_json::to_string(&NoticeRequest { message })
+        .map_err(|err| JsValue::from_str(&err.to_string()))?;
+    request(base, "POST", &path, token, Some(body)).await?;
+    Ok(())
+}
@@ -0,0 +1,78 @@
+//! Packs what the app knows into a [`DiagnosticsBundle`] and uploads it to
+//! [`endpoints::DIAGNOSTICS`], the server answers with a code to quote in bug reports.
+
+use common::{
+    api::{DiagnosticsBundle, DiagnosticsResponse, PlatformInfo, MAX_DIAGNOSTICS_LOG_BYTES},
+    endpoints,
+    message::PROTOCOL_VERSION,
+};
+use leptos::{use_context, window};
+use wasm_bindgen::{JsCast, JsValue};
+use wasm_bindgen_futures::JsFuture;
+
+use crate::{networking::room_manager::RoomManager, Endpoint, LogProvider};
+
+fn platform_info() -> PlatformInfo {
+    let navigator = window().navigator();
+    PlatformInfo {
+        user_agent: navigator.user_agent().unwrap_or_default(),
+        platform: navigator.platform().unwrap_or_default(),
+        language: navigator.language().unwrap_or_default(),
+        native: use_context::<Endpoint>().is_some_and(|endpoint| endpoint.is_configurable),
+    }
+}
+
+/// Must be called from a reactive scope, the log buffer is read from context.
+///
+/// The log buffer grows for the whole session, only its tail is sent.
+pub async fn collect(room_manager: &RoomManager) -> DiagnosticsBundle {
+    let logs = use_context::<LogProvider>()
+        .map(|provider| provider.logs.get_value())
+        .unwrap_or_default();
+    let platform = platform_info();
+    let mut bundle = DiagnosticsBundle {
+        app_version: env!("CARGO_PKG_VERSION").to_string(),
+        protocol_version: PROTOCOL_VERSION,
+        platform,
+        room: room_manager.diagnostics_room(),
+        peers: room_manager.peer_stats().await,
+        logs,
+        logs_truncated: false,
+    };
+    bundle.truncate_logs(MAX_DIAGNOSTICS_LOG_BYTES);
+    bundle
+}
+
+/// Returns the reference code of the stored bundle.
+pub async fn upload(base: &str, bundle: &DiagnosticsBundle) -> Result<String, JsValue> {
+    let body = serde_json::to_string(bundle).map_err(|err| JsValue::from_str(&err.to_string()))?;
+    let headers = web_sys::Headers::new()?;
+    headers.set("Content-Type", "application/json")?;
+    let init = web_sys::RequestInit::new();
+    init.set_method("POST");
+    init.set_headers(&headers);
+    init.set_body(&JsValue::from_str(&body));
+    let response = JsFuture::from(
+        window().fetch_with_str_and_init(&format!("{base}{}", endpoints::DIAGNOSTICS), &init),
+    )
+    .await?;
+    let response: web_sys::Response = response.dyn_into()?;
+    match response.status() {
+        404 => return Err(JsValue::from_str("Diagnostics are disabled on this server")),
+        413 => return Err(JsValue::from_str("Diagnostics are too large to upload")),
+        _ if !response.ok() => {
+            return Err(JsValue::from_str(&format!(
+                "Server responded with {}",
+                response.status()
+            )))
+        }
+        _ => {}
+    }
+    let body = JsFuture::from(response.text()?).await?;
+    let body = body
+        .as_string()
+        .ok_or(JsValue::from_str("Invalid diagnostics response"))?;
+    let response: DiagnosticsResponse =
+        serde_json::from_str(&body).map_err(|err| JsValue::from_str(&err.to_string()))?;
+    Ok(response.code)
+}
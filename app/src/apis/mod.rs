@@ -1,3 +1,6 @@
+pub mod admin;
+pub mod diagnostics;
+
 use leptos::{server, use_context, ServerFnError};
 use serde::{Deserialize, Serialize};
 
@@ -16,7 +19,11 @@ pub async fn get_room_info(room_id: String) -> Result<Option<RoomMetaInfo>, Serv
 
     Ok(rooms
         .with_room(&room_id, |room| {
-            room.users.first().map(|host| RoomMetaInfo {
+            let host = room
+                .host
+                .and_then(|host| room.users.iter().find(|user| user.meta.id == host))
+                .or(room.users.first());
+            host.map(|host| RoomMetaInfo {
                 room_id: room_id.clone(),
                 host: host.meta.name.clone(),
                 selected_video: host
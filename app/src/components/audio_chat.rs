@@ -9,7 +9,7 @@ use web_sys::AudioContext;
 
 use crate::components::portal::Portal;
 use crate::components::video_chat::VideoChatManager;
-use crate::networking::room_manager::RoomManager;
+use crate::networking::room_manager::{RoomManager, RtcSupport};
 use crate::MountPoints;
 
 #[component]
@@ -34,6 +34,14 @@ pub fn AudioChat() -> impl IntoView {
         }
     });
     let user_ids = create_memo(move |_| users.get().into_iter().map(|u| u.id).collect::<Vec<_>>());
+    let rtc_support = create_memo({
+        let rm = rm.clone();
+        move |_| {
+            rm.get_room_info()
+                .with(|r| r.as_ref().map(|r| r.rtc_support))
+                .unwrap_or(RtcSupport::Unavailable)
+        }
+    });
 
     let acs = store_value(HashMap::new());
 
@@ -172,6 +180,7 @@ pub fn AudioChat() -> impl IntoView {
                             class="h-full w-full bg-black p-2 flex justify-center flex-col"
                         >
                             <button class="text-xs text-center"
+                                class=("hidden", move || !rtc_support.get().is_available())
                                 on:click=move|_|{
                                     set_video_manager_open.set(true);
                                 }
@@ -4,7 +4,7 @@ use tracing::info;
 use web_sys::ShareData;
 
 use crate::{
-    components::{icons::Icon, portal::Portal},
+    components::{icons::Icon, portal::Portal, user_badge::UserBadge},
     networking::room_manager::RoomManager,
     tauri_provider::{FullScreenProvider, ShareRequest},
     MountPoints,
@@ -100,7 +100,7 @@ pub fn ChatBox() -> impl IntoView {
                                                     if let Some((user, msg)) = msg {
                                                         view! {
                                                             <div class="w-full text-md font-thin14">
-                                                                <span class="font-thin8 text-md">{user.name} ": "</span>
+                                                                <span class="font-thin8 text-md"><UserBadge user=user /> ": "</span>
                                                                 <span>{msg}</span>
                                                             </div>
                                                         }
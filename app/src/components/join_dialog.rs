@@ -1,14 +1,26 @@
 use leptos::*;
 use tracing::{info, warn};
 
-use crate::{components::dialog::Dialog, networking::room_manager::RoomManager};
+use crate::{
+    components::{
+        dialog::Dialog,
+        profile::{use_profile, ProfileFields},
+    },
+    networking::room_manager::{JoinOptions, RoomManager},
+};
 
 #[component]
 pub fn JoinDialog(
     #[prop(into)] is_open: MaybeSignal<bool>,
     #[prop(into)] on_close: Callback<()>,
     #[prop(into)] init_room_code: MaybeSignal<String>,
+    /// Host token from a claim link of a room created through the API.
+    #[prop(optional, into)]
+    host_token: MaybeSignal<Option<String>>,
 ) -> impl IntoView {
+    let host_token = Signal::derive(move || host_token.get());
+    // Claiming the room as host needs neither the password nor a spectator seat.
+    let claiming = Signal::derive(move || host_token.with(Option::is_some));
     view! {
         <Dialog
             is_self_sized=false
@@ -18,8 +30,11 @@ pub fn JoinDialog(
             }
         >
             {
-                let (name, set_name) = create_signal(String::new());
+                let (saved_profile, set_saved_profile) = use_profile();
+                let profile = create_rw_signal(saved_profile.get_untracked());
                 let (room_code, set_room_code) = create_signal(init_room_code.get_untracked());
+                let (password, set_password) = create_signal(String::new());
+                let (spectator, set_spectator) = create_signal(false);
                 create_effect(move|_|{
                     set_room_code.set(init_room_code.get());
                 });
@@ -28,35 +43,53 @@ pub fn JoinDialog(
 
                     <div class="h-4" />
 
+                    <ProfileFields profile=profile />
+
                     <div class="flex items-center">
-                        <label class=" font-thin8 text-sm" for="name">
-                            "Name: "
+                        <label class=" font-thin8 text-sm" for="roomid">
+                            "Room Id: "
                         </label>
                         <input
                             class="bg-white/10 focus:outline-white/50  text-md font-thin8 p-2"
-                            name="name"
+                            name="roomid"
                             type="text"
-                            placeholder="Enter your name"
+                            placeholder="Room Id"
+                            prop:value=room_code
                             on:input=move |ev| {
-                                set_name.set(event_target_value(&ev));
+                                set_room_code.set(event_target_value(&ev));
                             }
                         />
                     </div>
 
-                    <div class="flex items-center">
-                        <label class=" font-thin8 text-sm" for="roomid">
-                            "Room Id: "
+                    <div class="flex items-center" class=("hidden", claiming)>
+                        <label class=" font-thin8 text-sm" for="password">
+                            "Password: "
                         </label>
                         <input
                             class="bg-white/10 focus:outline-white/50  text-md font-thin8 p-2"
-                            name="roomid"
-                            type="text"
-                            placeholder="Room Id"
-                            prop:value=room_code
+                            name="password"
+                            type="password"
+                            placeholder="If the room has one"
                             on:input=move |ev| {
-                                set_room_code.set(event_target_value(&ev));
+                                set_password.set(event_target_value(&ev));
+                            }
+                        />
+                    </div>
+
+                    <div class="flex items-center" class=("hidden", claiming)>
+                        <input
+                            class="mr-2"
+                            id="spectator"
+                            name="spectator"
+                            type="checkbox"
+                            prop:checked=spectator
+                            on:change=move |ev| {
+                                set_spectator.set(event_target_checked(&ev));
                             }
                         />
+                        <label class=" font-thin8 text-sm" for="spectator">
+                            "Join as spectator (watch and chat only)"
+                        </label>
                     </div>
 
                     <div class="h-4" />
@@ -65,16 +98,30 @@ pub fn JoinDialog(
                         class="text-sm hover:bg-white/20 self-center px-4 py-1"
                         type="button"
                         on:click=move |_| {
-                            if name.get_untracked().is_empty()
+                            let profile = profile.get_untracked();
+                            if profile.name.is_empty()
                                 || room_code.get_untracked().is_empty()
                             {
                                 warn!("Name cant be empty");
                             } else {
+                                set_saved_profile.set(profile.clone());
+                                let appearance = profile.appearance();
                                 let room_manager = expect_context::<RoomManager>();
                                 if let Err(err) = room_manager
                                     .host_join(
-                                        name.get_untracked(),
+                                        profile.name,
                                         Some(room_code.get_untracked()),
+                                        JoinOptions {
+                                            password: Some(password.get_untracked())
+                                                .filter(|p| !p.is_empty()),
+                                            host_token: host_token.get_untracked(),
+                                            role: if spectator.get_untracked() {
+                                                common::UserRole::Spectator
+                                            } else {
+                                                common::UserRole::Member
+                                            },
+                                            appearance,
+                                        },
                                     )
                                 {
                                     warn!("Cannot join {err:#?}");
@@ -5,7 +5,11 @@ pub mod help_dialog;
 pub mod icons;
 pub mod join_dialog;
 pub mod portal;
+pub mod profile;
 pub mod room_info;
+pub mod server_picker;
 pub mod toaster;
+pub mod update_prompt;
+pub mod user_badge;
 pub mod video_chat;
 pub mod video_player;
@@ -0,0 +1,200 @@
+use codee::string::JsonSerdeCodec;
+use common::{is_hex_color, Appearance, Avatar};
+use leptos::*;
+use leptos_use::storage::use_local_storage;
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+use wasm_bindgen::{JsCast, JsValue};
+use wasm_bindgen_futures::JsFuture;
+use web_sys::{
+    js_sys::{Function, Promise},
+    CanvasRenderingContext2d, File, HtmlCanvasElement, HtmlImageElement, Url,
+};
+
+use crate::components::{
+    toaster::{Toast, ToastType, Toaster},
+    user_badge::UserAvatar,
+};
+
+const PROFILE_STORAGE_KEY: &str = "tvmate_profile";
+/// Uploaded avatars are cropped to a square this many pixels wide.
+const AVATAR_IMAGE_SIZE: u32 = 64;
+
+/// What the user entered last time, pre-fills the host and join dialogs.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Profile {
+    pub name: String,
+    /// Css hex colour, `#rrggbb`.
+    pub color: String,
+    /// A single emoji, empty when unset.
+    pub avatar: String,
+    /// Data url of an uploaded image, shown instead of the emoji.
+    pub avatar_image: Option<String>,
+}
+
+impl Default for Profile {
+    fn default() -> Self {
+        Self {
+            name: String::new(),
+            color: "#ffffff".to_string(),
+            avatar: String::new(),
+            avatar_image: None,
+        }
+    }
+}
+
+impl Profile {
+    pub fn appearance(&self) -> Appearance {
+        let avatar = match (&self.avatar_image, self.avatar.trim()) {
+            (Some(image), _) => Some(Avatar::Image(image.clone())),
+            (None, "") => None,
+            (None, emoji) => Some(Avatar::Emoji(emoji.to_string())),
+        };
+        Appearance {
+            color: Some(self.color.clone()).filter(|c| is_hex_color(c)),
+            avatar,
+        }
+    }
+}
+
+/// The profile saved in local storage.
+pub fn use_profile() -> (Signal<Profile>, WriteSignal<Profile>) {
+    let (profile, set_profile, _) =
+        use_local_storage::<Profile, JsonSerdeCodec>(PROFILE_STORAGE_KEY);
+    (profile, set_profile)
+}
+
+/// Crops the centre square of the picked image and scales it down to a small jpeg data url.
+async fn avatar_from_file(file: &File) -> Result<String, JsValue> {
+    let image = HtmlImageElement::new()?;
+    let loaded = Promise::new(&mut |resolve: Function, reject: Function| {
+        image.set_onload(Some(&resolve));
+        image.set_onerror(Some(&reject));
+    });
+    let url = Url::create_object_url_with_blob(file)?;
+    image.set_src(&url);
+    let result = JsFuture::from(loaded).await;
+    Url::revoke_object_url(&url)?;
+    result.map_err(|_| JsValue::from_str("Cannot read image"))?;
+
+    let canvas: HtmlCanvasElement = document().create_element("canvas")?.dyn_into()?;
+    canvas.set_width(AVATAR_IMAGE_SIZE);
+    canvas.set_height(AVATAR_IMAGE_SIZE);
+    let context: CanvasRenderingContext2d = canvas
+        .get_context("2d")?
+        .ok_or(JsValue::from_str("No canvas context"))?
+        .dyn_into()?;
+    let (width, height) = (
+        f64::from(image.natural_width()),
+        f64::from(image.natural_height()),
+    );
+    let side = width.min(height);
+    let size = f64::from(AVATAR_IMAGE_SIZE);
+    context.draw_image_with_html_image_element_and_sw_and_sh_and_dx_and_dy_and_dw_and_dh(
+        &image,
+        (width - side) / 2.0,
+        (height - side) / 2.0,
+        side,
+        side,
+        0.0,
+        0.0,
+        size,
+        size,
+    )?;
+    canvas.to_data_url_with_type_and_encoder_options("image/jpeg", &JsValue::from_f64(0.8))
+}
+
+/// Name, colour and avatar inputs, the caller decides when to save.
+#[component]
+pub fn ProfileFields(profile: RwSignal<Profile>) -> impl IntoView {
+    view! {
+        <div class="flex items-center">
+            <label class=" font-thin8 text-sm" for="name">
+                "Name: "
+            </label>
+            <input
+                class="bg-white/10 focus:outline-white/50  text-md font-thin8 p-2"
+                name="name"
+                type="text"
+                placeholder="Enter your name"
+                prop:value=move || profile.with(|p| p.name.clone())
+                on:input=move |ev| {
+                    profile.update(|p| p.name = event_target_value(&ev));
+                }
+            />
+        </div>
+
+        <div class="flex items-center gap-2">
+            <label class=" font-thin8 text-sm" for="color">
+                "Colour: "
+            </label>
+            <input
+                class="bg-transparent h-8 w-10"
+                name="color"
+                type="color"
+                prop:value=move || profile.with(|p| p.color.clone())
+                on:input=move |ev| {
+                    profile.update(|p| p.color = event_target_value(&ev));
+                }
+            />
+            <label class=" font-thin8 text-sm" for="avatar">
+                "Avatar: "
+            </label>
+            <input
+                class="bg-white/10 focus:outline-white/50  text-md font-thin8 p-2 w-16 text-center"
+                name="avatar"
+                type="text"
+                placeholder="🙂"
+                prop:value=move || profile.with(|p| p.avatar.clone())
+                on:input=move |ev| {
+                    profile.update(|p| p.avatar = event_target_value(&ev));
+                }
+            />
+        </div>
+
+        <div class="flex items-center gap-2">
+            <label class="text-sm cursor-pointer">
+                "[ Image ]"
+                <input
+                    class="hidden"
+                    type="file"
+                    accept="image/*"
+                    on:change=move |ev| {
+                        let input = event_target::<web_sys::HtmlInputElement>(&ev);
+                        let Some(file) = input.files().and_then(|files| files.get(0)) else {
+                            return;
+                        };
+                        input.set_value("");
+                        let toaster = expect_context::<Toaster>();
+                        spawn_local(async move {
+                            match avatar_from_file(&file).await {
+                                Ok(image) => profile.update(|p| p.avatar_image = Some(image)),
+                                Err(err) => {
+                                    warn!("Cannot load avatar {err:?}");
+                                    toaster.toast(Toast {
+                                        message: err
+                                            .as_string()
+                                            .unwrap_or("Cannot load image".to_string())
+                                            .into(),
+                                        r#type: ToastType::Failed,
+                                    });
+                                }
+                            }
+                        });
+                    }
+                />
+            </label>
+            {move || profile.with(|p| p.avatar_image.is_some()).then(|| view! {
+                <UserAvatar avatar=Signal::derive(move || profile.with(|p| p.appearance().avatar)) />
+                <button
+                    class="text-sm"
+                    type="button"
+                    on:click=move |_| profile.update(|p| p.avatar_image = None)
+                >
+                    "[ Remove ]"
+                </button>
+            })}
+        </div>
+    }
+}
@@ -2,16 +2,65 @@ use leptos::*;
 use tracing::warn;
 use web_sys::ShareData;
 
+use crate::apis::diagnostics;
 use crate::components::icons::Icon;
 use crate::components::portal::Portal;
+use crate::components::profile::use_profile;
+use crate::components::toaster::{Toast, ToastType, Toaster};
+use crate::components::user_badge::UserBadge;
 use crate::networking::room_manager::RoomManager;
 use crate::tauri_provider::{FullScreenProvider, ShareRequest};
-use crate::MountPoints;
+use crate::{Endpoint, MountPoints};
 
 #[component]
 pub fn RoomInfo() -> impl IntoView {
     let room_manager = expect_context::<RoomManager>();
     let room_info = room_manager.get_room_info();
+    let overlay_url = room_manager.overlay_url;
+    let (diagnostics_code, set_diagnostics_code) = create_signal(None::<String>);
+    let (sending_diagnostics, set_sending_diagnostics) = create_signal(false);
+    let (renaming, set_renaming) = create_signal(false);
+    let new_name = create_rw_signal(String::new());
+    let (_, set_saved_profile) = use_profile();
+    let rename = move || {
+        let name = new_name.get_untracked().trim().to_string();
+        if !name.is_empty() {
+            expect_context::<RoomManager>().set_name(name.clone());
+            // Next visit starts with the name picked here.
+            set_saved_profile.update(|p| p.name = name);
+        }
+        set_renaming.set(false);
+    };
+    let endpoint = expect_context::<Endpoint>();
+    let send_diagnostics = move |_: ev::MouseEvent| {
+        let room_manager = expect_context::<RoomManager>();
+        let toaster = expect_context::<Toaster>();
+        let base = endpoint.http_endpoint();
+        set_sending_diagnostics.set(true);
+        spawn_local(async move {
+            let bundle = diagnostics::collect(&room_manager).await;
+            match diagnostics::upload(&base, &bundle).await {
+                Ok(code) => {
+                    toaster.toast(Toast {
+                        message: format!("Diagnostics sent, reference {code}").into(),
+                        r#type: ToastType::Success,
+                    });
+                    set_diagnostics_code.set(Some(code));
+                }
+                Err(err) => {
+                    warn!("Cannot send diagnostics {err:?}");
+                    toaster.toast(Toast {
+                        message: err
+                            .as_string()
+                            .unwrap_or("Cannot send diagnostics".to_string())
+                            .into(),
+                        r#type: ToastType::Failed,
+                    });
+                }
+            }
+            set_sending_diagnostics.set(false);
+        });
+    };
     view! {
         {move || {
             let mount_points = expect_context::<MountPoints>();
@@ -40,22 +89,89 @@ pub fn RoomInfo() -> impl IntoView {
                                 .with(|r| r.as_ref().map(|r| r.users.clone()))
                                 .unwrap_or_default()
                                 .into_iter()
+                                .filter(|user| !user.role.is_spectator())
                                 .map(|user| {
+                                    let status = match user.state {
+                                        common::UserState::VideoNotSelected => "⌛",
+                                        common::UserState::VideoSelected(_) => "✔️",
+                                    };
                                     view! {
                                         <div
                                             class="text-left w-full mt-2 break-words"
                                         >
                                             "> "
-                                            {user.name}
-                                            {match user.state {
-                                                common::UserState::VideoNotSelected => "⌛",
-                                                common::UserState::VideoSelected(_) => "✔️",
-                                            }}
+                                            <UserBadge user=user />
+                                            {status}
                                         </div>
                                     }
                                 })
                                 .collect::<Vec<_>>()
                         }}
+                        {move || {
+                            let spectators = room_info
+                                .with(|r| r.as_ref().map(|r| r.users.clone()))
+                                .unwrap_or_default()
+                                .into_iter()
+                                .filter(|user| user.role.is_spectator())
+                                .collect::<Vec<_>>();
+                            if spectators.is_empty() {
+                                return view! {}.into_view();
+                            }
+                            view! {
+                                <div class="h-3" />
+                                <div class="text-xs font-thin8 text-center">"Spectators"</div>
+                                {spectators
+                                    .into_iter()
+                                    .map(|user| {
+                                        view! {
+                                            <div class="text-left w-full mt-2 break-words text-white/60">
+                                                "👁 "
+                                                <UserBadge user=user />
+                                            </div>
+                                        }
+                                    })
+                                    .collect::<Vec<_>>()}
+                            }
+                                .into_view()
+                        }}
+                        <div class="h-3" />
+                        {move || if renaming.get() {
+                            view! {
+                                <input
+                                    class="w-full bg-black text-sm border border-white p-1"
+                                    type="text"
+                                    prop:value=new_name
+                                    on:input=move |ev| new_name.set(event_target_value(&ev))
+                                    on:keydown=move |ev| {
+                                        if ev.key() == "Enter" {
+                                            rename();
+                                        }
+                                    }
+                                />
+                                <button class="text-sm mt-2" on:click=move |_| rename()>
+                                    "[ Save ]"
+                                </button>
+                            }
+                                .into_view()
+                        } else {
+                            view! {
+                                <button class="text-sm"
+                                    disabled=move || room_info.with(Option::is_none)
+                                    on:click=move |_| {
+                                        let name = room_info.with_untracked(|r| {
+                                            r.as_ref().and_then(|r| {
+                                                r.users.iter().find(|u| u.id == r.user_id).map(|u| u.name.clone())
+                                            })
+                                        });
+                                        new_name.set(name.unwrap_or_default());
+                                        set_renaming.set(true);
+                                    }
+                                >
+                                    "[ Rename ]"
+                                </button>
+                            }
+                                .into_view()
+                        }}
                         <div class="h-3" />
                         <hr class="border-white border-t w-full" />
                         <div class="h-3" />
@@ -85,6 +201,51 @@ pub fn RoomInfo() -> impl IntoView {
                                                 "Invite"
                                             </span>
                                         </button>
+                                        <div class="h-3" />
+                                        <button class="text-sm"
+                                            title="Read-only feed of this room for stream overlays, host only"
+                                            on:click=move|_|{
+                                                let rm = expect_context::<RoomManager>();
+                                                rm.send_message(
+                                                    common::message::ClientMessage::CreateOverlayToken,
+                                                    crate::networking::room_manager::SendType::Reliable,
+                                                );
+                                            }
+                                        >
+                                            "[ Overlay Link ]"
+                                        </button>
+                                        {move || overlay_url.get().map(|url| view! {
+                                            <input
+                                                class="w-full mt-2 bg-black text-xs border border-white p-1"
+                                                readonly
+                                                value=url
+                                                on:focus=move |ev| {
+                                                    event_target::<web_sys::HtmlInputElement>(&ev).select();
+                                                }
+                                            />
+                                        })}
+                                        <div class="h-3" />
+                                        <button class="text-sm"
+                                            title="Upload logs and connection details, quote the code when reporting a bug"
+                                            disabled=sending_diagnostics
+                                            on:click=send_diagnostics
+                                        >
+                                            {move || if sending_diagnostics.get() {
+                                                "[ Sending... ]"
+                                            } else {
+                                                "[ Send Diagnostics ]"
+                                            }}
+                                        </button>
+                                        {move || diagnostics_code.get().map(|code| view! {
+                                            <input
+                                                class="w-full mt-2 bg-black text-xs border border-white p-1 text-center"
+                                                readonly
+                                                value=code
+                                                on:focus=move |ev| {
+                                                    event_target::<web_sys::HtmlInputElement>(&ev).select();
+                                                }
+                                            />
+                                        })}
                                     }.into_view()
                                 }else{
                                     view! {}.into_view()
@@ -0,0 +1,194 @@
+use std::{cell::RefCell, collections::HashMap};
+
+use codee::string::FromToStringCodec;
+use common::{endpoints, message::PROTOCOL_VERSION, version::VersionInfo};
+use leptos::*;
+use leptos_use::storage::use_local_storage;
+use tracing::{info, warn};
+use wasm_bindgen::{JsCast, JsValue};
+use wasm_bindgen_futures::JsFuture;
+
+use crate::{
+    components::{
+        dialog::Dialog,
+        toaster::{Toast, ToastType, Toaster},
+    },
+    utils::{http_to_ws, ws_to_http},
+    Endpoint,
+};
+
+const SERVER_STORAGE_KEY: &str = "tvmate_server";
+
+thread_local! {
+    /// Server urls already handed to server functions, each one is leaked only once.
+    static SERVER_URLS: RefCell<HashMap<String, &'static str>> = RefCell::default();
+}
+
+/// Server functions only accept a static url, switching back and forth reuses the leaked one.
+fn static_server_url(url: String) -> &'static str {
+    SERVER_URLS.with_borrow_mut(|urls| {
+        *urls
+            .entry(url)
+            .or_insert_with_key(|url| Box::leak(url.clone().into_boxed_str()))
+    })
+}
+
+/// Restores the server picked in [`ServerPicker`] and keeps server functions pointed at it.
+pub fn use_saved_endpoint(endpoint: Endpoint) {
+    let (saved, _, _) = use_local_storage::<String, FromToStringCodec>(SERVER_STORAGE_KEY);
+    let saved = saved.get_untracked();
+    if !saved.is_empty() {
+        info!("Using saved server {saved}");
+        endpoint.main_endpoint.set(saved.into());
+    }
+    create_effect(move |_| {
+        let http = ws_to_http(&endpoint.main_endpoint.get());
+        leptos::server_fn::client::set_server_url(static_server_url(http));
+    });
+}
+
+/// Accepts `host`, `http(s)://host` or `ws(s)://host` and returns the websocket base url.
+fn normalize_endpoint(input: &str) -> Option<String> {
+    let input = input.trim().trim_end_matches('/');
+    if input.is_empty() {
+        return None;
+    }
+    if input.starts_with("ws://") || input.starts_with("wss://") {
+        Some(input.to_string())
+    } else if input.starts_with("http://") || input.starts_with("https://") {
+        Some(http_to_ws(input))
+    } else {
+        Some(format!("wss://{input}"))
+    }
+}
+
+async fn fetch_version(endpoint: &str) -> Result<VersionInfo, JsValue> {
+    let url = format!("{}{}", ws_to_http(endpoint), endpoints::VERSION);
+    let response = JsFuture::from(window().fetch_with_str(&url)).await?;
+    let response: web_sys::Response = response.dyn_into()?;
+    if !response.ok() {
+        return Err(JsValue::from_str(&format!(
+            "Server responded with {}",
+            response.status()
+        )));
+    }
+    let body = JsFuture::from(response.text()?).await?;
+    let body = body
+        .as_string()
+        .ok_or(JsValue::from_str("Invalid version response"))?;
+    serde_json::from_str(&body).map_err(|err| JsValue::from_str(&err.to_string()))
+}
+
+#[component]
+pub fn ServerPicker(
+    #[prop(into)] is_open: MaybeSignal<bool>,
+    #[prop(into)] on_close: Callback<()>,
+) -> impl IntoView {
+    let endpoint = expect_context::<Endpoint>();
+    let (_, set_saved, _) = use_local_storage::<String, FromToStringCodec>(SERVER_STORAGE_KEY);
+
+    let (server, set_server) = create_signal(endpoint.main_endpoint.get_untracked().to_string());
+    create_effect(move |_| {
+        if is_open.get() {
+            set_server.set(endpoint.main_endpoint.get_untracked().to_string());
+        }
+    });
+    let (is_checking, set_is_checking) = create_signal(false);
+
+    view! {
+        <Dialog
+            is_self_sized=false
+            is_open=is_open
+            on_close=move |_| {
+                on_close.call(());
+            }
+        >
+            <h3 class="font-bold2  text-xl text-center w-full">"Server"</h3>
+
+            <div class="h-4" />
+
+            <div class="flex items-center">
+                <label class=" font-thin8 text-sm" for="server">
+                    "Server: "
+                </label>
+                <input
+                    class="bg-white/10 focus:outline-white/50  text-md font-thin8 p-2"
+                    name="server"
+                    type="text"
+                    placeholder="wss://tvmate.example.com"
+                    prop:value=server
+                    on:input=move |ev| {
+                        set_server.set(event_target_value(&ev));
+                    }
+                />
+            </div>
+
+            <div class="h-4" />
+
+            <div class="flex gap-4 justify-center">
+                <button
+                    class="text-sm hover:bg-white/20 self-center px-4 py-1"
+                    type="button"
+                    disabled=is_checking
+                    on:click=move |_| {
+                        let toaster = expect_context::<Toaster>();
+                        let Some(new_endpoint) = normalize_endpoint(&server.get_untracked()) else {
+                            toaster.toast(Toast {
+                                message: "Server cannot be empty".into(),
+                                r#type: ToastType::Failed,
+                            });
+                            return;
+                        };
+                        set_is_checking.set(true);
+                        toaster.toast(Toast {
+                            message: "Checking server".into(),
+                            r#type: ToastType::Info,
+                        });
+                        leptos::spawn_local(async move {
+                            match fetch_version(&new_endpoint).await {
+                                Ok(version) if !version.accepts(PROTOCOL_VERSION) => {
+                                    warn!("Server {new_endpoint} is incompatible {version:?}");
+                                    toaster.toast(Toast {
+                                        message: format!("Server runs incompatible {} {}", version.name, version.version).into(),
+                                        r#type: ToastType::Failed,
+                                    });
+                                }
+                                Ok(version) => {
+                                    info!("Server {new_endpoint} is {version:?}");
+                                    set_saved.set(new_endpoint.clone());
+                                    endpoint.main_endpoint.set(new_endpoint.into());
+                                    toaster.toast(Toast {
+                                        message: format!("Connected to {} {}", version.name, version.version).into(),
+                                        r#type: ToastType::Success,
+                                    });
+                                    on_close.call(());
+                                }
+                                Err(err) => {
+                                    warn!("Server check failed {err:?}");
+                                    toaster.toast(Toast {
+                                        message: "Server not reachable, not saved".into(),
+                                        r#type: ToastType::Failed,
+                                    });
+                                }
+                            }
+                            set_is_checking.set(false);
+                        });
+                    }
+                >
+                    "[ Save ]"
+                </button>
+                <button
+                    class="text-sm hover:bg-white/20 self-center px-4 py-1"
+                    type="button"
+                    on:click=move |_| {
+                        set_saved.set(String::new());
+                        endpoint.main_endpoint.set(endpoint.default_endpoint());
+                        on_close.call(());
+                    }
+                >
+                    "[ Reset ]"
+                </button>
+            </div>
+        </Dialog>
+    }
+}
@@ -0,0 +1,49 @@
+use leptos::*;
+
+use crate::{components::dialog::Dialog, networking::room_manager::RoomManager};
+
+/// Shown when the server closes the connection because this build speaks an older protocol.
+#[component]
+pub fn UpdatePrompt() -> impl IntoView {
+    let room_manager = expect_context::<RoomManager>();
+    let update_required = room_manager.update_required;
+
+    view! {
+        <div class="fixed bg-black/50 top-0 left-0 h-full w-full flex items-center justify-center p-6 z-50"
+            class=("hidden", move || !update_required.get())
+        >
+            <div class="lg:max-w-[60%] h-fit max-h-full overflow-auto text-white">
+                <Dialog
+                    is_self_sized=true
+                    is_open=true
+                    on_close=move |_| {
+                        update_required.set(false);
+                    }
+                >
+                    <h3 class="font-bold2 text-xl text-center w-full">"Update required"</h3>
+                    <div class="h-4" />
+                    <div class="text-sm text-center">
+                        {if cfg!(feature = "csr") {
+                            "The server runs a newer version of TVMate, please install the latest app to continue."
+                        } else {
+                            "TVMate was updated, reload the page to continue."
+                        }}
+                    </div>
+                    <div class="h-4" />
+                    <button
+                        class="text-sm hover:bg-white/20 self-center px-4 py-1"
+                        type="button"
+                        on:click=move |_| {
+                            update_required.set(false);
+                            if !cfg!(feature = "csr") {
+                                let _ = window().location().reload();
+                            }
+                        }
+                    >
+                        {if cfg!(feature = "csr") { "[ Ok ]" } else { "[ Reload ]" }}
+                    </button>
+                </Dialog>
+            </div>
+        </div>
+    }
+}
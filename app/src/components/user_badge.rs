@@ -0,0 +1,42 @@
+use common::{is_hex_color, Appearance, Avatar, UserMeta};
+use leptos::*;
+
+/// The user's colour, `None` when they have not picked a valid one.
+pub fn user_color(appearance: &Appearance) -> Option<&str> {
+    appearance.color.as_deref().filter(|c| is_hex_color(c))
+}
+
+/// Emoji or image avatar, nothing when the user has none.
+#[component]
+pub fn UserAvatar(
+    #[prop(into)] avatar: MaybeSignal<Option<Avatar>>,
+    #[prop(optional)] class: &'static str,
+) -> impl IntoView {
+    move || match avatar.get() {
+        Some(Avatar::Emoji(emoji)) => view! { <span class=class>{emoji}</span> }.into_view(),
+        Some(Avatar::Image(url)) => view! {
+            <img
+                class=format!("inline-block w-5 h-5 rounded-full object-cover align-middle {class}")
+                src=url
+                alt=""
+            />
+        }
+        .into_view(),
+        None => view! {}.into_view(),
+    }
+}
+
+/// Avatar followed by the name in the user's colour, used wherever users are shown.
+#[component]
+pub fn UserBadge(#[prop(into)] user: MaybeSignal<UserMeta>) -> impl IntoView {
+    let user = Signal::derive(move || user.get());
+    view! {
+        <UserAvatar
+            avatar=Signal::derive(move || user.with(|u| u.appearance.avatar.clone()))
+            class="mr-1"
+        />
+        <span style=move || {
+            user.with(|u| user_color(&u.appearance).map(|c| format!("color: {c}")))
+        }>{move || user.with(|u| u.name.clone())}</span>
+    }
+}
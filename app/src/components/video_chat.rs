@@ -13,8 +13,9 @@ use crate::{
         dialog::Dialog,
         icons::Icon,
         toaster::{Toast, Toaster},
+        user_badge::{user_color, UserBadge},
     },
-    networking::room_manager::RoomManager,
+    networking::room_manager::{RoomManager, RtcSupport},
     MountPoints,
 };
 
@@ -40,14 +41,24 @@ pub fn VideoChat() -> impl IntoView {
     let room_info = rm.get_room_info();
     let owner = Owner::current().unwrap();
 
+    // Spectators cannot take part in calls.
     let users = create_memo(move |_| {
-        room_info
+        let mut users = room_info
             .with(|r| r.as_ref().map(|r| r.users.clone()))
-            .unwrap_or_default()
+            .unwrap_or_default();
+        users.retain(|u| !u.role.is_spectator());
+        users
     });
 
     create_effect(move |_| {
         let users = users.get();
+        // Names and avatars change while in the room.
+        let existing = video_users.get_untracked();
+        for user in &users {
+            if let Some(video_user) = existing.get(&user.id) {
+                video_user.user_meta.set(user.clone());
+            }
+        }
         set_video_users.update(|video_user| {
             video_user.retain(|&k, _| users.iter().any(|u| u.id == k));
 
@@ -303,13 +314,24 @@ pub fn VideoChat() -> impl IntoView {
                                             if let Some(user) = user.get() {
                                                 let video_ref= user.video_ref;
                                                 let is_video_active = user.is_video_active;
+                                                let user_meta = user.user_meta;
                                                 view! {
-                                                    <video ref={video_ref}
-                                                        playsinline=true
-                                                        disableRemotePlayback=true
-                                                        class="w-full -scale-x-100"
+                                                    <div
+                                                        class="relative border-2 border-transparent rounded-md overflow-hidden"
                                                         class=("hidden", move || !is_video_active.get())
-                                                    />
+                                                        style=move || user_meta.with(|u| {
+                                                            user_color(&u.appearance).map(|c| format!("border-color: {c}"))
+                                                        })
+                                                    >
+                                                        <video ref={video_ref}
+                                                            playsinline=true
+                                                            disableRemotePlayback=true
+                                                            class="w-full -scale-x-100"
+                                                        />
+                                                        <div class="absolute left-1 bottom-1 px-1 rounded-sm bg-black/60 text-xs">
+                                                            <UserBadge user=user_meta />
+                                                        </div>
+                                                    </div>
                                                 }.into_view()
                                             }else{
                                                 view! {}.into_view()
@@ -352,7 +374,11 @@ pub fn VideoChatManager(
         if let Some(room_info) = room_info.get() {
             let vu = video_users.get_untracked();
             let mut new_users = HashMap::new();
-            for user in room_info.users {
+            for user in room_info
+                .users
+                .into_iter()
+                .filter(|u| !u.role.is_spectator())
+            {
                 if let Some(user_v) = vu.get(&user.id) {
                     let user_id = user.id;
                     user_v.meta.set(user);
@@ -399,6 +425,16 @@ pub fn VideoChatManager(
                         <div class="text-center">
                             "Video/Audio Call"
                         </div>
+                        <div
+                            class="text-center text-xs text-yellow-500"
+                            class=("hidden", move || {
+                                room_info.with(|r| {
+                                    r.as_ref().map(|r| r.rtc_support) != Some(RtcSupport::NoRelay)
+                                })
+                            })
+                        >
+                            "No relay server available, calls may not connect across networks"
+                        </div>
                         <div class="h-4" />
                         <For
                             each=move||{
@@ -434,7 +470,7 @@ pub fn VideoChatManager(
                                     }else{
                                         view! {
                                             <div class="flex gap-4 items-center">
-                                                <div class="text-lg"> { move || user.meta.get().name } </div>
+                                                <div class="text-lg"> <UserBadge user=user.meta /> </div>
                                                 <div class="flex-grow min-w-6" />
                                                 <div class="flex gap-3">
                                                     <button class="flex flex-row hover:bg-white/20 px-4 py-1 gap-2 items-center"
@@ -609,7 +645,7 @@ pub fn VideoChatConsent() -> impl IntoView {
                                             <Icon icon={if request.1 {crate::components::icons::Icons::Video}else {crate::components::icons::Icons::Mic}} />
                                         </span>
                                         <span>
-                                            {request.0.name}
+                                            <UserBadge user=request.0.clone() />
                                         </span>
                                     </div>
                                     <div class="h-6" />
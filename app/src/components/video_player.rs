@@ -14,7 +14,10 @@ use wasm_bindgen::JsCast;
 use web_sys::{Element, MediaStream};
 
 use crate::{
-    components::toaster::{Toast, ToastType, Toaster},
+    components::{
+        toaster::{Toast, ToastType, Toaster},
+        user_badge::UserBadge,
+    },
     networking::room_manager::RoomManager,
     tauri_provider::FullScreenProvider,
     utils::download_logs,
@@ -502,7 +505,7 @@ pub fn VideoPlayer(#[prop(into)] src: Signal<Option<VideoSource>>) -> impl IntoV
                                         if let Some((user, msg)) = msg {
                                             view! {
                                                 <div class="w-full text-md font-thin14 [text-shadow:_0_1px_0_rgb(0_0_0_/_40%)]">
-                                                    <span class="font-thin8 text-sm">{user.name} ": "</span>
+                                                    <span class="font-thin8 text-sm"><UserBadge user=user /> ": "</span>
                                                     <span>{msg}</span>
                                                 </div>
                                             }
@@ -3,7 +3,7 @@ use std::borrow::Cow;
 use crate::error_template::{AppError, ErrorTemplate};
 
 use cfg_if::cfg_if;
-use components::toaster::ToasterWrapper;
+use components::{toaster::ToasterWrapper, update_prompt::UpdatePrompt};
 use leptos::*;
 use leptos_meta::*;
 use leptos_router::*;
@@ -11,7 +11,7 @@ use leptos_use::{use_window_size, UseWindowSizeReturn};
 use networking::room_manager::RoomManager;
 use pages::room::RoomPage;
 
-use crate::pages::home_page::HomePage;
+use crate::pages::{admin::AdminPage, home_page::HomePage};
 
 pub mod apis;
 pub mod components;
@@ -31,9 +31,39 @@ pub struct MountPoints {
     pub main_point: NodeRef<leptos::html::Div>,
 }
 
-#[derive(Clone)]
+#[derive(Clone, Copy)]
 pub struct Endpoint {
-    pub main_endpoint: Cow<'static, str>,
+    pub main_endpoint: RwSignal<Cow<'static, str>>,
+    /// Native builds let the user pick another server, the web build always talks to its origin.
+    pub is_configurable: bool,
+    default_endpoint: StoredValue<Cow<'static, str>>,
+}
+
+impl Endpoint {
+    pub fn new(main_endpoint: impl Into<Cow<'static, str>>) -> Self {
+        let main_endpoint = main_endpoint.into();
+        Self {
+            default_endpoint: store_value(main_endpoint.clone()),
+            main_endpoint: create_rw_signal(main_endpoint),
+            is_configurable: false,
+        }
+    }
+
+    pub fn configurable(default_endpoint: impl Into<Cow<'static, str>>) -> Self {
+        Self {
+            is_configurable: true,
+            ..Self::new(default_endpoint)
+        }
+    }
+
+    pub fn default_endpoint(&self) -> Cow<'static, str> {
+        self.default_endpoint.get_value()
+    }
+
+    /// Base url for plain http requests against the selected server.
+    pub fn http_endpoint(&self) -> String {
+        utils::ws_to_http(&self.main_endpoint.get_untracked())
+    }
 }
 
 #[derive(Clone)]
@@ -46,6 +76,10 @@ pub fn App() -> impl IntoView {
     // Provides context that manages stylesheets, titles, meta tags, etc.
     provide_meta_context();
 
+    if let Some(endpoint) = use_context::<Endpoint>().filter(|e| e.is_configurable) {
+        components::server_picker::use_saved_endpoint(endpoint);
+    }
+
     let room_manager = RoomManager::new(Owner::current().unwrap());
 
     provide_context(room_manager);
@@ -121,6 +155,7 @@ pub fn App() -> impl IntoView {
                         >
                             <Routes>
                                 <Route path="" view=HomePage />
+                                <Route path="admin" view=AdminPage />
                                 <Route ssr=SsrMode::PartiallyBlocked path="room/:id" view=RoomPage />
                             </Routes>
                         </div>
@@ -137,6 +172,7 @@ pub fn App() -> impl IntoView {
                 </div>
 
                 <div ref=side_point></div>
+                <UpdatePrompt />
             </main>
             </ToasterWrapper>
         </Router>
@@ -1,19 +1,21 @@
-use std::{cell::RefCell, collections::HashMap, marker::PhantomData, rc::Rc};
+use std::{cell::RefCell, collections::HashMap, marker::PhantomData, rc::Rc, time::Duration};
 
 use codee::binary::BincodeSerdeCodec;
 use common::{
+    api::{DiagnosticsRoom, PeerStats},
+    encoding::Encoding,
     endpoints,
     message::{
-        ClientMessage, Message, OfferReason, RTCSessionDesc, RtcConfig, UserJoined, UserLeft,
-        VideoMeta,
+        ClientMessage, ErrorCode, Message, OfferReason, RTCSessionDesc, RtcConfig, UserJoined,
+        UserLeft, VideoMeta, CLOSE_CODE_SERVER_RESTART, PROTOCOL_VERSION,
     },
     params::{HostParams, JoinParams},
-    PlayerStatus, UserMeta, UserState,
+    Appearance, PlayerStatus, UserMeta, UserRole, UserState,
 };
 use leptos::{
-    create_effect, create_rw_signal, create_signal, expect_context, logging::warn, store_value,
-    with_owner, Callback, NodeRef, Owner, ReadSignal, RwSignal, Signal, SignalGet,
-    SignalGetUntracked, SignalSet, SignalSetUntracked, SignalUpdate, SignalWith,
+    create_effect, create_rw_signal, create_signal, expect_context, logging::warn, set_timeout,
+    store_value, window, with_owner, Callback, NodeRef, Owner, ReadSignal, RwSignal, Signal,
+    SignalGet, SignalGetUntracked, SignalSet, SignalSetUntracked, SignalUpdate, SignalWith,
     SignalWithUntracked, StoredValue, WriteSignal,
 };
 use leptos_router::use_navigate;
@@ -24,7 +26,11 @@ use thiserror::Error;
 use tracing::info;
 use uuid::Uuid;
 use wasm_bindgen::{JsCast, JsValue};
-use web_sys::{MediaStream, MediaStreamTrack, RtcPeerConnection, WebSocket};
+use wasm_bindgen_futures::JsFuture;
+use web_sys::{
+    js_sys::{Object, JSON},
+    MediaStream, MediaStreamTrack, RtcPeerConnection, WebSocket,
+};
 
 use crate::{
     components::toaster::{Toast, Toaster},
@@ -85,9 +91,27 @@ pub struct RoomManager {
     share_video_permission_tx: WriteSignal<Option<Uuid>>,
 
     video_offer_type: StoredValue<OfferReason>,
+
+    /// Set when the server rejected this build's protocol version.
+    pub update_required: RwSignal<bool>,
+    /// Event stream url for stream overlays, set once the host asked for one.
+    pub overlay_url: RwSignal<Option<String>>,
+    /// Room to rejoin when the server restarts underneath us.
+    resume: StoredValue<Option<ResumeInfo>>,
     owner: Owner,
 }
 
+#[derive(Clone)]
+struct ResumeInfo {
+    name: String,
+    room_id: String,
+    options: JoinOptions,
+    attempts: u32,
+}
+
+/// Rejoin attempts after a restart, each waits a second longer than the previous one.
+const MAX_RESUME_ATTEMPTS: u32 = 5;
+
 pub enum RoomState<Tx>
 where
     Tx: 'static,
@@ -153,7 +177,7 @@ where
         ReadSignal<Option<(UserMeta, String)>>,
         WriteSignal<Option<(UserMeta, String)>>,
     ),
-    pub rtc_config: StoredValue<RtcConfig>,
+    pub rtc_config: StoredValue<Option<RtcConfig>>,
 }
 
 #[derive(Debug, Clone)]
@@ -162,6 +186,61 @@ pub struct RoomInfo {
     pub user_id: Uuid,
     pub users: Vec<UserMeta>,
     pub player_status: PlayerStatus,
+    pub rtc_support: RtcSupport,
+}
+
+/// What the server's ICE configuration allows calls and video shares to do.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RtcSupport {
+    /// Server runs in sync-only mode, calls and video share are disabled.
+    Unavailable,
+    /// Only STUN is available, peers behind strict NATs may fail to connect.
+    NoRelay,
+    Full,
+}
+
+impl RtcSupport {
+    pub fn from_config(rtc_config: Option<&RtcConfig>) -> Self {
+        match rtc_config {
+            None => Self::Unavailable,
+            Some(config) if !config.has_relay() => Self::NoRelay,
+            Some(_) => Self::Full,
+        }
+    }
+
+    /// Returns `true` if calls can be attempted at all.
+    #[must_use]
+    pub fn is_available(&self) -> bool {
+        !matches!(self, Self::Unavailable)
+    }
+}
+
+/// Wording shown for errors reported by the server.
+pub fn describe_error(error: ErrorCode) -> &'static str {
+    match error {
+        ErrorCode::VersionMismatch => "This app is outdated, update it to keep watching",
+        ErrorCode::InvalidParams => "Check your name and room code",
+        ErrorCode::RoomNotFound => "This room does not exist anymore",
+        ErrorCode::RoomFull => "This room is full, try creating a new one",
+        ErrorCode::WrongPassword => "Wrong room password",
+        ErrorCode::InvalidToken => "This invite link is not valid anymore",
+        ErrorCode::NotHost => "Only the host can do that",
+        ErrorCode::RoomClosed => "This room was closed",
+        ErrorCode::RateLimited => "You were sending too fast and got disconnected",
+        ErrorCode::SpectatorNotAllowed => "Spectators can only watch and chat",
+        ErrorCode::CallCooldown => "Wait a bit before calling again",
+        ErrorCode::ServerError => "Something went wrong on the server, try again",
+    }
+}
+
+/// How to join a room, the secrets are only needed for rooms created through the API.
+#[derive(Debug, Clone, Default)]
+pub struct JoinOptions {
+    pub password: Option<String>,
+    pub host_token: Option<String>,
+    pub role: UserRole,
+    /// Sent once joined, images are too large for the join url.
+    pub appearance: Appearance,
 }
 
 #[derive(Clone)]
@@ -215,6 +294,9 @@ impl RoomManager {
         });
 
         let video_offer = store_value(OfferReason::VideoCall);
+        let update_required = with_owner(owner, || create_rw_signal(false));
+        let overlay_url = with_owner(owner, || create_rw_signal(None));
+        let resume = with_owner(owner, || store_value(None));
 
         let rm = Self {
             state,
@@ -236,6 +318,9 @@ impl RoomManager {
             share_video_permission: share_video_sig.0.into(),
             share_video_permission_tx: share_video_sig.1,
             video_offer_type: video_offer,
+            update_required,
+            overlay_url,
+            resume,
         };
         with_owner(owner, {
             let rm = rm.clone();
@@ -260,7 +345,7 @@ impl RoomManager {
                                 } else {
                                     None
                                 };
-                            rtc_config_peer.map(|s| s.get_value())
+                            rtc_config_peer.and_then(|s| s.get_value())
                         })
                     },
                     Callback::new(move |user_id| {
@@ -395,8 +480,24 @@ impl RoomManager {
         &self,
         name: String,
         room_code: Option<String>,
+        options: JoinOptions,
     ) -> Result<Signal<Option<Message>>, RoomManagerError> {
         let toaster = expect_context::<Toaster>();
+        let endpoint = expect_context::<Endpoint>();
+        // A fresh join, forget any room we were waiting to get back into.
+        self.resume.set_value(None);
+        self.connect(name, room_code, options, toaster, endpoint)
+    }
+
+    /// Contexts are passed in so a resume can reconnect from a timeout.
+    fn connect(
+        &self,
+        name: String,
+        room_code: Option<String>,
+        options: JoinOptions,
+        toaster: Toaster,
+        endpoint: Endpoint,
+    ) -> Result<Signal<Option<Message>>, RoomManagerError> {
         toaster.toast(Toast {
             message: "Connecting to server".into(),
             r#type: crate::components::toaster::ToastType::Info,
@@ -416,16 +517,36 @@ impl RoomManager {
             } else {
                 endpoints::HOST_ROOM
             };
+            let resume = self.resume;
+            let resume_name = name.clone();
+            let resume_options = options.clone();
             let params = {
                 if let Some(room_id) = room_code {
-                    let join_params = JoinParams { name, room_id };
+                    let join_params = JoinParams {
+                        name,
+                        room_id,
+                        protocol_version: PROTOCOL_VERSION,
+                        encoding: Encoding::Binary,
+                        password: options.password,
+                        host_token: options.host_token,
+                        role: options.role,
+                    };
                     serde_urlencoded::to_string(&join_params)
                 } else {
-                    let host_params = HostParams { name };
+                    let host_params = HostParams {
+                        name,
+                        protocol_version: PROTOCOL_VERSION,
+                        encoding: Encoding::Binary,
+                    };
                     serde_urlencoded::to_string(&host_params)
                 }
             };
-            let main_endpoint = expect_context::<Endpoint>().main_endpoint;
+            let main_endpoint = endpoint.main_endpoint.get_untracked();
+            let update_required = self.update_required;
+            let overlay_url = self.overlay_url;
+            let http_endpoint = endpoint.http_endpoint();
+            let rm = self.clone();
+            let navigate = use_navigate();
             match params {
                 Ok(params) => {
                     let UseWebSocketReturn {
@@ -445,9 +566,31 @@ impl RoomManager {
                                 });
                             })
                             .on_close(move |ev| {
-                                let reason = ev.reason();
+                                let error = ErrorCode::from_close_code(ev.code());
+                                match error {
+                                    Some(ErrorCode::VersionMismatch) => update_required.set(true),
+                                    Some(ErrorCode::RoomNotFound | ErrorCode::RoomClosed) => {
+                                        navigate("/", Default::default());
+                                    }
+                                    _ => {}
+                                }
+                                // 1006 while resuming means the server is not back up yet.
+                                let resuming = resume
+                                    .with_value(|r| r.as_ref().is_some_and(|r| r.attempts > 0));
+                                if ev.code() == CLOSE_CODE_SERVER_RESTART
+                                    || (resuming && ev.code() == 1006)
+                                {
+                                    rm.schedule_resume(toaster, endpoint);
+                                    return;
+                                }
+                                resume.set_value(None);
+                                let message = match error {
+                                    // The reason names the field that failed validation.
+                                    Some(ErrorCode::InvalidParams) | None => ev.reason().into(),
+                                    Some(error) => describe_error(error).into(),
+                                };
                                 toaster.toast(Toast {
-                                    message: reason.into(),
+                                    message,
                                     r#type: crate::components::toaster::ToastType::Failed,
                                 });
                             }),
@@ -504,6 +647,9 @@ impl RoomManager {
                                             ready_state,
                                         )) = &*state_c_ref
                                         {
+                                            let rtc_support = RtcSupport::from_config(
+                                                room_info.rtc_config.as_ref(),
+                                            );
                                             let rtc_config = with_owner(owner, || {
                                                 store_value(room_info.rtc_config)
                                             });
@@ -512,6 +658,7 @@ impl RoomManager {
                                                 user_id: room_info.user_id,
                                                 users: room_info.users,
                                                 player_status: room_info.player_status,
+                                                rtc_support,
                                             };
 
                                             let chat_signal =
@@ -539,11 +686,25 @@ impl RoomManager {
                                             let mut state = state_c.borrow_mut();
                                             *state = RoomState::Connected(connection_info);
                                             drop(state);
+                                            resume.set_value(Some(ResumeInfo {
+                                                name: resume_name.clone(),
+                                                room_id: room_info.id.clone(),
+                                                options: resume_options.clone(),
+                                                attempts: 0,
+                                            }));
                                             nav(
                                                 &format!("/room/{}", room_info.id),
                                                 Default::default(),
                                             );
                                             room_info_writer.set(Some(room_info));
+                                            if resume_options.appearance != Appearance::default() {
+                                                rm.send_message(
+                                                    ClientMessage::SetAppearance(
+                                                        resume_options.appearance.clone(),
+                                                    ),
+                                                    SendType::Reliable,
+                                                );
+                                            }
                                         }
                                     }
                                     common::message::ServerMessage::UserJoined(UserJoined {
@@ -572,10 +733,53 @@ impl RoomManager {
                                     }
                                     common::message::ServerMessage::Error(error) => {
                                         toaster.toast(Toast {
-                                            message: error.into(),
+                                            message: describe_error(error).into(),
                                             r#type: crate::components::toaster::ToastType::Failed,
                                         });
                                     }
+                                    common::message::ServerMessage::Notice(notice) => {
+                                        toaster.toast(Toast {
+                                            message: notice.into(),
+                                            r#type: crate::components::toaster::ToastType::Info,
+                                        });
+                                    }
+                                    common::message::ServerMessage::Restarting(_) => {
+                                        toaster.toast(Toast {
+                                            message:
+                                                "Server is restarting, you will be reconnected"
+                                                    .into(),
+                                            r#type: crate::components::toaster::ToastType::Info,
+                                        });
+                                    }
+                                    common::message::ServerMessage::Invalid(error) => {
+                                        toaster.toast(Toast {
+                                            message: format!("Message not sent, {error}").into(),
+                                            r#type: crate::components::toaster::ToastType::Failed,
+                                        });
+                                    }
+                                    common::message::ServerMessage::RateLimited(limited) => {
+                                        warn!("Rate limited {limited:?}");
+                                        toaster.toast(Toast {
+                                            message: "Slow down, some messages were dropped".into(),
+                                            r#type: crate::components::toaster::ToastType::Failed,
+                                        });
+                                    }
+                                    common::message::ServerMessage::OverlayToken(token) => {
+                                        if let Some(room_id) = room_info_reader
+                                            .with_untracked(|r| r.as_ref().map(|r| r.id.clone()))
+                                        {
+                                            // The web build talks to its own origin.
+                                            let base = if http_endpoint.is_empty() {
+                                                window().location().origin().unwrap_or_default()
+                                            } else {
+                                                http_endpoint.clone()
+                                            };
+                                            let path =
+                                                endpoints::ROOM_EVENTS.replace(":id", &room_id);
+                                            overlay_url
+                                                .set(Some(format!("{base}{path}?token={token}")));
+                                        }
+                                    }
                                 },
                                 Message::ClientMessage((from_user, message)) => match message {
                                     common::message::ClientMessage::SetVideoMeta(video_name) => {
@@ -591,6 +795,42 @@ impl RoomManager {
                                             }
                                         }
                                     }
+                                    common::message::ClientMessage::SetName(name) => {
+                                        if let Some(mut room_info) =
+                                            room_info_reader.get_untracked()
+                                        {
+                                            if from_user == room_info.user_id {
+                                                // Rejoin under the new name after a restart.
+                                                resume.update_value(|r| {
+                                                    if let Some(r) = r {
+                                                        r.name = name.clone();
+                                                    }
+                                                });
+                                            }
+                                            if let Some(user) = room_info
+                                                .users
+                                                .iter_mut()
+                                                .find(|u| u.id == from_user)
+                                            {
+                                                user.name = name;
+                                                room_info_writer.set(Some(room_info));
+                                            }
+                                        }
+                                    }
+                                    common::message::ClientMessage::SetAppearance(appearance) => {
+                                        if let Some(mut room_info) =
+                                            room_info_reader.get_untracked()
+                                        {
+                                            if let Some(user) = room_info
+                                                .users
+                                                .iter_mut()
+                                                .find(|u| u.id == from_user)
+                                            {
+                                                user.appearance = appearance;
+                                                room_info_writer.set(Some(room_info));
+                                            }
+                                        }
+                                    }
                                     common::message::ClientMessage::Play(time) => {
                                         if let Some(mut room_info) =
                                             room_info_reader.get_untracked()
@@ -681,6 +921,7 @@ impl RoomManager {
                                     ClientMessage::RequestVideoShare(_) => {
                                         share_permission_tx.set(Some(from_user));
                                     }
+                                    ClientMessage::CreateOverlayToken => {}
                                 },
                             }
                         } else {
@@ -713,6 +954,37 @@ impl RoomManager {
         })
     }
 
+    fn schedule_resume(&self, toaster: Toaster, endpoint: Endpoint) {
+        let Some(mut info) = self.resume.get_value() else {
+            return;
+        };
+        if info.attempts >= MAX_RESUME_ATTEMPTS {
+            self.resume.set_value(None);
+            toaster.toast(Toast {
+                message: "Could not reconnect to the room".into(),
+                r#type: crate::components::toaster::ToastType::Failed,
+            });
+            return;
+        }
+        info.attempts += 1;
+        self.resume.set_value(Some(info.clone()));
+        let rm = self.clone();
+        set_timeout(
+            move || {
+                if let Err(err) = rm.connect(
+                    info.name,
+                    Some(info.room_id),
+                    info.options,
+                    toaster,
+                    endpoint,
+                ) {
+                    warn!("Cannot rejoin room {err:?}");
+                }
+            },
+            Duration::from_secs(info.attempts.into()),
+        );
+    }
+
     pub fn message_signal(&self) -> Result<Signal<Option<Message>>, RoomManagerError> {
         let val = self.state.borrow();
         match &*val {
@@ -792,7 +1064,22 @@ impl RoomManager {
         }
     }
 
+    /// Returns `true` if we joined the current room as a spectator.
+    pub fn is_spectator(&self) -> bool {
+        self.room_info_signal.0.with_untracked(|r| {
+            r.as_ref().is_some_and(|r| {
+                r.users
+                    .iter()
+                    .any(|u| u.id == r.user_id && u.role.is_spectator())
+            })
+        })
+    }
+
     pub fn send_message(&self, message: ClientMessage, send_type: SendType) {
+        // The server refuses these from spectators, local player events should not reach it.
+        if !message.is_allowed_for_spectator() && self.is_spectator() {
+            return;
+        }
         with_owner(self.owner, || {
             if let Some(player_id) = self
                 .room_info_signal
@@ -841,6 +1128,15 @@ impl RoomManager {
         }
     }
 
+    /// Asks the server to rename us, the room info updates once it is relayed back.
+    pub fn set_name(&self, name: String) {
+        let name = name.trim().to_string();
+        if name.is_empty() {
+            return;
+        }
+        self.send_message(ClientMessage::SetName(name), SendType::Reliable);
+    }
+
     pub fn send_chat(&self, msg: String) {
         if msg.trim().is_empty() {
             return;
@@ -866,6 +1162,13 @@ impl RoomManager {
         video: bool,
         audio: bool,
     ) -> Result<(), JsValue> {
+        if !self
+            .room_info_signal
+            .0
+            .with_untracked(|r| r.as_ref().is_some_and(|r| r.rtc_support.is_available()))
+        {
+            return Err(JsValue::from_str("Calls are not available on this server"));
+        }
         let stream = get_media_stream(video, audio).await?;
         let audio_track = stream
             .get_audio_tracks()
@@ -916,7 +1219,10 @@ impl RoomManager {
             return Err(JsValue::from_str("Room not connected"));
         };
 
-        if let Some(rtc_config) = rtc_config_peer {
+        let Some(rtc_config) = rtc_config_peer else {
+            return Err(JsValue::from_str("Room not connected"));
+        };
+        if let Some(rtc_config) = rtc_config.get_value() {
             let ice_signal = self.ice_signal.0;
             let session_signal = self.sdp_signal.0;
             let owner = self.owner;
@@ -944,7 +1250,7 @@ impl RoomManager {
                 video_share,
                 room_info.user_id,
                 user,
-                &rtc_config.get_value(),
+                &rtc_config,
                 video,
                 audio,
                 Callback::new(move |(video, audio)| async move {
@@ -1007,7 +1313,7 @@ impl RoomManager {
             .await?;
             Ok(())
         } else {
-            Err(JsValue::from_str("Room not connected"))
+            Err(JsValue::from_str("Calls are not available on this server"))
         }
     }
 
@@ -1059,6 +1365,45 @@ impl RoomManager {
 
         Ok(())
     }
+
+    /// Snapshot of the current room for a diagnostics bundle.
+    pub fn diagnostics_room(&self) -> Option<DiagnosticsRoom> {
+        self.get_room_info().with_untracked(|room_info| {
+            room_info.as_ref().map(|room_info| DiagnosticsRoom {
+                room_id: room_info.id.clone(),
+                user_id: room_info.user_id,
+                users: room_info.users.clone(),
+                player_status: room_info.player_status.clone(),
+                rtc_support: format!("{:?}", room_info.rtc_support),
+            })
+        })
+    }
+
+    /// `getStats()` of every open peer connection, failed ones are skipped.
+    pub async fn peer_stats(&self) -> Vec<PeerStats> {
+        let peers = self.rtc_signal.get_untracked();
+        let mut stats = Vec::with_capacity(peers.len());
+        for (user_id, pc) in peers {
+            let report = match JsFuture::from(pc.get_stats()).await.and_then(|report| {
+                let report = Object::from_entries(&report)?;
+                JSON::stringify(&report)
+            }) {
+                Ok(report) => report,
+                Err(err) => {
+                    warn!("Cannot get stats for {user_id} {err:?}");
+                    continue;
+                }
+            };
+            stats.push(PeerStats {
+                user_id,
+                connection_state: format!("{:?}", pc.connection_state()),
+                report: String::from(report)
+                    .parse()
+                    .unwrap_or(serde_json::Value::Null),
+            });
+        }
+        stats
+    }
 }
 
 pub struct WebsocketContext<Tx>
@@ -24,18 +24,27 @@ pub fn connect_rtc(rtc_config: &RtcConfig) -> Result<RtcPeerConnection, JsValue>
         let config = RtcConfiguration::new();
         config.set_ice_servers(&{
             let array = Array::new();
-            array.push(&JsValue::from({
-                let ice_server = RtcIceServer::new();
-                ice_server.set_urls(&JsValue::from_str(&rtc_config.stun));
-                ice_server
-            }));
-            array.push(&JsValue::from({
-                let ice_server = RtcIceServer::new();
-                ice_server.set_urls(&JsValue::from_str(&rtc_config.turn));
-                ice_server.set_username(&rtc_config.turn_user);
-                ice_server.set_credential(&rtc_config.turn_creds);
-                ice_server
-            }));
+            if !rtc_config.stun.is_empty() {
+                array.push(&JsValue::from({
+                    let ice_server = RtcIceServer::new();
+                    let urls = rtc_config
+                        .stun
+                        .iter()
+                        .map(|url| JsValue::from_str(url))
+                        .collect::<Array>();
+                    ice_server.set_urls(&urls);
+                    ice_server
+                }));
+            }
+            if let Some(turn) = &rtc_config.turn {
+                array.push(&JsValue::from({
+                    let ice_server = RtcIceServer::new();
+                    ice_server.set_urls(&JsValue::from_str(&turn.url));
+                    ice_server.set_username(&turn.user);
+                    ice_server.set_credential(&turn.creds);
+                    ice_server
+                }));
+            }
             JsValue::from(array)
         });
         config
@@ -0,0 +1,195 @@
+use codee::string::FromToStringCodec;
+use common::{api::RoomStatus, PlayerStatus};
+use leptos::*;
+use leptos_use::storage::use_session_storage;
+use tracing::warn;
+use wasm_bindgen::JsValue;
+
+use crate::{
+    apis::admin::{close_room, list_rooms, send_notice},
+    components::toaster::{Toast, ToastType, Toaster},
+    Endpoint,
+};
+
+const ADMIN_TOKEN_STORAGE_KEY: &str = "tvmate_admin_token";
+
+fn error_message(err: JsValue) -> String {
+    err.as_string().unwrap_or_else(|| format!("{err:?}"))
+}
+
+fn describe_player(status: &PlayerStatus) -> String {
+    let (state, time) = match status {
+        PlayerStatus::Paused(time) => ("paused", *time),
+        PlayerStatus::Playing(time) => ("playing", *time),
+    };
+    let time = time.max(0.0) as u64;
+    format!("{state} {:02}:{:02}", time / 60, time % 60)
+}
+
+/// Operator dashboard listing every room, lives at `/admin`.
+#[component]
+pub fn AdminPage() -> impl IntoView {
+    let base = store_value(
+        use_context::<Endpoint>()
+            .map(|endpoint| endpoint.http_endpoint())
+            .unwrap_or_default(),
+    );
+    let (token, set_token, _) =
+        use_session_storage::<String, FromToStringCodec>(ADMIN_TOKEN_STORAGE_KEY);
+    let (rooms, set_rooms) = create_signal(Vec::<RoomStatus>::new());
+    let (notice, set_notice) = create_signal(String::new());
+    let toaster = expect_context::<Toaster>();
+
+    let refresh = move || {
+        let token = token.get_untracked();
+        if token.is_empty() {
+            return;
+        }
+        spawn_local(async move {
+            match list_rooms(&base.get_value(), &token).await {
+                Ok(list) => set_rooms.set(list),
+                Err(err) => {
+                    warn!("Cannot list rooms {err:?}");
+                    toaster.toast(Toast {
+                        message: error_message(err).into(),
+                        r#type: ToastType::Failed,
+                    });
+                }
+            }
+        });
+    };
+
+    let notify = move |room_id: Option<String>| {
+        let message = notice.get_untracked();
+        if message.trim().is_empty() {
+            return;
+        }
+        let token = token.get_untracked();
+        spawn_local(async move {
+            match send_notice(&base.get_value(), &token, room_id.as_deref(), message).await {
+                Ok(()) => toaster.toast(Toast {
+                    message: "Notice sent".into(),
+                    r#type: ToastType::Success,
+                }),
+                Err(err) => toaster.toast(Toast {
+                    message: error_message(err).into(),
+                    r#type: ToastType::Failed,
+                }),
+            }
+        });
+    };
+
+    let close = move |room_id: String| {
+        let token = token.get_untracked();
+        spawn_local(async move {
+            if let Err(err) = close_room(&base.get_value(), &token, &room_id).await {
+                toaster.toast(Toast {
+                    message: error_message(err).into(),
+                    r#type: ToastType::Failed,
+                });
+            }
+            refresh();
+        });
+    };
+
+    create_effect(move |_| refresh());
+
+    view! {
+        <div class="h-full w-full flex flex-col p-4 gap-2 overflow-auto text-sm">
+            <h1 class="text-xl font-bold2">"Admin"</h1>
+
+            <div class="flex items-center gap-2">
+                <label class="font-thin8" for="admin-token">"Token: "</label>
+                <input
+                    class="bg-white/10 focus:outline-white/50 font-thin8 p-1"
+                    id="admin-token"
+                    type="password"
+                    prop:value=token
+                    on:change=move |ev| {
+                        set_token.set(event_target_value(&ev));
+                        refresh();
+                    }
+                />
+                <button class="hover:bg-white/20 px-2" on:click=move |_| refresh()>
+                    "[ Refresh ]"
+                </button>
+            </div>
+
+            <div class="flex items-center gap-2">
+                <input
+                    class="bg-white/10 focus:outline-white/50 font-thin8 p-1 flex-grow"
+                    type="text"
+                    placeholder="Notice"
+                    prop:value=notice
+                    on:input=move |ev| set_notice.set(event_target_value(&ev))
+                />
+                <button class="hover:bg-white/20 px-2" on:click=move |_| notify(None)>
+                    "[ Notify All ]"
+                </button>
+            </div>
+
+            <div>
+                {move || {
+                    rooms.with(|rooms| {
+                        format!(
+                            "{} rooms, {} users",
+                            rooms.len(),
+                            rooms.iter().map(|room| room.users.len()).sum::<usize>(),
+                        )
+                    })
+                }}
+            </div>
+
+            <For
+                each=move || rooms.get()
+                key=|room| room.room_id.clone()
+                let:room
+            >
+                {
+                    let spectators = room.users.iter().filter(|u| u.role.is_spectator()).count();
+                    let members = room.users.len() - spectators;
+                    let room_id = room.room_id.clone();
+                    let notice_room = room.room_id.clone();
+                    view! {
+                        <div class="border border-white/50 p-2 flex flex-col gap-1">
+                            <div class="flex justify-between">
+                                <span class="font-bold2">{room.room_id.to_uppercase()}</span>
+                                <span>{describe_player(&room.player_status)}</span>
+                            </div>
+                            <div>
+                                {format!("{members}/{} users, {spectators} spectators", room.capacity)}
+                                {room.has_password.then_some(", password")}
+                                {format!(", {} min old", room.age_secs / 60)}
+                            </div>
+                            <div class="break-words">
+                                {room
+                                    .users
+                                    .iter()
+                                    .map(|user| {
+                                        let is_host = room.host.as_ref().is_some_and(|h| h.id == user.id);
+                                        format!("{}{}", user.name, if is_host { " (host)" } else { "" })
+                                    })
+                                    .collect::<Vec<_>>()
+                                    .join(", ")}
+                            </div>
+                            <div class="flex gap-2">
+                                <button
+                                    class="hover:bg-white/20 px-2"
+                                    on:click=move |_| notify(Some(notice_room.clone()))
+                                >
+                                    "[ Notify ]"
+                                </button>
+                                <button
+                                    class="hover:bg-white/20 px-2"
+                                    on:click=move |_| close(room_id.clone())
+                                >
+                                    "[ Close ]"
+                                </button>
+                            </div>
+                        </div>
+                    }
+                }
+            </For>
+        </div>
+    }
+}
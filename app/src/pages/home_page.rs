@@ -9,14 +9,19 @@ use web_sys::js_sys;
 use crate::components::dialog::Dialog;
 use crate::components::help_dialog::IntroHelpDialog;
 use crate::components::join_dialog::JoinDialog;
+use crate::components::profile::{use_profile, ProfileFields};
+use crate::components::server_picker::ServerPicker;
 use crate::components::toaster::{Toast, Toaster};
-use crate::networking::room_manager::RoomManager;
+use crate::networking::room_manager::{JoinOptions, RoomManager};
+use crate::Endpoint;
 
 /// Renders the home page of your application.
 #[component]
 pub fn HomePage() -> impl IntoView {
     let (host_open, set_host_open) = create_signal(false);
     let (join_open, set_join_open) = create_signal(false);
+    let (server_open, set_server_open) = create_signal(false);
+    let endpoint = expect_context::<Endpoint>();
 
     let (install_prompt, set_install_prompt) = create_signal(None);
     create_effect(move |_| {
@@ -41,26 +46,14 @@ pub fn HomePage() -> impl IntoView {
             }
         >
             {{
-                let (name, set_name) = create_signal(String::new());
+                let (saved_profile, set_saved_profile) = use_profile();
+                let profile = create_rw_signal(saved_profile.get_untracked());
                 view! {
                     <h3 class="font-bold2  text-xl text-center w-full">"Host"</h3>
 
                     <div class="h-4" />
 
-                    <div class="flex items-center">
-                        <label class=" font-thin8 text-sm" for="name">
-                            "Name: "
-                        </label>
-                        <input
-                            class="bg-white/10 focus:outline-white/50  text-md font-thin8 p-2"
-                            name="name"
-                            type="text"
-                            placeholder="Enter your name"
-                            on:input=move |ev| {
-                                set_name.set(event_target_value(&ev));
-                            }
-                        />
-                    </div>
+                    <ProfileFields profile=profile />
 
                     <div class="h-4" />
 
@@ -71,11 +64,17 @@ pub fn HomePage() -> impl IntoView {
                             let toaster = expect_context::<Toaster>();
                             toaster.toast(Toast{message:"Hosting room".into(), r#type:crate::components::toaster::ToastType::Info});
 
-                            if name.get_untracked().is_empty() {
+                            let profile = profile.get_untracked();
+                            if profile.name.is_empty() {
                                 toaster.toast(Toast{message:"Name cannot be empty".into(), r#type:crate::components::toaster::ToastType::Failed});
                             } else {
+                                set_saved_profile.set(profile.clone());
+                                let options = JoinOptions {
+                                    appearance: profile.appearance(),
+                                    ..Default::default()
+                                };
                                 let room_manager = expect_context::<RoomManager>();
-                                if let Err(err) = room_manager.host_join(name.get_untracked(), None)
+                                if let Err(err) = room_manager.host_join(profile.name, None, options)
                                 {
                                     toaster.toast(Toast{message:format!("Cannot join room {err:?}").into(), r#type:crate::components::toaster::ToastType::Failed});
                                 }
@@ -95,6 +94,12 @@ pub fn HomePage() -> impl IntoView {
             })
             init_room_code=""
         />
+        <ServerPicker
+            is_open=server_open
+            on_close=Callback::new(move|_|{
+                set_server_open.set(false);
+            })
+        />
         <div class="h-full w-full flex flex-col items-center justify-center ">
 
             <div class="flex-grow" />
@@ -113,6 +118,14 @@ pub fn HomePage() -> impl IntoView {
                     "[ Join ]"
                 </button>
             </div>
+            <div class="h-4" />
+            <button
+                class="font-thin8 text-sm"
+                class=("hidden", !endpoint.is_configurable)
+                on:click=move |_| set_server_open.set(true)
+            >
+                "Server: " {move || endpoint.main_endpoint.get().to_string()}
+            </button>
             <div class="flex-grow" />
 
             {move || {
@@ -22,6 +22,8 @@ struct RoomParam {
 #[component]
 pub fn RoomPage() -> impl IntoView {
     let params = use_params::<RoomParam>();
+    let query = use_query_map();
+    let host_token = create_memo(move |_| query.with(|q| q.get("host_token").cloned()));
     let (video_url, set_video_url) = create_signal(None);
     let (video_name, set_video_name) = create_signal(None);
 
@@ -85,10 +87,15 @@ pub fn RoomPage() -> impl IntoView {
     });
 
     let selected_users = create_memo(move |_| {
-        if let Some(room_info) = room_info.get() {
+        if let Some(room_info) = room_info
+            .get()
+            .filter(|room_info| room_info.rtc_support.is_available())
+        {
             let mut users = room_info.users;
             users.retain(|u| {
-                u.id != room_info.user_id && matches!(u.state, common::UserState::VideoSelected(_))
+                u.id != room_info.user_id
+                    && !u.role.is_spectator()
+                    && matches!(u.state, common::UserState::VideoSelected(_))
             });
             users
         } else {
@@ -114,6 +121,7 @@ pub fn RoomPage() -> impl IntoView {
                     }
                 })
             }
+            host_token
         />
         <Suspense>
             {
@@ -54,3 +54,25 @@ impl Write for StringWriter {
         Ok(())
     }
 }
+
+/// Maps a websocket base url to the matching http one, `wss://host` becomes `https://host`.
+pub fn ws_to_http(endpoint: &str) -> String {
+    if let Some(rest) = endpoint.strip_prefix("wss://") {
+        format!("https://{rest}")
+    } else if let Some(rest) = endpoint.strip_prefix("ws://") {
+        format!("http://{rest}")
+    } else {
+        endpoint.to_string()
+    }
+}
+
+/// Maps an http base url to the matching websocket one, `https://host` becomes `wss://host`.
+pub fn http_to_ws(endpoint: &str) -> String {
+    if let Some(rest) = endpoint.strip_prefix("https://") {
+        format!("wss://{rest}")
+    } else if let Some(rest) = endpoint.strip_prefix("http://") {
+        format!("ws://{rest}")
+    } else {
+        endpoint.to_string()
+    }
+}
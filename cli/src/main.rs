@@ -0,0 +1,261 @@
+//! Terminal client to host, join and control rooms.
+//!
+//! `status` reads the room through the http api. `chat` and `watch` join as a spectator,
+//! the playback commands and `join` join as a regular user and need a free slot in the room
+//! for as long as they run. With `--spectator` they take no slot, but can only chat.
+
+mod output;
+mod schedule;
+
+use chrono::{DateTime, Local};
+use clap::{Args, Parser, Subcommand};
+use client::{ClientError, RoomClient};
+use common::{encoding::Encoding, message::PROTOCOL_VERSION, params::JoinParams, UserRole};
+use output::{print_status, EventPrinter};
+use schedule::{parse_at, parse_position, wait_until};
+use thiserror::Error;
+use tokio::io::{AsyncBufReadExt, BufReader};
+use tracing::level_filters::LevelFilter;
+use tracing_subscriber::EnvFilter;
+
+#[derive(Parser)]
+#[command(
+    name = "tvmate-cli",
+    version,
+    about = "Host, join and control TVMate rooms"
+)]
+struct Cli {
+    /// Websocket url of the server.
+    #[arg(
+        long,
+        global = true,
+        env = "TVMATE_SERVER",
+        default_value = "wss://tvmate.deepgaurav.com"
+    )]
+    server: String,
+
+    /// Name shown to the other users in the room.
+    #[arg(long, global = true, env = "TVMATE_NAME", default_value = "tvmate-cli")]
+    name: String,
+
+    /// Password of the room, if it has one.
+    #[arg(long, global = true, env = "TVMATE_ROOM_PASSWORD")]
+    password: Option<String>,
+
+    /// Host token of a room created through the API, joins as its host.
+    #[arg(long, global = true, env = "TVMATE_HOST_TOKEN")]
+    host_token: Option<String>,
+
+    /// Join without taking a seat, playback commands are refused by the server.
+    #[arg(long, global = true)]
+    spectator: bool,
+
+    /// Print events as JSON lines instead of text.
+    #[arg(long, global = true)]
+    json: bool,
+
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Create a room and stay in it, printing events until interrupted.
+    Host,
+    /// Join a room, printing events and sending every line from stdin as chat.
+    Join { room: String },
+    /// Print the users and player state of a room, without joining it.
+    Status { room: String },
+    /// Start playback for everyone in the room.
+    Play {
+        room: String,
+        /// Position to play from (seconds, MM:SS or HH:MM:SS), defaults to the current one.
+        #[arg(long, value_parser = parse_position)]
+        from: Option<f64>,
+        #[command(flatten)]
+        schedule: Schedule,
+    },
+    /// Pause playback for everyone in the room.
+    Pause {
+        room: String,
+        #[command(flatten)]
+        schedule: Schedule,
+    },
+    /// Seek everyone to a position (seconds, MM:SS or HH:MM:SS).
+    Seek {
+        room: String,
+        #[arg(value_parser = parse_position)]
+        position: f64,
+        #[command(flatten)]
+        schedule: Schedule,
+    },
+    /// Send a chat message as a spectator.
+    Chat { room: String, message: String },
+    /// Print room events as a spectator until interrupted.
+    Watch { room: String },
+}
+
+#[derive(Args)]
+struct Schedule {
+    /// Stay in the room and send at this local time (HH:MM, HH:MM:SS or RFC 3339).
+    #[arg(long, value_parser = parse_at)]
+    at: Option<DateTime<Local>>,
+}
+
+impl Schedule {
+    async fn wait(&self) {
+        if let Some(at) = self.at {
+            wait_until(at).await;
+        }
+    }
+}
+
+#[derive(Error, Debug)]
+enum CliError {
+    #[error(transparent)]
+    Client(#[from] ClientError),
+
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+}
+
+#[tokio::main]
+async fn main() {
+    tracing_subscriber::fmt()
+        .with_writer(std::io::stderr)
+        .with_env_filter(
+            EnvFilter::builder()
+                .with_default_directive(LevelFilter::WARN.into())
+                .from_env_lossy(),
+        )
+        .init();
+
+    let cli = Cli::parse();
+    if let Err(err) = run(cli).await {
+        eprintln!("error: {err}");
+        std::process::exit(1);
+    }
+}
+
+async fn run(cli: Cli) -> Result<(), CliError> {
+    let Cli {
+        server,
+        name,
+        password,
+        host_token,
+        spectator,
+        json,
+        command,
+    } = cli;
+    let role = if spectator {
+        UserRole::Spectator
+    } else {
+        UserRole::Member
+    };
+    let join_as = |room_id: String, role: UserRole| {
+        let server = server.clone();
+        let params = JoinParams {
+            name: name.clone(),
+            room_id,
+            protocol_version: PROTOCOL_VERSION,
+            encoding: Encoding::Binary,
+            password: password.clone(),
+            host_token: host_token.clone(),
+            role,
+        };
+        async move { RoomClient::join_with_params(&server, params).await }
+    };
+    let join = |room_id: String| join_as(room_id, role);
+
+    match command {
+        Command::Host => {
+            let room = RoomClient::host(&server, name).await?;
+            println!("{}", room.room_id());
+            tail(&room, json, false).await?;
+            room.close().await;
+        }
+        Command::Join { room } => {
+            let room = join(room).await?;
+            tail(&room, json, true).await?;
+            room.close().await;
+        }
+        Command::Watch { room } => {
+            let room = join_as(room, UserRole::Spectator).await?;
+            tail(&room, json, false).await?;
+            room.close().await;
+        }
+        Command::Status { room } => {
+            let status =
+                client::room_status(&server, &room, password.as_deref(), host_token.as_deref())
+                    .await?;
+            print_status(&status);
+        }
+        Command::Play {
+            room,
+            from,
+            schedule,
+        } => {
+            let room = join(room).await?;
+            schedule.wait().await;
+            let position = from.unwrap_or_else(|| room.state().player_status.get_time());
+            room.play(position).await?;
+            room.close().await;
+        }
+        Command::Pause { room, schedule } => {
+            let room = join(room).await?;
+            schedule.wait().await;
+            let position = room.state().player_status.get_time();
+            room.pause(position).await?;
+            room.close().await;
+        }
+        Command::Seek {
+            room,
+            position,
+            schedule,
+        } => {
+            let room = join(room).await?;
+            schedule.wait().await;
+            room.seek(position).await?;
+            room.close().await;
+        }
+        Command::Chat { room, message } => {
+            let room = join_as(room, UserRole::Spectator).await?;
+            room.chat(message).await?;
+            room.close().await;
+        }
+    }
+    Ok(())
+}
+
+/// Prints events until the connection drops or ctrl-c, optionally sending stdin lines as chat.
+async fn tail(room: &RoomClient, json: bool, chat_from_stdin: bool) -> Result<(), CliError> {
+    let mut printer = EventPrinter::new(json, &room.state().users);
+    let mut messages = room.subscribe();
+    let mut lines = BufReader::new(tokio::io::stdin()).lines();
+    let mut stdin_open = chat_from_stdin;
+    loop {
+        tokio::select! {
+            message = messages.recv() => {
+                match message {
+                    Ok(message) => printer.print(&message),
+                    Err(tokio::sync::broadcast::error::RecvError::Lagged(skipped)) => {
+                        eprintln!("skipped {skipped} events");
+                    }
+                    Err(tokio::sync::broadcast::error::RecvError::Closed) => {
+                        eprintln!("disconnected");
+                        break;
+                    }
+                }
+            }
+            line = lines.next_line(), if stdin_open => {
+                match line? {
+                    Some(line) if !line.trim().is_empty() => room.chat(line).await?,
+                    Some(_) => {}
+                    None => stdin_open = false,
+                }
+            }
+            _ = tokio::signal::ctrl_c() => break,
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,150 @@
+use std::collections::HashMap;
+
+use common::{
+    api::RoomStatus,
+    message::{ClientMessage, Message, ServerMessage, UserJoined, UserLeft},
+    PlayerStatus, UserMeta, UserState,
+};
+use uuid::Uuid;
+
+use crate::schedule::format_position;
+
+/// Turns room messages into terminal lines, remembering names of users who already left.
+pub struct EventPrinter {
+    json: bool,
+    names: HashMap<Uuid, String>,
+}
+
+impl EventPrinter {
+    pub fn new(json: bool, users: &[UserMeta]) -> Self {
+        let mut printer = Self {
+            json,
+            names: HashMap::new(),
+        };
+        printer.remember(users);
+        printer
+    }
+
+    fn remember(&mut self, users: &[UserMeta]) {
+        for user in users {
+            self.names.insert(user.id, user.name.clone());
+        }
+    }
+
+    fn name(&self, id: &Uuid) -> String {
+        self.names
+            .get(id)
+            .cloned()
+            .unwrap_or_else(|| id.to_string())
+    }
+
+    pub fn print(&mut self, message: &Message) {
+        if self.json {
+            match serde_json::to_string(message) {
+                Ok(line) => println!("{line}"),
+                Err(err) => eprintln!("Failed to serialize message {err}"),
+            }
+            return;
+        }
+        if let Some(line) = self.describe(message) {
+            println!("{line}");
+        }
+    }
+
+    fn describe(&mut self, message: &Message) -> Option<String> {
+        match message {
+            Message::ServerMessage(message) => match message {
+                ServerMessage::RoomCreated(info) | ServerMessage::RoomJoined(info) => {
+                    self.remember(&info.users);
+                    Some(format!("in room {}", info.room_id))
+                }
+                ServerMessage::UserJoined(UserJoined {
+                    new_user, users, ..
+                }) => {
+                    self.remember(users);
+                    Some(format!("* {} joined", self.name(new_user)))
+                }
+                ServerMessage::UserLeft(UserLeft { user_left, .. }) => {
+                    Some(format!("* {} left", self.name(user_left)))
+                }
+                ServerMessage::Error(error) => Some(format!("! {error}")),
+                ServerMessage::OverlayToken(token) => Some(format!("overlay token {token}")),
+                ServerMessage::Notice(notice) => Some(format!("! notice: {notice}")),
+                ServerMessage::Restarting(_) => Some("! server is restarting".to_string()),
+                ServerMessage::Invalid(error) => Some(format!("! {error}")),
+                ServerMessage::RateLimited(limited) => Some(format!(
+                    "! slow down, {} dropped, retry in {}ms",
+                    limited.kind, limited.retry_after_ms
+                )),
+            },
+            Message::ClientMessage((from, message)) => {
+                let name = self.name(from);
+                match message {
+                    ClientMessage::Chat(text) => Some(format!("<{name}> {text}")),
+                    ClientMessage::Play(time) => {
+                        Some(format!("* {name} played at {}", format_position(*time)))
+                    }
+                    ClientMessage::Pause(time) => {
+                        Some(format!("* {name} paused at {}", format_position(*time)))
+                    }
+                    ClientMessage::Seek(time, _) => {
+                        Some(format!("* {name} seeked to {}", format_position(*time)))
+                    }
+                    ClientMessage::SetVideoMeta(video) => {
+                        Some(format!("* {name} selected {}", video.name))
+                    }
+                    ClientMessage::SetName(new_name) => {
+                        self.names.insert(*from, new_name.clone());
+                        Some(format!("* {name} is now {new_name}"))
+                    }
+                    // Position updates are periodic, RTC signalling is noise in a terminal and avatars
+                    // cannot be shown there.
+                    ClientMessage::Update(_)
+                    | ClientMessage::SendSessionDesc(..)
+                    | ClientMessage::ReceivedSessionDesc(_)
+                    | ClientMessage::ExchangeCandidate(..)
+                    | ClientMessage::RequestCall(..)
+                    | ClientMessage::RequestVideoShare(_)
+                    | ClientMessage::CreateOverlayToken
+                    | ClientMessage::SetAppearance(_) => None,
+                }
+            }
+        }
+    }
+}
+
+pub fn print_status(status: &RoomStatus) {
+    println!("room: {}", status.room_id);
+    let player = match &status.player_status {
+        PlayerStatus::Paused(time) => format!("paused at {}", format_position(*time)),
+        PlayerStatus::Playing(time) => format!("playing at {}", format_position(*time)),
+    };
+    println!("player: {player}");
+    let members = status
+        .users
+        .iter()
+        .filter(|user| !user.role.is_spectator())
+        .count();
+    println!("seats: {members}/{}", status.capacity);
+    if status.has_password {
+        println!("password protected");
+    }
+    println!("users:");
+    for user in &status.users {
+        let marker = if status.host.as_ref().is_some_and(|host| host.id == user.id) {
+            " (host)"
+        } else {
+            ""
+        };
+        let role = if user.role.is_spectator() {
+            " [spectator]"
+        } else {
+            ""
+        };
+        let video = match &user.state {
+            UserState::VideoNotSelected => "no video".to_string(),
+            UserState::VideoSelected(video) => video.name.clone(),
+        };
+        println!("  {}{role}{marker}: {video}", user.name);
+    }
+}
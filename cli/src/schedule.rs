@@ -0,0 +1,122 @@
+use chrono::{DateTime, Local, NaiveTime, TimeDelta};
+use tracing::info;
+
+/// Parses `HH:MM`, `HH:MM:SS` (the next occurrence in local time) or an RFC 3339 timestamp.
+pub fn parse_at(input: &str) -> Result<DateTime<Local>, String> {
+    parse_at_after(input, Local::now())
+}
+
+fn parse_at_after(input: &str, now: DateTime<Local>) -> Result<DateTime<Local>, String> {
+    if let Ok(at) = DateTime::parse_from_rfc3339(input) {
+        return Ok(at.with_timezone(&Local));
+    }
+    let time = NaiveTime::parse_from_str(input, "%H:%M:%S")
+        .or_else(|_| NaiveTime::parse_from_str(input, "%H:%M"))
+        .map_err(|_| format!("expected HH:MM, HH:MM:SS or an RFC 3339 timestamp, got {input:?}"))?;
+    let mut date = now.date_naive();
+    if time <= now.time() {
+        date = date.succ_opt().ok_or("date out of range")?;
+    }
+    date.and_time(time)
+        .and_local_timezone(Local)
+        .earliest()
+        .ok_or_else(|| format!("{input} does not exist in the local timezone"))
+}
+
+/// Parses a media position given as seconds, `MM:SS` or `HH:MM:SS`.
+pub fn parse_position(input: &str) -> Result<f64, String> {
+    let invalid = || format!("expected seconds, MM:SS or HH:MM:SS, got {input:?}");
+    let mut position = 0.0;
+    let parts = input.split(':').collect::<Vec<_>>();
+    if parts.len() > 3 {
+        return Err(invalid());
+    }
+    for part in parts {
+        let value = part.parse::<f64>().map_err(|_| invalid())?;
+        if !value.is_finite() || value < 0.0 {
+            return Err(invalid());
+        }
+        position = position * 60.0 + value;
+    }
+    Ok(position)
+}
+
+pub fn format_position(position: f64) -> String {
+    let total = position.max(0.0) as u64;
+    format!(
+        "{:02}:{:02}:{:02}",
+        total / 3600,
+        (total / 60) % 60,
+        total % 60
+    )
+}
+
+pub async fn wait_until(at: DateTime<Local>) {
+    let remaining = at.signed_duration_since(Local::now());
+    if remaining > TimeDelta::zero() {
+        info!("Waiting until {at}");
+        if let Ok(remaining) = remaining.to_std() {
+            tokio::time::sleep(remaining).await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{FixedOffset, TimeZone};
+
+    use super::*;
+
+    #[test]
+    fn parses_positions() {
+        assert_eq!(parse_position("90"), Ok(90.0));
+        assert_eq!(parse_position("1:30"), Ok(90.0));
+        assert_eq!(parse_position("1:02:03"), Ok(3723.0));
+        assert_eq!(parse_position("2.5"), Ok(2.5));
+    }
+
+    #[test]
+    fn rejects_bad_positions() {
+        for input in ["1:02:03:04", "-5", "1:-30", "NaN", "inf", "", "1:", "abc"] {
+            assert!(parse_position(input).is_err(), "{input}");
+        }
+    }
+
+    #[test]
+    fn formats_positions() {
+        assert_eq!(format_position(3723.9), "01:02:03");
+        assert_eq!(format_position(59.0), "00:00:59");
+        assert_eq!(format_position(-5.0), "00:00:00");
+    }
+
+    #[test]
+    fn parses_times_of_day() {
+        let now = Local.with_ymd_and_hms(2024, 3, 10, 12, 0, 0).unwrap();
+        assert_eq!(
+            parse_at_after("18:30", now),
+            Ok(Local.with_ymd_and_hms(2024, 3, 10, 18, 30, 0).unwrap())
+        );
+        assert_eq!(
+            parse_at_after("08:15:30", now),
+            Ok(Local.with_ymd_and_hms(2024, 3, 11, 8, 15, 30).unwrap())
+        );
+        assert_eq!(
+            parse_at_after("12:00", now),
+            Ok(Local.with_ymd_and_hms(2024, 3, 11, 12, 0, 0).unwrap())
+        );
+        assert!(parse_at_after("25:00", now).is_err());
+        assert!(parse_at_after("tomorrow", now).is_err());
+    }
+
+    #[test]
+    fn parses_rfc3339() {
+        let expected = FixedOffset::east_opt(2 * 3600)
+            .unwrap()
+            .with_ymd_and_hms(2024, 3, 10, 20, 0, 0)
+            .unwrap();
+        assert_eq!(
+            parse_at("2024-03-10T20:00:00+02:00"),
+            Ok(expected.with_timezone(&Local))
+        );
+    }
+}
@@ -0,0 +1,390 @@
+//! Headless async client for the room websocket protocol.
+//!
+//! ```no_run
+//! # async fn run() -> Result<(), client::ClientError> {
+//! let room = client::RoomClient::host("wss://tvmate.deepgaurav.com", "bot").await?;
+//! println!("hosting {}", room.room_id());
+//! room.chat("hello").await?;
+//! # Ok(())
+//! # }
+//! ```
+
+mod state;
+
+pub use state::RoomState;
+use state::Unconfirmed;
+
+use common::{
+    api::RoomStatus,
+    encoding::{Encoding, EncodingError, Frame},
+    endpoints,
+    message::{ClientMessage, ErrorCode, Message, ServerMessage, PROTOCOL_VERSION},
+    params::{HostParams, JoinParams},
+    UserRole,
+};
+use futures::{SinkExt, Stream, StreamExt};
+use thiserror::Error;
+use tokio::{
+    net::TcpStream,
+    sync::{broadcast, mpsc, watch},
+    task::JoinHandle,
+};
+use tokio_tungstenite::{
+    connect_async,
+    tungstenite::{protocol::CloseFrame, Message as WsMessage},
+    MaybeTlsStream, WebSocketStream,
+};
+use tracing::{debug, info, warn};
+use uuid::Uuid;
+
+/// Messages buffered for slow subscribers before they start lagging.
+const MESSAGE_BUFFER: usize = 64;
+
+type Socket = WebSocketStream<MaybeTlsStream<TcpStream>>;
+
+#[derive(Error, Debug)]
+pub enum ClientError {
+    #[error("Param failed to encode")]
+    ParamError(#[from] serde_urlencoded::ser::Error),
+
+    #[error(transparent)]
+    WebSocket(#[from] tokio_tungstenite::tungstenite::Error),
+
+    #[error(transparent)]
+    Encoding(#[from] EncodingError),
+
+    #[error("server speaks an incompatible protocol version, update the client")]
+    IncompatibleProtocol,
+
+    #[error("server refused: {reason}")]
+    Refused { error: ErrorCode, reason: String },
+
+    #[error("server closed the connection ({code}): {reason}")]
+    Rejected { code: u16, reason: String },
+
+    #[error("connection closed before joining the room")]
+    ClosedBeforeJoin,
+
+    #[error("not connected to room")]
+    Disconnected,
+
+    #[error(transparent)]
+    Http(#[from] reqwest::Error),
+
+    #[error("server answered {status}: {reason}")]
+    Api { status: u16, reason: String },
+}
+
+impl ClientError {
+    fn from_close(frame: Option<CloseFrame<'static>>) -> Self {
+        match frame {
+            Some(frame) => match ErrorCode::from_close_code(frame.code.into()) {
+                Some(ErrorCode::VersionMismatch) => Self::IncompatibleProtocol,
+                Some(error) => Self::Refused {
+                    error,
+                    reason: frame.reason.into_owned(),
+                },
+                None => Self::Rejected {
+                    code: frame.code.into(),
+                    reason: frame.reason.into_owned(),
+                },
+            },
+            None => Self::ClosedBeforeJoin,
+        }
+    }
+}
+
+/// Fetches the users and player state of a room over the http api, without joining it.
+///
+/// `password` or `host_token` are needed for password protected rooms.
+pub async fn room_status(
+    endpoint: &str,
+    room_id: &str,
+    password: Option<&str>,
+    host_token: Option<&str>,
+) -> Result<RoomStatus, ClientError> {
+    let url = format!(
+        "{}{}",
+        ws_to_http(endpoint),
+        endpoints::API_ROOM.replace(":id", room_id)
+    );
+    let mut request = reqwest::Client::new().get(url);
+    if let Some(password) = password {
+        request = request.query(&[("password", password)]);
+    }
+    if let Some(host_token) = host_token {
+        request = request.bearer_auth(host_token);
+    }
+    let response = request.send().await?;
+    let status = response.status();
+    if !status.is_success() {
+        return Err(ClientError::Api {
+            status: status.as_u16(),
+            reason: response.text().await.unwrap_or_default(),
+        });
+    }
+    Ok(response.json().await?)
+}
+
+/// Maps a websocket base url to the matching http one, `wss://host` becomes `https://host`.
+fn ws_to_http(endpoint: &str) -> String {
+    if let Some(rest) = endpoint.strip_prefix("wss://") {
+        format!("https://{rest}")
+    } else if let Some(rest) = endpoint.strip_prefix("ws://") {
+        format!("http://{rest}")
+    } else {
+        endpoint.to_string()
+    }
+}
+
+/// A connection to one room.
+///
+/// Dropping the client closes the connection.
+pub struct RoomClient {
+    user_id: Uuid,
+    room_id: String,
+    outgoing: mpsc::Sender<ClientMessage>,
+    state: watch::Receiver<RoomState>,
+    messages: broadcast::Receiver<Message>,
+    connection: JoinHandle<()>,
+}
+
+impl RoomClient {
+    /// Creates a new room on `endpoint` (e.g. `wss://tvmate.deepgaurav.com`) and joins it as host.
+    pub async fn host(endpoint: &str, name: impl Into<String>) -> Result<Self, ClientError> {
+        let params = serde_urlencoded::to_string(HostParams {
+            name: name.into(),
+            protocol_version: PROTOCOL_VERSION,
+            encoding: Encoding::Binary,
+        })?;
+        Self::connect(format!("{endpoint}{}?{params}", endpoints::HOST_ROOM)).await
+    }
+
+    pub async fn join(
+        endpoint: &str,
+        name: impl Into<String>,
+        room_id: impl Into<String>,
+    ) -> Result<Self, ClientError> {
+        Self::join_with_params(
+            endpoint,
+            JoinParams {
+                name: name.into(),
+                room_id: room_id.into(),
+                protocol_version: PROTOCOL_VERSION,
+                encoding: Encoding::Binary,
+                password: None,
+                host_token: None,
+                role: UserRole::Member,
+            },
+        )
+        .await
+    }
+
+    /// Joins with a password or host token, protocol version and encoding are always ours.
+    pub async fn join_with_params(
+        endpoint: &str,
+        mut params: JoinParams,
+    ) -> Result<Self, ClientError> {
+        params.protocol_version = PROTOCOL_VERSION;
+        params.encoding = Encoding::Binary;
+        let params = serde_urlencoded::to_string(params)?;
+        Self::connect(format!("{endpoint}{}?{params}", endpoints::JOIN_ROOM)).await
+    }
+
+    async fn connect(url: String) -> Result<Self, ClientError> {
+        info!("Connecting to {url}");
+        let (mut socket, _) = connect_async(url.as_str()).await?;
+        let join_info = loop {
+            let Some(frame) = socket.next().await else {
+                return Err(ClientError::ClosedBeforeJoin);
+            };
+            match decode_frame(frame?)? {
+                Incoming::Message(Message::ServerMessage(
+                    ServerMessage::RoomCreated(info) | ServerMessage::RoomJoined(info),
+                )) => break info,
+                Incoming::Message(message) => {
+                    debug!("Ignoring message before join {message:?}");
+                }
+                Incoming::Close(frame) => return Err(ClientError::from_close(frame)),
+                Incoming::Control => {}
+            }
+        };
+
+        let user_id = join_info.user_id;
+        let room_id = join_info.room_id.clone();
+        let (state_tx, state) = watch::channel(RoomState::from(join_info));
+        let (messages_tx, messages) = broadcast::channel(MESSAGE_BUFFER);
+        let (outgoing, outgoing_rx) = mpsc::channel(MESSAGE_BUFFER);
+        let connection = tokio::spawn(run_connection(
+            socket,
+            user_id,
+            state_tx,
+            messages_tx,
+            outgoing_rx,
+        ));
+        Ok(Self {
+            user_id,
+            room_id,
+            outgoing,
+            state,
+            messages,
+            connection,
+        })
+    }
+
+    pub fn user_id(&self) -> Uuid {
+        self.user_id
+    }
+
+    pub fn room_id(&self) -> &str {
+        &self.room_id
+    }
+
+    /// Snapshot of the reconciled room state.
+    pub fn state(&self) -> RoomState {
+        self.state.borrow().clone()
+    }
+
+    /// Receiver notified on every state change, including disconnection.
+    pub fn watch_state(&self) -> watch::Receiver<RoomState> {
+        self.state.clone()
+    }
+
+    /// Every message received from now on, slow receivers skip what they missed.
+    pub fn subscribe(&self) -> broadcast::Receiver<Message> {
+        self.messages.resubscribe()
+    }
+
+    /// Same as [`RoomClient::subscribe`] as a [`Stream`] which ends on disconnect.
+    pub fn messages(&self) -> impl Stream<Item = Message> {
+        futures::stream::unfold(self.subscribe(), |mut rx| async move {
+            loop {
+                match rx.recv().await {
+                    Ok(message) => return Some((message, rx)),
+                    Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                        warn!("Message stream lagged, skipped {skipped} messages");
+                    }
+                    Err(broadcast::error::RecvError::Closed) => return None,
+                }
+            }
+        })
+    }
+
+    pub fn is_connected(&self) -> bool {
+        self.state.borrow().connected
+    }
+
+    /// Sends `message` on behalf of this user.
+    pub async fn send(&self, message: ClientMessage) -> Result<(), ClientError> {
+        self.outgoing
+            .send(message)
+            .await
+            .map_err(|_| ClientError::Disconnected)
+    }
+
+    pub async fn play(&self, time: f64) -> Result<(), ClientError> {
+        self.send(ClientMessage::Play(time)).await
+    }
+
+    pub async fn pause(&self, time: f64) -> Result<(), ClientError> {
+        self.send(ClientMessage::Pause(time)).await
+    }
+
+    /// Seeks everyone to `time`, keeping the current play state.
+    pub async fn seek(&self, time: f64) -> Result<(), ClientError> {
+        let is_playing = !self.state.borrow().player_status.is_paused();
+        self.send(ClientMessage::Seek(time, is_playing)).await
+    }
+
+    pub async fn chat(&self, message: impl Into<String>) -> Result<(), ClientError> {
+        self.send(ClientMessage::Chat(message.into())).await
+    }
+
+    /// Flushes queued messages and closes the connection.
+    pub async fn close(self) {
+        drop(self.outgoing);
+        if let Err(err) = self.connection.await {
+            warn!("Connection task failed {err:?}");
+        }
+    }
+}
+
+enum Incoming {
+    Message(Message),
+    Close(Option<CloseFrame<'static>>),
+    Control,
+}
+
+fn decode_frame(frame: WsMessage) -> Result<Incoming, EncodingError> {
+    match frame {
+        WsMessage::Binary(data) => Ok(Incoming::Message(Encoding::Binary.decode(&data)?)),
+        WsMessage::Text(text) => Ok(Incoming::Message(Encoding::Json.decode(text.as_bytes())?)),
+        WsMessage::Close(frame) => Ok(Incoming::Close(frame)),
+        WsMessage::Ping(_) | WsMessage::Pong(_) | WsMessage::Frame(_) => Ok(Incoming::Control),
+    }
+}
+
+async fn run_connection(
+    socket: Socket,
+    user_id: Uuid,
+    state: watch::Sender<RoomState>,
+    messages: broadcast::Sender<Message>,
+    mut outgoing: mpsc::Receiver<ClientMessage>,
+) {
+    let (mut sink, mut stream) = socket.split();
+    let mut unconfirmed = Unconfirmed::default();
+    loop {
+        tokio::select! {
+            frame = stream.next() => {
+                let frame = match frame {
+                    Some(Ok(frame)) => frame,
+                    Some(Err(err)) => {
+                        warn!("Msg receive error {err:#?}");
+                        break;
+                    }
+                    None => break,
+                };
+                match decode_frame(frame) {
+                    Ok(Incoming::Message(message)) => {
+                        state.send_modify(|state| state.apply_received(&message, &mut unconfirmed));
+                        // Nobody listening is fine, the state is still reconciled.
+                        let _ = messages.send(message);
+                    }
+                    Ok(Incoming::Close(frame)) => {
+                        info!("Server closed connection {frame:?}");
+                        break;
+                    }
+                    Ok(Incoming::Control) => {}
+                    Err(err) => {
+                        warn!("Received msg decode error {err:#?}");
+                    }
+                }
+            }
+            message = outgoing.recv() => {
+                let Some(message) = message else {
+                    // Client dropped
+                    if let Err(err) = sink.send(WsMessage::Close(None)).await {
+                        debug!("Cant send close {err:?}");
+                    }
+                    break;
+                };
+                let own = message.clone();
+                let message = Message::ClientMessage((user_id, message));
+                let frame = match Encoding::Binary.encode(&message) {
+                    Ok(Frame::Binary(data)) => WsMessage::Binary(data),
+                    Ok(Frame::Text(data)) => WsMessage::Text(data),
+                    Err(err) => {
+                        warn!("Failed to serialize message {err:#?}");
+                        continue;
+                    }
+                };
+                if let Err(err) = sink.send(frame).await {
+                    warn!("Failed to send message {err:#?}");
+                    break;
+                }
+                state.send_modify(|state| state.apply_own(&own, &mut unconfirmed));
+            }
+        }
+    }
+    state.send_modify(|state| state.connected = false);
+}
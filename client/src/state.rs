@@ -0,0 +1,563 @@
+use std::{
+    collections::VecDeque,
+    time::{Duration, Instant},
+};
+
+use common::{
+    message::{
+        ClientMessage, ErrorCode, Field, Message, RateLimited, RoomJoinInfo, RtcConfig,
+        ServerMessage, UserJoined, UserLeft, ValidationError,
+    },
+    PlayerStatus, UserMeta, UserState,
+};
+use uuid::Uuid;
+
+/// Own changes kept before the server confirmed them, a periodic `Update` must not grow it forever.
+const MAX_UNCONFIRMED: usize = 32;
+/// The server answers a refused message right away and says nothing about accepted ones, so a
+/// change without an answer after this long counts as accepted.
+const CONFIRM_AFTER: Duration = Duration::from_secs(5);
+
+/// Room as seen by this client, kept up to date from every message sent and received.
+///
+/// Own player and video changes take effect right away, the server does not echo them. Should
+/// it reject one with [`ServerMessage::Invalid`], [`ServerMessage::RateLimited`] or
+/// [`ServerMessage::Error`], that change is taken back and later own changes are applied again
+/// on top. Names and appearances wait for the server to send them back.
+#[derive(Debug, Clone)]
+pub struct RoomState {
+    pub room_id: String,
+    pub user_id: Uuid,
+    pub users: Vec<UserMeta>,
+    pub player_status: PlayerStatus,
+    /// `None` for spectators and when the server runs in sync-only mode.
+    pub rtc_config: Option<RtcConfig>,
+    /// Last error reported by the server.
+    pub last_error: Option<String>,
+    pub connected: bool,
+}
+
+impl From<RoomJoinInfo> for RoomState {
+    fn from(info: RoomJoinInfo) -> Self {
+        Self {
+            room_id: info.room_id,
+            user_id: info.user_id,
+            users: info.users,
+            player_status: info.player_status,
+            rtc_config: info.rtc_config,
+            last_error: None,
+            connected: true,
+        }
+    }
+}
+
+/// Own changes applied to a [`RoomState`] that the server has not confirmed yet, oldest first.
+#[derive(Debug, Default)]
+pub(crate) struct Unconfirmed {
+    changes: VecDeque<Change>,
+}
+
+/// An own message and what it replaced.
+#[derive(Debug)]
+struct Change {
+    message: ClientMessage,
+    before: Before,
+    sent_at: Instant,
+}
+
+#[derive(Debug)]
+enum Before {
+    Player(PlayerStatus),
+    Video(UserState),
+}
+
+impl Unconfirmed {
+    fn push(&mut self, change: Change) {
+        if self.changes.len() == MAX_UNCONFIRMED {
+            self.changes.pop_front();
+        }
+        self.changes.push_back(change);
+    }
+
+    /// Forgets changes sent before `now - CONFIRM_AFTER`, the server kept those.
+    fn confirm(&mut self, now: Instant) {
+        while self
+            .changes
+            .front()
+            .is_some_and(|change| now.duration_since(change.sent_at) >= CONFIRM_AFTER)
+        {
+            self.changes.pop_front();
+        }
+    }
+
+    /// Index of the change `refusal` answers. Refusals do not say which message they are
+    /// about, the newest one of its kind is the likeliest since limits hit the tail of a burst.
+    fn refused(&self, refusal: &Refusal) -> Option<usize> {
+        self.changes
+            .iter()
+            .rposition(|change| refusal.applies_to(change.message.kind()))
+    }
+}
+
+/// Message kinds a server reply refuses.
+enum Refusal<'a> {
+    Kind(&'a str),
+    Kinds(&'static [&'static str]),
+    Any,
+}
+
+impl<'a> Refusal<'a> {
+    /// `None` if `message` refuses nothing this client keeps track of.
+    fn from_message(message: &'a ServerMessage) -> Option<Self> {
+        match message {
+            ServerMessage::RateLimited(RateLimited { kind, .. }) => Some(Self::Kind(kind)),
+            ServerMessage::Invalid(
+                ValidationError::Empty(field)
+                | ValidationError::TooLong(field, _)
+                | ValidationError::Invalid(field),
+            ) => match field {
+                Field::Position => Some(Self::Kinds(&["play", "pause", "seek", "update"])),
+                Field::VideoName | Field::VideoDuration => Some(Self::Kinds(&["set_video_meta"])),
+                _ => None,
+            },
+            ServerMessage::Error(ErrorCode::SpectatorNotAllowed) => Some(Self::Any),
+            _ => None,
+        }
+    }
+
+    fn applies_to(&self, kind: &str) -> bool {
+        match self {
+            Self::Kind(refused) => *refused == kind,
+            Self::Kinds(refused) => refused.contains(&kind),
+            Self::Any => true,
+        }
+    }
+}
+
+impl RoomState {
+    pub fn user(&self, id: Uuid) -> Option<&UserMeta> {
+        self.users.iter().find(|user| user.id == id)
+    }
+
+    pub fn me(&self) -> Option<&UserMeta> {
+        self.user(self.user_id)
+    }
+
+    /// Applies a message this client sent, remembering what it replaced in `unconfirmed`.
+    pub(crate) fn apply_own(&mut self, message: &ClientMessage, unconfirmed: &mut Unconfirmed) {
+        unconfirmed.confirm(Instant::now());
+        // Sent back to everyone including us, possibly cleaned up.
+        if matches!(
+            message,
+            ClientMessage::SetName(_) | ClientMessage::SetAppearance(_)
+        ) {
+            return;
+        }
+        if self.me().is_some_and(|me| me.role.is_spectator()) && !message.is_allowed_for_spectator()
+        {
+            return;
+        }
+        let Some(before) = self.before(message) else {
+            return;
+        };
+        unconfirmed.push(Change {
+            message: message.clone(),
+            before,
+            sent_at: Instant::now(),
+        });
+        self.apply(&Message::ClientMessage((self.user_id, message.clone())));
+    }
+
+    /// What applying `message` replaces, `None` for messages not kept track of.
+    fn before(&self, message: &ClientMessage) -> Option<Before> {
+        match message {
+            ClientMessage::Play(_)
+            | ClientMessage::Pause(_)
+            | ClientMessage::Seek(..)
+            | ClientMessage::Update(_) => Some(Before::Player(self.player_status.clone())),
+            ClientMessage::SetVideoMeta(_) => Some(Before::Video(self.me()?.state.clone())),
+            _ => None,
+        }
+    }
+
+    /// Applies a message from the server, undoing own changes it refused.
+    pub(crate) fn apply_received(&mut self, message: &Message, unconfirmed: &mut Unconfirmed) {
+        unconfirmed.confirm(Instant::now());
+        match message {
+            Message::ServerMessage(server_message) => {
+                if let Some(index) = Refusal::from_message(server_message)
+                    .and_then(|refusal| unconfirmed.refused(&refusal))
+                {
+                    self.undo(unconfirmed, index);
+                }
+                if matches!(
+                    server_message,
+                    ServerMessage::RoomCreated(_)
+                        | ServerMessage::RoomJoined(_)
+                        | ServerMessage::UserJoined(_)
+                        | ServerMessage::UserLeft(_)
+                ) {
+                    // Carries the room as the server has it, nothing left to undo.
+                    unconfirmed.changes.clear();
+                }
+            }
+            Message::ClientMessage((_, client_message)) => {
+                if matches!(
+                    client_message,
+                    ClientMessage::Play(_)
+                        | ClientMessage::Pause(_)
+                        | ClientMessage::Seek(..)
+                        | ClientMessage::Update(_)
+                ) {
+                    // Someone else moved the player after us, undoing ours would lose theirs.
+                    unconfirmed
+                        .changes
+                        .retain(|change| !matches!(change.before, Before::Player(_)));
+                }
+            }
+        }
+        self.apply(message);
+    }
+
+    /// Takes back the change at `index`, then applies the own changes to the same part of the
+    /// state that were sent after it again.
+    fn undo(&mut self, unconfirmed: &mut Unconfirmed, index: usize) {
+        let Some(refused) = unconfirmed.changes.remove(index) else {
+            return;
+        };
+        let player = matches!(refused.before, Before::Player(_));
+        self.restore(refused.before);
+        for change in unconfirmed.changes.range_mut(index..) {
+            if matches!(change.before, Before::Player(_)) != player {
+                continue;
+            }
+            if let Some(before) = self.before(&change.message) {
+                change.before = before;
+            }
+            self.apply(&Message::ClientMessage((
+                self.user_id,
+                change.message.clone(),
+            )));
+        }
+    }
+
+    fn restore(&mut self, before: Before) {
+        match before {
+            Before::Player(status) => self.player_status = status,
+            Before::Video(state) => {
+                let user_id = self.user_id;
+                if let Some(user) = self.users.iter_mut().find(|u| u.id == user_id) {
+                    user.state = state;
+                }
+            }
+        }
+    }
+
+    /// Applies `message` the same way the web client does, unknown or signalling
+    /// messages leave the state untouched.
+    pub fn apply(&mut self, message: &Message) {
+        match message {
+            Message::ServerMessage(message) => match message {
+                ServerMessage::RoomCreated(info) | ServerMessage::RoomJoined(info) => {
+                    *self = info.clone().into();
+                }
+                ServerMessage::UserJoined(UserJoined {
+                    users,
+                    player_status,
+                    ..
+                })
+                | ServerMessage::UserLeft(UserLeft {
+                    users,
+                    player_status,
+                    ..
+                }) => {
+                    self.users = users.clone();
+                    self.player_status = player_status.clone();
+                }
+                ServerMessage::Error(error) => {
+                    self.last_error = Some(error.to_string());
+                }
+                ServerMessage::OverlayToken(_)
+                | ServerMessage::Notice(_)
+                | ServerMessage::Restarting(_)
+                | ServerMessage::RateLimited(_) => {}
+                ServerMessage::Invalid(error) => {
+                    self.last_error = Some(error.to_string());
+                }
+            },
+            Message::ClientMessage((from_user, message)) => match message {
+                ClientMessage::SetVideoMeta(video_meta) => {
+                    if let Some(user) = self.users.iter_mut().find(|u| u.id == *from_user) {
+                        user.state = UserState::VideoSelected(video_meta.clone());
+                    }
+                }
+                ClientMessage::Play(time) => {
+                    self.player_status = PlayerStatus::Playing(*time);
+                }
+                ClientMessage::Pause(time) => {
+                    self.player_status = PlayerStatus::Paused(*time);
+                }
+                ClientMessage::Seek(time, _) | ClientMessage::Update(time) => {
+                    match &mut self.player_status {
+                        PlayerStatus::Paused(val) | PlayerStatus::Playing(val) => *val = *time,
+                    }
+                }
+                ClientMessage::SetName(name) => {
+                    if let Some(user) = self.users.iter_mut().find(|u| u.id == *from_user) {
+                        user.name = name.clone();
+                    }
+                }
+                ClientMessage::SetAppearance(appearance) => {
+                    if let Some(user) = self.users.iter_mut().find(|u| u.id == *from_user) {
+                        user.appearance = appearance.clone();
+                    }
+                }
+                ClientMessage::Chat(_)
+                | ClientMessage::SendSessionDesc(..)
+                | ClientMessage::ReceivedSessionDesc(_)
+                | ClientMessage::ExchangeCandidate(..)
+                | ClientMessage::RequestCall(..)
+                | ClientMessage::RequestVideoShare(_)
+                | ClientMessage::CreateOverlayToken => {}
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use common::{message::VideoMeta, UserRole};
+
+    use super::*;
+
+    fn user(role: UserRole) -> UserMeta {
+        UserMeta {
+            id: Uuid::new_v4(),
+            name: "me".to_string(),
+            state: UserState::VideoNotSelected,
+            role,
+            appearance: Default::default(),
+        }
+    }
+
+    fn room(role: UserRole) -> RoomState {
+        let me = user(role);
+        RoomState {
+            room_id: "room".to_string(),
+            user_id: me.id,
+            users: vec![me],
+            player_status: PlayerStatus::Paused(10.0),
+            rtc_config: None,
+            last_error: None,
+            connected: true,
+        }
+    }
+
+    fn server(message: ServerMessage) -> Message {
+        Message::ServerMessage(message)
+    }
+
+    #[test]
+    fn applies_player_messages() {
+        let mut state = room(UserRole::Member);
+        let other = Uuid::new_v4();
+        state.apply(&Message::ClientMessage((other, ClientMessage::Play(12.0))));
+        assert!(matches!(state.player_status, PlayerStatus::Playing(t) if t == 12.0));
+        state.apply(&Message::ClientMessage((
+            other,
+            ClientMessage::Seek(30.0, true),
+        )));
+        assert!(matches!(state.player_status, PlayerStatus::Playing(t) if t == 30.0));
+        state.apply(&Message::ClientMessage((other, ClientMessage::Pause(31.0))));
+        assert!(matches!(state.player_status, PlayerStatus::Paused(t) if t == 31.0));
+    }
+
+    #[test]
+    fn applies_user_changes() {
+        let mut state = room(UserRole::Member);
+        let me = state.user_id;
+        state.apply(&Message::ClientMessage((
+            me,
+            ClientMessage::SetName("renamed".to_string()),
+        )));
+        assert_eq!(state.me().unwrap().name, "renamed");
+        let other = user(UserRole::Member);
+        state.apply(&server(ServerMessage::UserJoined(UserJoined {
+            new_user: other.id,
+            users: vec![state.me().unwrap().clone(), other.clone()],
+            player_status: PlayerStatus::Playing(5.0),
+        })));
+        assert_eq!(state.users.len(), 2);
+        assert!(matches!(state.player_status, PlayerStatus::Playing(t) if t == 5.0));
+        state.apply(&server(ServerMessage::Invalid(ValidationError::Empty(
+            Field::Name,
+        ))));
+        assert!(state.last_error.is_some());
+    }
+
+    #[test]
+    fn waits_for_echoed_names() {
+        let mut state = room(UserRole::Member);
+        let mut unconfirmed = Unconfirmed::default();
+        state.apply_own(
+            &ClientMessage::SetName("  renamed ".to_string()),
+            &mut unconfirmed,
+        );
+        assert_eq!(state.me().unwrap().name, "me");
+    }
+
+    #[test]
+    fn spectators_do_not_move_the_player() {
+        let mut state = room(UserRole::Spectator);
+        let mut unconfirmed = Unconfirmed::default();
+        state.apply_own(&ClientMessage::Play(10.0), &mut unconfirmed);
+        assert!(matches!(state.player_status, PlayerStatus::Paused(_)));
+        assert!(unconfirmed.changes.is_empty());
+    }
+
+    #[test]
+    fn undoes_refused_player_changes() {
+        let mut state = room(UserRole::Member);
+        let mut unconfirmed = Unconfirmed::default();
+        state.apply_own(&ClientMessage::Play(10.0), &mut unconfirmed);
+        assert!(matches!(state.player_status, PlayerStatus::Playing(_)));
+        state.apply_received(
+            &server(ServerMessage::RateLimited(RateLimited {
+                kind: "play".to_string(),
+                retry_after_ms: 100,
+            })),
+            &mut unconfirmed,
+        );
+        assert!(matches!(state.player_status, PlayerStatus::Paused(t) if t == 10.0));
+
+        state.apply_own(&ClientMessage::Seek(f64::MAX, false), &mut unconfirmed);
+        state.apply_received(
+            &server(ServerMessage::Invalid(ValidationError::Invalid(
+                Field::Position,
+            ))),
+            &mut unconfirmed,
+        );
+        assert!(matches!(state.player_status, PlayerStatus::Paused(t) if t == 10.0));
+        assert!(unconfirmed.changes.is_empty());
+    }
+
+    #[test]
+    fn undoes_refused_video_meta() {
+        let mut state = room(UserRole::Member);
+        let mut unconfirmed = Unconfirmed::default();
+        state.apply_own(
+            &ClientMessage::SetVideoMeta(VideoMeta {
+                name: "movie.mkv".to_string(),
+                duration: Some(-1.0),
+            }),
+            &mut unconfirmed,
+        );
+        assert!(matches!(
+            state.me().unwrap().state,
+            UserState::VideoSelected(_)
+        ));
+        state.apply_received(
+            &server(ServerMessage::Invalid(ValidationError::Invalid(
+                Field::VideoDuration,
+            ))),
+            &mut unconfirmed,
+        );
+        assert!(matches!(
+            state.me().unwrap().state,
+            UserState::VideoNotSelected
+        ));
+    }
+
+    #[test]
+    fn keeps_confirmed_state() {
+        let mut state = room(UserRole::Member);
+        let mut unconfirmed = Unconfirmed::default();
+        state.apply_own(&ClientMessage::Play(10.0), &mut unconfirmed);
+        let other = Uuid::new_v4();
+        state.apply_received(
+            &Message::ClientMessage((other, ClientMessage::Pause(20.0))),
+            &mut unconfirmed,
+        );
+        state.apply_received(
+            &server(ServerMessage::RateLimited(RateLimited {
+                kind: "play".to_string(),
+                retry_after_ms: 100,
+            })),
+            &mut unconfirmed,
+        );
+        assert!(matches!(state.player_status, PlayerStatus::Paused(t) if t == 20.0));
+
+        state.apply_own(&ClientMessage::Play(20.0), &mut unconfirmed);
+        let users = state.users.clone();
+        state.apply_received(
+            &server(ServerMessage::UserLeft(UserLeft {
+                user_left: other,
+                users,
+                player_status: PlayerStatus::Playing(21.0),
+            })),
+            &mut unconfirmed,
+        );
+        assert!(unconfirmed.changes.is_empty());
+    }
+
+    #[test]
+    fn keeps_accepted_seek_when_next_is_refused() {
+        let mut state = room(UserRole::Member);
+        let mut unconfirmed = Unconfirmed::default();
+        state.apply_own(&ClientMessage::Seek(20.0, false), &mut unconfirmed);
+        state.apply_own(&ClientMessage::Seek(30.0, false), &mut unconfirmed);
+        state.apply_received(
+            &server(ServerMessage::RateLimited(RateLimited {
+                kind: "seek".to_string(),
+                retry_after_ms: 100,
+            })),
+            &mut unconfirmed,
+        );
+        assert!(matches!(state.player_status, PlayerStatus::Paused(t) if t == 20.0));
+        assert_eq!(unconfirmed.changes.len(), 1);
+    }
+
+    #[test]
+    fn reapplies_changes_after_the_refused_one() {
+        let mut state = room(UserRole::Member);
+        let mut unconfirmed = Unconfirmed::default();
+        state.apply_own(&ClientMessage::Play(10.0), &mut unconfirmed);
+        state.apply_own(&ClientMessage::Seek(40.0, true), &mut unconfirmed);
+        state.apply_received(
+            &server(ServerMessage::RateLimited(RateLimited {
+                kind: "play".to_string(),
+                retry_after_ms: 100,
+            })),
+            &mut unconfirmed,
+        );
+        // The seek went through but does not start playback on its own.
+        assert!(matches!(state.player_status, PlayerStatus::Paused(t) if t == 40.0));
+    }
+
+    #[test]
+    fn confirms_unanswered_changes() {
+        let mut state = room(UserRole::Member);
+        let mut unconfirmed = Unconfirmed::default();
+        state.apply_own(&ClientMessage::Play(10.0), &mut unconfirmed);
+        unconfirmed.confirm(Instant::now() + CONFIRM_AFTER);
+        assert!(unconfirmed.changes.is_empty());
+        state.apply_received(
+            &server(ServerMessage::RateLimited(RateLimited {
+                kind: "play".to_string(),
+                retry_after_ms: 100,
+            })),
+            &mut unconfirmed,
+        );
+        assert!(matches!(state.player_status, PlayerStatus::Playing(t) if t == 10.0));
+    }
+
+    #[test]
+    fn caps_unconfirmed_changes() {
+        let mut state = room(UserRole::Member);
+        let mut unconfirmed = Unconfirmed::default();
+        for time in 0..MAX_UNCONFIRMED * 2 {
+            state.apply_own(&ClientMessage::Update(time as f64), &mut unconfirmed);
+        }
+        assert_eq!(unconfirmed.changes.len(), MAX_UNCONFIRMED);
+    }
+}
@@ -0,0 +1,168 @@
+//! Bodies of the JSON room API under [`crate::endpoints::API_ROOMS`], the admin API
+//! under [`crate::endpoints::ADMIN_ROOMS`] and diagnostics under [`crate::endpoints::DIAGNOSTICS`].
+
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::{PlayerStatus, UserMeta};
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct CreateRoomRequest {
+    /// Maximum users in the room, capped by the server's limit.
+    #[serde(default)]
+    pub capacity: Option<usize>,
+    /// Required from everyone joining except the host.
+    #[serde(default)]
+    pub password: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct CreateRoomResponse {
+    pub room_id: String,
+    /// Pass as `host_token` when joining to become host, or as a bearer token to manage the room.
+    pub host_token: String,
+    /// Seconds the room is kept while nobody is in it.
+    pub expires_in_secs: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct RoomStatus {
+    pub room_id: String,
+    pub users: Vec<UserMeta>,
+    pub host: Option<UserMeta>,
+    pub player_status: PlayerStatus,
+    pub capacity: usize,
+    pub has_password: bool,
+    pub age_secs: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct NoticeRequest {
+    pub message: String,
+}
+
+/// Everything the app knows that could explain a bug report, uploaded on the user's request.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct DiagnosticsBundle {
+    pub app_version: String,
+    pub protocol_version: u32,
+    pub platform: PlatformInfo,
+    /// Missing when the user is not in a room.
+    pub room: Option<DiagnosticsRoom>,
+    pub peers: Vec<PeerStats>,
+    /// The end of the in-memory tracing buffer, see [`DiagnosticsBundle::truncate_logs`].
+    pub logs: String,
+    /// Older log lines were dropped to keep the bundle uploadable.
+    #[serde(default)]
+    pub logs_truncated: bool,
+}
+
+/// Logs kept in a bundle, well under the server's default `diagnostics.max_bundle_kb` of 2 MiB
+/// so the rest of the bundle always fits.
+pub const MAX_DIAGNOSTICS_LOG_BYTES: usize = 1024 * 1024;
+
+impl DiagnosticsBundle {
+    /// Keeps only the last `max_bytes` of the logs, starting at a whole line.
+    pub fn truncate_logs(&mut self, max_bytes: usize) {
+        if self.logs.len() <= max_bytes {
+            return;
+        }
+        let mut start = self.logs.len() - max_bytes;
+        while !self.logs.is_char_boundary(start) {
+            start += 1;
+        }
+        if let Some(newline) = self.logs[start..].find('\n') {
+            start += newline + 1;
+        }
+        self.logs.drain(..start);
+        self.logs_truncated = true;
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct PlatformInfo {
+    pub user_agent: String,
+    pub platform: String,
+    pub language: String,
+    /// Sent from the desktop or mobile app rather than a browser.
+    pub native: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct DiagnosticsRoom {
+    pub room_id: String,
+    pub user_id: Uuid,
+    pub users: Vec<UserMeta>,
+    pub player_status: PlayerStatus,
+    pub rtc_support: String,
+}
+
+/// State of one peer connection, `report` is the browser's `RTCStatsReport` keyed by stat id.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct PeerStats {
+    pub user_id: Uuid,
+    pub connection_state: String,
+    pub report: serde_json::Value,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct DiagnosticsResponse {
+    /// Short reference the user quotes in a bug report.
+    pub code: String,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bundle(logs: &str) -> DiagnosticsBundle {
+        DiagnosticsBundle {
+            app_version: String::new(),
+            protocol_version: 0,
+            platform: PlatformInfo {
+                user_agent: String::new(),
+                platform: String::new(),
+                language: String::new(),
+                native: false,
+            },
+            room: None,
+            peers: vec![],
+            logs: logs.to_string(),
+            logs_truncated: false,
+        }
+    }
+
+    #[test]
+    fn keeps_short_logs() {
+        let mut bundle = bundle("one\ntwo\n");
+        bundle.truncate_logs(8);
+        assert_eq!(bundle.logs, "one\ntwo\n");
+        assert!(!bundle.logs_truncated);
+    }
+
+    #[test]
+    fn keeps_whole_lines_from_the_end() {
+        let mut bundle = bundle("first line\nsecond\nthird\n");
+        bundle.truncate_logs(10);
+        assert_eq!(bundle.logs, "third\n");
+        assert!(bundle.logs_truncated);
+    }
+
+    #[test]
+    fn cuts_on_char_boundary() {
+        // A single line with no newline to cut at, the limit falls inside an 'é'.
+        let mut bundle = bundle("éééé");
+        bundle.truncate_logs(3);
+        assert_eq!(bundle.logs, "é");
+        assert!(bundle.logs_truncated);
+    }
+}
@@ -0,0 +1,18 @@
+//! Prints the protocol JSON Schema to stdout, a copy is kept in `common/protocol-schema.json`
+//! and checked by `cargo test -p common --features schema`.
+//!
+//! ```sh
+//! cargo run -p common --features schema --bin protocol-schema > common/protocol-schema.json
+//! npx json-schema-to-typescript common/protocol-schema.json > protocol.d.ts
+//! ```
+
+fn main() {
+    let schema = common::schema::protocol_schema();
+    match serde_json::to_string_pretty(&schema) {
+        Ok(schema) => println!("{schema}"),
+        Err(err) => {
+            eprintln!("Failed to serialize schema {err}");
+            std::process::exit(1);
+        }
+    }
+}
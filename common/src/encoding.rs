@@ -0,0 +1,47 @@
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::message::Message;
+
+/// Wire format of a websocket connection.
+///
+/// The browser and native apps use bincode over binary frames, third party clients can
+/// pick `json` to exchange the same [`Message`] as text frames.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "lowercase")]
+pub enum Encoding {
+    #[default]
+    Binary,
+    Json,
+}
+
+#[derive(Error, Debug)]
+pub enum EncodingError {
+    #[error(transparent)]
+    Bincode(#[from] bincode::Error),
+    #[error(transparent)]
+    Json(#[from] serde_json::Error),
+}
+
+/// An encoded message along with the kind of frame it has to travel in.
+pub enum Frame {
+    Binary(Vec<u8>),
+    Text(String),
+}
+
+impl Encoding {
+    pub fn encode(&self, message: &Message) -> Result<Frame, EncodingError> {
+        match self {
+            Encoding::Binary => Ok(Frame::Binary(bincode::serialize(message)?)),
+            Encoding::Json => Ok(Frame::Text(serde_json::to_string(message)?)),
+        }
+    }
+
+    pub fn decode(&self, data: &[u8]) -> Result<Message, EncodingError> {
+        match self {
+            Encoding::Binary => Ok(bincode::deserialize(data)?),
+            Encoding::Json => Ok(serde_json::from_slice(data)?),
+        }
+    }
+}
@@ -1,2 +1,15 @@
 pub static HOST_ROOM: &str = "/hoost-room/ws";
 pub static JOIN_ROOM: &str = "/join-room/ws";
+pub static VERSION: &str = "/version";
+pub static HEALTHZ: &str = "/healthz";
+pub static READYZ: &str = "/readyz";
+pub static API_ROOMS: &str = "/api/rooms";
+pub static API_ROOM: &str = "/api/rooms/:id";
+pub static ROOM_EVENTS: &str = "/rooms/:id/events";
+pub static ADMIN_ROOMS: &str = "/admin/api/rooms";
+pub static ADMIN_ROOM: &str = "/admin/api/rooms/:id";
+pub static ADMIN_ROOM_NOTICE: &str = "/admin/api/rooms/:id/notice";
+pub static ADMIN_NOTICE: &str = "/admin/api/notice";
+pub static METRICS: &str = "/metrics";
+pub static DIAGNOSTICS: &str = "/api/diagnostics";
+pub static ADMIN_DIAGNOSTICS: &str = "/admin/api/diagnostics/:code";
@@ -1,8 +1,13 @@
+pub mod api;
+pub mod encoding;
 pub mod endpoints;
 pub mod message;
 pub mod message_sender;
 pub mod params;
+#[cfg(feature = "schema")]
+pub mod schema;
 pub mod util;
+pub mod version;
 
 use std::time::Instant;
 
@@ -22,6 +27,7 @@ pub struct User {
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub enum UserState {
     VideoNotSelected,
     VideoSelected(VideoMeta),
@@ -38,6 +44,7 @@ impl UserState {
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub enum PlayerStatus {
     Paused(f64),
     Playing(f64),
@@ -62,36 +69,190 @@ impl PlayerStatus {
     }
 }
 
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "lowercase")]
+pub enum UserRole {
+    #[default]
+    Member,
+    /// Follows playback and chats, but cannot control playback or call and does not take
+    /// one of the room's seats.
+    Spectator,
+}
+
+impl UserRole {
+    #[must_use]
+    pub fn is_spectator(&self) -> bool {
+        matches!(self, Self::Spectator)
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub struct UserMeta {
     pub id: Uuid,
     pub name: String,
     pub state: UserState,
+    #[serde(default)]
+    pub role: UserRole,
+    #[serde(default)]
+    pub appearance: Appearance,
+}
+
+/// How a user shows up next to their name, picked in the profile.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct Appearance {
+    /// Css hex colour, `#rrggbb`.
+    pub color: Option<String>,
+    pub avatar: Option<Avatar>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "lowercase")]
+pub enum Avatar {
+    Emoji(String),
+    /// `data:image/...;base64,...` url, the server caps its size.
+    Image(String),
+}
+
+/// Only `#rrggbb` is accepted so colours can go straight into a style attribute.
+pub fn is_hex_color(color: &str) -> bool {
+    color.len() == 7 && color.starts_with('#') && color[1..].chars().all(|c| c.is_ascii_hexdigit())
 }
 
 pub struct Room {
     pub users: Vec<User>,
     pub player_status: PlayerStatus,
+    /// User allowed to manage the room, `None` until a pre-created room is claimed.
+    pub host: Option<Uuid>,
+    #[cfg(feature = "ssr")]
+    pub options: RoomOptions,
+    #[cfg(feature = "ssr")]
+    pub created_at: Instant,
+    /// Tokens issued by the host for [`endpoints::ROOM_EVENTS`].
+    #[cfg(feature = "ssr")]
+    pub overlay_tokens: Vec<String>,
+    /// Read-only overlay subscribers, they do not count as users.
+    #[cfg(feature = "ssr")]
+    pub observers: tokio::sync::broadcast::Sender<Message>,
 }
 
 #[cfg(feature = "ssr")]
 mod ssr {
+    use api::RoomStatus;
     use futures::{stream::FuturesUnordered, StreamExt, TryStreamExt};
-    use message::{RoomJoinInfo, RtcConfig};
+    use message::{ClientMessage, ErrorCode, RoomJoinInfo, RtcConfig, ServerMessage, TurnConfig};
     use thiserror::Error;
-    use tokio::sync::RwLock;
-    use tracing::warn;
+    use tokio::sync::{broadcast, RwLock};
+    use tracing::{instrument, warn, Span};
     use unicase::UniCase;
-    use util::generate_random_string;
+    use util::{generate_random_string, token_matches};
 
     use super::*;
-    use std::{collections::HashMap, env::VarError, sync::Arc, time::SystemTimeError};
+    use std::{
+        collections::HashMap, str::FromStr, sync::Arc, time::Duration, time::SystemTimeError,
+    };
+
+    /// Which WebRTC features the server advertises to clients.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+    #[serde(rename_all = "kebab-case")]
+    pub enum RtcMode {
+        /// No ICE servers at all, only playback sync and chat.
+        SyncOnly,
+        /// Only the STUN server is advertised, no TURN credentials are generated.
+        StunOnly,
+        /// STUN and TURN, requires a TURN secret.
+        #[default]
+        Full,
+    }
 
-    const MAX_ROOM_SIZE: usize = 2;
+    #[derive(Error, Debug)]
+    #[error("unknown rtc mode {0:?}, expected one of sync-only, stun-only, full")]
+    pub struct ParseRtcModeError(String);
+
+    impl FromStr for RtcMode {
+        type Err = ParseRtcModeError;
+
+        fn from_str(s: &str) -> Result<Self, Self::Err> {
+            match s.trim().to_lowercase().as_str() {
+                "sync-only" | "sync" | "none" => Ok(Self::SyncOnly),
+                "stun-only" | "stun" => Ok(Self::StunOnly),
+                "full" | "turn" => Ok(Self::Full),
+                _ => Err(ParseRtcModeError(s.to_string())),
+            }
+        }
+    }
+
+    /// ICE servers advertised to clients in [`RoomJoinInfo`].
+    #[derive(Debug, Clone)]
+    pub struct RtcSettings {
+        pub mode: RtcMode,
+        pub stun_urls: Vec<String>,
+        pub turn_url: String,
+        pub turn_secret: Option<String>,
+        /// How long generated TURN credentials stay valid.
+        pub turn_ttl: Duration,
+    }
+
+    impl Default for RtcSettings {
+        fn default() -> Self {
+            Self {
+                mode: RtcMode::default(),
+                stun_urls: vec!["stun:coturn.deepgaurav.com:3478".to_string()],
+                turn_url: "turn:coturn.deepgaurav.com:3478?transport=udp".to_string(),
+                turn_secret: None,
+                turn_ttl: Duration::from_secs(3600),
+            }
+        }
+    }
+
+    #[derive(Debug, Clone)]
+    pub struct RoomSettings {
+        pub max_room_size: usize,
+        /// Spectators allowed per room on top of [`Self::max_room_size`].
+        pub max_spectators: usize,
+        pub rtc: RtcSettings,
+    }
+
+    impl Default for RoomSettings {
+        fn default() -> Self {
+            Self {
+                max_room_size: 2,
+                max_spectators: 20,
+                rtc: RtcSettings::default(),
+            }
+        }
+    }
+
+    /// Per room overrides, set when a room is created through the API.
+    #[derive(Debug, Clone, Default)]
+    pub struct RoomOptions {
+        /// Lower limit than [`RoomSettings::max_room_size`] for this room.
+        pub capacity: Option<usize>,
+        pub password: Option<String>,
+        /// Lets its holder claim host and manage the room through the API.
+        pub host_token: Option<String>,
+        /// Keeps the room alive while empty until then.
+        pub keep_until: Option<Instant>,
+    }
+
+    /// What survives a server restart, users have to reconnect.
+    #[derive(Debug, Clone, Serialize, Deserialize)]
+    pub struct RoomSnapshot {
+        pub room_id: String,
+        pub player_status: PlayerStatus,
+        pub capacity: Option<usize>,
+        pub password: Option<String>,
+        pub host_token: Option<String>,
+        pub overlay_tokens: Vec<String>,
+    }
 
     #[derive(Clone, Default)]
     pub struct RoomProvider {
         rooms: Arc<RwLock<HashMap<UniCase<String>, Room>>>,
+        settings: Arc<RoomSettings>,
     }
 
     #[derive(Error, Debug)]
@@ -104,8 +265,20 @@ mod ssr {
         #[error("Room is full, try creating new room")]
         RoomFull,
 
-        #[error("RTCConfig Generation Failed")]
-        RTCConfigGenerationFailed(#[from] VarError),
+        #[error("Room password is incorrect")]
+        WrongPassword,
+
+        #[error("Host token is invalid")]
+        InvalidHostToken,
+
+        #[error("Overlay token is invalid")]
+        InvalidOverlayToken,
+
+        #[error("Only the host can do this")]
+        NotHost,
+
+        #[error("RTCConfig Generation Failed, TURN secret not configured")]
+        RTCConfigGenerationFailed,
 
         #[error("SystemTime Error")]
         TimeError(#[from] SystemTimeError),
@@ -114,33 +287,62 @@ mod ssr {
         HmacError(#[from] sha1::digest::InvalidLength),
     }
 
+    impl From<&RoomProviderError> for ErrorCode {
+        fn from(error: &RoomProviderError) -> Self {
+            match error {
+                RoomProviderError::RoomDoesntExist => Self::RoomNotFound,
+                RoomProviderError::RoomFull => Self::RoomFull,
+                RoomProviderError::WrongPassword => Self::WrongPassword,
+                RoomProviderError::InvalidHostToken | RoomProviderError::InvalidOverlayToken => {
+                    Self::InvalidToken
+                }
+                RoomProviderError::NotHost => Self::NotHost,
+                RoomProviderError::KeyGenerationFailed
+                | RoomProviderError::RTCConfigGenerationFailed
+                | RoomProviderError::TimeError(_)
+                | RoomProviderError::HmacError(_) => Self::ServerError,
+            }
+        }
+    }
+
     impl RoomProvider {
-        pub fn new() -> Self {
+        pub fn new(settings: RoomSettings) -> Self {
             Self {
                 rooms: Arc::new(RwLock::new(HashMap::new())),
+                settings: Arc::new(settings),
             }
         }
 
+        pub fn settings(&self) -> &RoomSettings {
+            &self.settings
+        }
+
+        fn generate_room_id(
+            rooms: &HashMap<UniCase<String>, Room>,
+        ) -> Result<UniCase<String>, RoomProviderError> {
+            let mut tries = 5;
+            loop {
+                let id = UniCase::from(generate_random_string(6));
+                if !rooms.contains_key(&id) {
+                    return Ok(id);
+                }
+                tries -= 1;
+                if tries <= 0 {
+                    return Err(RoomProviderError::KeyGenerationFailed);
+                }
+            }
+        }
+
+        #[instrument(skip_all, fields(room_id, user_id = %user.meta.id))]
         pub async fn new_room(&self, user: User) -> Result<RoomJoinInfo, RoomProviderError> {
             let mut rooms = self.rooms.write().await;
-            let id = {
-                let mut tries = 5;
-                loop {
-                    let id = UniCase::from(generate_random_string(6));
-                    if !rooms.contains_key(&id) {
-                        break id;
-                    }
-                    tries -= 1;
-                    if tries <= 0 {
-                        return Err(RoomProviderError::KeyGenerationFailed);
-                    }
-                }
-            };
+            let id = Self::generate_room_id(&rooms)?;
+            Span::current().record("room_id", id.to_lowercase().as_str());
             let user_meta = user.meta.clone();
+            let rtc_config = get_rtc_info(&self.settings.rtc, &user_meta.name.to_string()).await?;
             let room = Room::new(user);
             let player_status = room.player_status.clone();
             rooms.insert(id.clone(), room);
-            let rtc_config = get_rtc_info(&user_meta.name.to_string()).await?;
             Ok(RoomJoinInfo {
                 room_id: id.to_lowercase(),
                 user_id: user_meta.id,
@@ -150,19 +352,59 @@ mod ssr {
             })
         }
 
+        /// Creates an empty room which survives without users until `options.keep_until`.
+        #[instrument(skip_all, fields(room_id))]
+        pub async fn create_room(&self, options: RoomOptions) -> Result<String, RoomProviderError> {
+            let mut rooms = self.rooms.write().await;
+            let id = Self::generate_room_id(&rooms)?;
+            Span::current().record("room_id", id.to_lowercase().as_str());
+            rooms.insert(id.clone(), Room::with_options(options));
+            Ok(id.to_lowercase())
+        }
+
+        #[instrument(skip_all, fields(%room_id, user_id = %user.meta.id, role = ?user.meta.role))]
         pub async fn join_room(
             &self,
             room_id: &str,
             user: User,
+            password: Option<&str>,
+            host_token: Option<&str>,
         ) -> Result<RoomJoinInfo, RoomProviderError> {
             let mut rooms = self.rooms.write().await;
             let user_id = user.meta.id;
             if let Some(room) = rooms.get_mut(&UniCase::from(room_id)) {
-                if room.users.len() >= MAX_ROOM_SIZE {
+                let is_host = match host_token {
+                    Some(token) if room.is_host_token(token) => true,
+                    Some(_) => return Err(RoomProviderError::InvalidHostToken),
+                    None => false,
+                };
+                let password_matches = match room.options.password.as_deref() {
+                    Some(expected) => token_matches(password, expected),
+                    None => true,
+                };
+                if !is_host && !password_matches {
+                    return Err(RoomProviderError::WrongPassword);
+                }
+                let role = user.meta.role;
+                let (seated, limit) = if role.is_spectator() {
+                    (room.spectator_count(), self.settings.max_spectators)
+                } else {
+                    (room.member_count(), self.capacity(room))
+                };
+                if seated >= limit {
                     return Err(RoomProviderError::RoomFull);
                 }
+                // Spectators never join calls, so they get no TURN credentials either.
+                let rtc_config = if role.is_spectator() {
+                    None
+                } else {
+                    get_rtc_info(&self.settings.rtc, &user_id.to_string()).await?
+                };
+                // Spectators cannot manage the room, so they never become host.
+                if !role.is_spectator() && (is_host || room.host.is_none()) {
+                    room.host = Some(user_id);
+                }
                 room.users.push(user);
-                let rtc_config = get_rtc_info(&user_id.to_string()).await?;
                 Ok(RoomJoinInfo {
                     room_id: room_id.to_string(),
                     user_id,
@@ -175,6 +417,7 @@ mod ssr {
             }
         }
 
+        #[instrument(level = "debug", skip_all, fields(%room_id))]
         pub async fn broadcast_msg_excluding(
             &self,
             room_id: &str,
@@ -183,6 +426,10 @@ mod ssr {
         ) {
             let rooms = self.rooms.read().await;
             if let Some(room) = rooms.get(&UniCase::from(room_id)) {
+                if is_overlay_visible(&message) {
+                    // Fails only when no overlay is subscribed.
+                    let _ = room.observers.send(message.clone());
+                }
                 let send_futures = room
                     .users
                     .iter()
@@ -201,12 +448,21 @@ mod ssr {
             }
         }
 
+        #[instrument(skip_all, fields(%room_id, %user_id))]
         pub async fn remove_user(&self, room_id: &str, user_id: Uuid) -> Option<Vec<UserMeta>> {
             let mut rooms = self.rooms.write().await;
             if let Some(room) = rooms.get_mut(&UniCase::from(room_id)) {
                 room.users.retain(|user| user.meta.id != user_id);
+                if room.host == Some(user_id) {
+                    // Hand the room to the longest present member, spectators cannot manage it.
+                    room.host = room
+                        .users
+                        .iter()
+                        .find(|user| !user.meta.role.is_spectator())
+                        .map(|user| user.meta.id);
+                }
                 let users = room.users.iter().map(|u| u.meta.clone()).collect();
-                if room.users.is_empty() {
+                if room.users.is_empty() && !room.is_kept_alive(Instant::now()) {
                     rooms.remove(&UniCase::from(room_id));
                 }
                 Some(users)
@@ -215,6 +471,186 @@ mod ssr {
             }
         }
 
+        /// Users allowed in `room` at the same time.
+        pub fn capacity(&self, room: &Room) -> usize {
+            room.options
+                .capacity
+                .map_or(self.settings.max_room_size, |capacity| {
+                    capacity.min(self.settings.max_room_size)
+                })
+        }
+
+        /// Returns `Ok` if `token` is the room's host token.
+        pub async fn authorize_host(
+            &self,
+            room_id: &str,
+            token: &str,
+        ) -> Result<(), RoomProviderError> {
+            let rooms = self.rooms.read().await;
+            let room = rooms
+                .get(&UniCase::from(room_id))
+                .ok_or(RoomProviderError::RoomDoesntExist)?;
+            if room.is_host_token(token) {
+                Ok(())
+            } else {
+                Err(RoomProviderError::InvalidHostToken)
+            }
+        }
+
+        /// Issues a token for the room's event stream, only the host may do so.
+        #[instrument(skip_all, fields(%room_id, %user_id))]
+        pub async fn create_overlay_token(
+            &self,
+            room_id: &str,
+            user_id: Uuid,
+        ) -> Result<String, RoomProviderError> {
+            let mut rooms = self.rooms.write().await;
+            let room = rooms
+                .get_mut(&UniCase::from(room_id))
+                .ok_or(RoomProviderError::RoomDoesntExist)?;
+            if room.host != Some(user_id) {
+                return Err(RoomProviderError::NotHost);
+            }
+            let token = generate_random_string(32);
+            room.overlay_tokens.push(token.clone());
+            Ok(token)
+        }
+
+        /// Current state of the room and a receiver for everything overlays may see after it.
+        #[instrument(skip_all, fields(%room_id))]
+        pub async fn subscribe_overlay(
+            &self,
+            room_id: &str,
+            token: &str,
+        ) -> Result<(RoomStatus, broadcast::Receiver<Message>), RoomProviderError> {
+            let rooms = self.rooms.read().await;
+            let room = rooms
+                .get(&UniCase::from(room_id))
+                .ok_or(RoomProviderError::RoomDoesntExist)?;
+            if !room
+                .overlay_tokens
+                .iter()
+                .any(|t| token_matches(Some(token), t))
+            {
+                return Err(RoomProviderError::InvalidOverlayToken);
+            }
+            Ok((self.status(room_id, room), room.observers.subscribe()))
+        }
+
+        pub fn status(&self, room_id: &str, room: &Room) -> RoomStatus {
+            let users = room
+                .users
+                .iter()
+                .map(|user| user.meta.clone())
+                .collect::<Vec<_>>();
+            RoomStatus {
+                room_id: room_id.to_lowercase(),
+                host: room
+                    .host
+                    .and_then(|host| users.iter().find(|user| user.id == host))
+                    .cloned(),
+                users,
+                player_status: room.player_status.clone(),
+                capacity: self.capacity(room),
+                has_password: room.options.password.is_some(),
+                age_secs: room.created_at.elapsed().as_secs(),
+            }
+        }
+
+        /// Status of every room, oldest first.
+        pub async fn list_rooms(&self) -> Vec<RoomStatus> {
+            let rooms = self.rooms.read().await;
+            let mut statuses = rooms
+                .iter()
+                .map(|(id, room)| self.status(id, room))
+                .collect::<Vec<_>>();
+            statuses.sort_by_key(|status| std::cmp::Reverse(status.age_secs));
+            statuses
+        }
+
+        /// Sends `message` to every user of every room.
+        pub async fn broadcast_all(&self, message: Message) {
+            let senders = {
+                let rooms = self.rooms.read().await;
+                rooms
+                    .values()
+                    .flat_map(|room| room.users.iter().map(|user| user.sender.clone()))
+                    .collect::<Vec<_>>()
+            };
+            senders
+                .iter()
+                .map(|sender| sender.send(message.clone()))
+                .collect::<FuturesUnordered<_>>()
+                .for_each_concurrent(None, |data| async {
+                    if let Err(err) = data {
+                        warn!("broadcast failed {err:?}");
+                    }
+                })
+                .await;
+        }
+
+        /// Removes the room, connected users are disconnected as their senders drop.
+        #[instrument(skip_all, fields(%room_id))]
+        pub async fn close_room(&self, room_id: &str) -> bool {
+            let mut rooms = self.rooms.write().await;
+            rooms.remove(&UniCase::from(room_id)).is_some()
+        }
+
+        pub async fn snapshot(&self) -> Vec<RoomSnapshot> {
+            let rooms = self.rooms.read().await;
+            rooms
+                .iter()
+                .map(|(id, room)| RoomSnapshot {
+                    room_id: id.to_lowercase(),
+                    player_status: room.player_status.clone(),
+                    capacity: room.options.capacity,
+                    password: room.options.password.clone(),
+                    host_token: room.options.host_token.clone(),
+                    overlay_tokens: room.overlay_tokens.clone(),
+                })
+                .collect()
+        }
+
+        /// Recreates rooms saved by [`Self::snapshot`], paused and kept alive empty for
+        /// `keep_for` so their users can rejoin. Existing rooms with the same id win.
+        pub async fn restore(&self, snapshots: Vec<RoomSnapshot>, keep_for: Duration) -> usize {
+            let keep_until = Instant::now() + keep_for;
+            let mut rooms = self.rooms.write().await;
+            let before = rooms.len();
+            for snapshot in snapshots {
+                rooms
+                    .entry(UniCase::from(snapshot.room_id))
+                    .or_insert_with(|| {
+                        let mut room = Room::with_options(RoomOptions {
+                            capacity: snapshot.capacity,
+                            password: snapshot.password,
+                            host_token: snapshot.host_token,
+                            keep_until: Some(keep_until),
+                        });
+                        room.player_status =
+                            PlayerStatus::Paused(snapshot.player_status.get_time());
+                        room.overlay_tokens = snapshot.overlay_tokens;
+                        room
+                    });
+            }
+            rooms.len() - before
+        }
+
+        /// Drops empty rooms whose keep alive deadline has passed, returns their ids.
+        pub async fn remove_expired(&self) -> Vec<String> {
+            let now = Instant::now();
+            let mut rooms = self.rooms.write().await;
+            let mut removed = vec![];
+            rooms.retain(|id, room| {
+                let keep = !room.users.is_empty() || room.is_kept_alive(now);
+                if !keep {
+                    removed.push(id.to_lowercase());
+                }
+                keep
+            });
+            removed
+        }
+
         pub async fn get_room_player_status(&self, room_id: &str) -> Option<PlayerStatus> {
             let rooms = self.rooms.read().await;
             rooms
@@ -240,27 +676,105 @@ mod ssr {
     impl Room {
         pub fn new(user: User) -> Self {
             Self {
+                host: Some(user.meta.id),
                 users: vec![user],
                 player_status: PlayerStatus::Paused(0.0),
+                options: RoomOptions::default(),
+                created_at: Instant::now(),
+                overlay_tokens: vec![],
+                observers: broadcast::channel(OVERLAY_CHANNEL_SIZE).0,
+            }
+        }
+
+        pub fn with_options(options: RoomOptions) -> Self {
+            Self {
+                users: vec![],
+                player_status: PlayerStatus::Paused(0.0),
+                host: None,
+                options,
+                created_at: Instant::now(),
+                overlay_tokens: vec![],
+                observers: broadcast::channel(OVERLAY_CHANNEL_SIZE).0,
             }
         }
+
+        pub fn member_count(&self) -> usize {
+            self.users
+                .iter()
+                .filter(|user| !user.meta.role.is_spectator())
+                .count()
+        }
+
+        pub fn spectator_count(&self) -> usize {
+            self.users.len() - self.member_count()
+        }
+
+        fn is_host_token(&self, token: &str) -> bool {
+            self.options
+                .host_token
+                .as_deref()
+                .is_some_and(|expected| token_matches(Some(token), expected))
+        }
+
+        fn is_kept_alive(&self, now: Instant) -> bool {
+            self.options.keep_until.is_some_and(|until| until > now)
+        }
     }
 
-    pub async fn get_rtc_info(username: &str) -> Result<message::RtcConfig, RoomProviderError> {
+    /// Messages overlays lag behind by before missing some.
+    const OVERLAY_CHANNEL_SIZE: usize = 64;
+
+    /// Room activity mirrored to overlays, signalling and call requests stay private.
+    fn is_overlay_visible(message: &Message) -> bool {
+        match message {
+            Message::ServerMessage(message) => matches!(
+                message,
+                ServerMessage::UserJoined(_) | ServerMessage::UserLeft(_)
+            ),
+            Message::ClientMessage((_, message)) => matches!(
+                message,
+                ClientMessage::SetVideoMeta(_)
+                    | ClientMessage::Play(_)
+                    | ClientMessage::Pause(_)
+                    | ClientMessage::Seek(_, _)
+                    | ClientMessage::Update(_)
+                    | ClientMessage::Chat(_)
+                    | ClientMessage::SetName(_)
+                    | ClientMessage::SetAppearance(_)
+            ),
+        }
+    }
+
+    pub async fn get_rtc_info(
+        rtc: &RtcSettings,
+        username: &str,
+    ) -> Result<Option<message::RtcConfig>, RoomProviderError> {
         use base64::prelude::*;
         use hmac::{Hmac, Mac};
         use sha1::Sha1;
         use std::time::SystemTime;
         use std::time::UNIX_EPOCH;
 
-        const TTL: u64 = 3600;
+        match rtc.mode {
+            RtcMode::SyncOnly => return Ok(None),
+            RtcMode::StunOnly => {
+                return Ok(Some(RtcConfig {
+                    stun: rtc.stun_urls.clone(),
+                    turn: None,
+                }))
+            }
+            RtcMode::Full => {}
+        }
 
         let now = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
-        let timestamp = now + TTL;
+        let timestamp = now + rtc.turn_ttl.as_secs();
         let turn_username = format!("{}:{}", timestamp, username);
 
         // Your TURN server's static auth secret
-        let secret = std::env::var("TURN_SECRET")?;
+        let secret = rtc
+            .turn_secret
+            .as_ref()
+            .ok_or(RoomProviderError::RTCConfigGenerationFailed)?;
 
         // Create the HMAC using secret and username
         let mut mac = Hmac::<Sha1>::new_from_slice(secret.as_bytes())?;
@@ -270,11 +784,195 @@ mod ssr {
         // Base64 encode the resulting HMAC digest
         let credential = BASE64_STANDARD.encode(result);
 
-        Ok(RtcConfig {
-            stun: "stun:coturn.deepgaurav.com:3478".to_string(),
-            turn: "turn:coturn.deepgaurav.com:3478?transport=udp".to_string(),
-            turn_user: turn_username,
-            turn_creds: credential,
-        })
+        Ok(Some(RtcConfig {
+            stun: rtc.stun_urls.clone(),
+            turn: Some(TurnConfig {
+                url: rtc.turn_url.clone(),
+                user: turn_username,
+                creds: credential,
+            }),
+        }))
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        fn provider() -> RoomProvider {
+            RoomProvider::new(RoomSettings {
+                max_room_size: 3,
+                rtc: RtcSettings {
+                    mode: RtcMode::SyncOnly,
+                    ..Default::default()
+                },
+                ..Default::default()
+            })
+        }
+
+        fn user(role: UserRole) -> User {
+            User {
+                meta: UserMeta {
+                    id: Uuid::new_v4(),
+                    name: "test".to_string(),
+                    state: UserState::VideoNotSelected,
+                    role,
+                    appearance: Appearance::default(),
+                },
+                sender: tokio::sync::mpsc::channel(1).0,
+                last_chat_request: None,
+            }
+        }
+
+        async fn protected_room(rooms: &RoomProvider, host_token: &str) -> String {
+            rooms
+                .create_room(RoomOptions {
+                    password: Some("secret".to_string()),
+                    host_token: Some(host_token.to_string()),
+                    ..Default::default()
+                })
+                .await
+                .unwrap()
+        }
+
+        async fn host(rooms: &RoomProvider, room_id: &str) -> Option<Uuid> {
+            rooms.with_room(room_id, |room| room.host).await.unwrap()
+        }
+
+        #[tokio::test]
+        async fn rejects_wrong_password() {
+            let rooms = provider();
+            let room_id = protected_room(&rooms, "token").await;
+            let result = rooms
+                .join_room(&room_id, user(UserRole::Member), Some("guess"), None)
+                .await;
+            assert!(matches!(result, Err(RoomProviderError::WrongPassword)));
+        }
+
+        #[tokio::test]
+        async fn rejects_missing_password() {
+            let rooms = provider();
+            let room_id = protected_room(&rooms, "token").await;
+            let result = rooms
+                .join_room(&room_id, user(UserRole::Spectator), None, None)
+                .await;
+            assert!(matches!(result, Err(RoomProviderError::WrongPassword)));
+            rooms
+                .join_room(&room_id, user(UserRole::Member), Some("secret"), None)
+                .await
+                .unwrap();
+        }
+
+        #[tokio::test]
+        async fn host_token_grants_host() {
+            let rooms = provider();
+            let room_id = protected_room(&rooms, "token").await;
+            let first = rooms
+                .join_room(&room_id, user(UserRole::Member), Some("secret"), None)
+                .await
+                .unwrap();
+            assert_eq!(host(&rooms, &room_id).await, Some(first.user_id));
+
+            // The token holder takes over even when someone claimed the room first, without
+            // needing the password.
+            let owner = rooms
+                .join_room(&room_id, user(UserRole::Member), None, Some("token"))
+                .await
+                .unwrap();
+            assert_eq!(host(&rooms, &room_id).await, Some(owner.user_id));
+        }
+
+        #[tokio::test]
+        async fn rejects_host_token_of_other_room() {
+            let rooms = provider();
+            let room_id = protected_room(&rooms, "token").await;
+            protected_room(&rooms, "other-token").await;
+            let result = rooms
+                .join_room(
+                    &room_id,
+                    user(UserRole::Member),
+                    Some("secret"),
+                    Some("other-token"),
+                )
+                .await;
+            assert!(matches!(result, Err(RoomProviderError::InvalidHostToken)));
+            assert_eq!(host(&rooms, &room_id).await, None);
+        }
+
+        #[tokio::test]
+        async fn rejects_near_miss_secrets() {
+            let rooms = provider();
+            let room_id = protected_room(&rooms, "token").await;
+            // Prefixes and extensions must fail like any other wrong secret.
+            for wrong in ["toke", "tokens", "tokeN", ""] {
+                let result = rooms
+                    .join_room(
+                        &room_id,
+                        user(UserRole::Member),
+                        Some("secret"),
+                        Some(wrong),
+                    )
+                    .await;
+                assert!(matches!(result, Err(RoomProviderError::InvalidHostToken)));
+                assert!(matches!(
+                    rooms.authorize_host(&room_id, wrong).await,
+                    Err(RoomProviderError::InvalidHostToken)
+                ));
+            }
+            for wrong in ["secre", "secrets", "Secret", ""] {
+                let result = rooms
+                    .join_room(&room_id, user(UserRole::Member), Some(wrong), None)
+                    .await;
+                assert!(matches!(result, Err(RoomProviderError::WrongPassword)));
+            }
+            rooms.authorize_host(&room_id, "token").await.unwrap();
+        }
+
+        #[tokio::test]
+        async fn spectators_get_no_rtc_config() {
+            let rooms = RoomProvider::new(RoomSettings {
+                rtc: RtcSettings {
+                    mode: RtcMode::Full,
+                    turn_secret: Some("turn-secret".to_string()),
+                    ..Default::default()
+                },
+                ..Default::default()
+            });
+            let room_id = rooms.create_room(RoomOptions::default()).await.unwrap();
+            let member = rooms
+                .join_room(&room_id, user(UserRole::Member), None, None)
+                .await
+                .unwrap();
+            assert!(member.rtc_config.is_some_and(|rtc| rtc.turn.is_some()));
+            let spectator = rooms
+                .join_room(&room_id, user(UserRole::Spectator), None, None)
+                .await
+                .unwrap();
+            assert!(spectator.rtc_config.is_none());
+        }
+
+        #[tokio::test]
+        async fn hands_host_to_next_member() {
+            let rooms = provider();
+            let room_id = protected_room(&rooms, "token").await;
+            let mut joined = vec![];
+            for role in [UserRole::Member, UserRole::Spectator, UserRole::Member] {
+                let info = rooms
+                    .join_room(&room_id, user(role), Some("secret"), None)
+                    .await
+                    .unwrap();
+                joined.push(info.user_id);
+            }
+            rooms.remove_user(&room_id, joined[0]).await.unwrap();
+            assert_eq!(host(&rooms, &room_id).await, Some(joined[2]));
+
+            // Only the spectator is left, so nobody manages the room until a member joins.
+            rooms.remove_user(&room_id, joined[2]).await.unwrap();
+            assert_eq!(host(&rooms, &room_id).await, None);
+            let next = rooms
+                .join_room(&room_id, user(UserRole::Member), Some("secret"), None)
+                .await
+                .unwrap();
+            assert_eq!(host(&rooms, &room_id).await, Some(next.user_id));
+        }
     }
 }
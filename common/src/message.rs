@@ -1,15 +1,39 @@
+use std::fmt::Display;
+
 use serde::{Deserialize, Serialize};
+use thiserror::Error;
 use uuid::Uuid;
 
-use crate::{PlayerStatus, UserMeta};
+use crate::{Appearance, PlayerStatus, UserMeta};
+
+/// Bumped whenever [`Message`] or anything it carries changes shape, bincode cannot skip
+/// unknown variants or fields so mismatched peers fail to decode each other.
+pub const PROTOCOL_VERSION: u32 = 10;
+
+/// Oldest client protocol the server still accepts.
+pub const MIN_SUPPORTED_PROTOCOL_VERSION: u32 = 10;
+
+/// Websocket close code sent when the client speaks an incompatible protocol version, it
+/// never changes so outdated clients keep recognising it.
+pub const CLOSE_CODE_VERSION_MISMATCH: u16 = 4001;
+
+/// Websocket close code (RFC 6455 "Service Restart") sent when the server shuts down,
+/// clients should rejoin the same room once it is back.
+pub const CLOSE_CODE_SERVER_RESTART: u16 = 1012;
+
+pub fn is_protocol_supported(version: u32) -> bool {
+    (MIN_SUPPORTED_PROTOCOL_VERSION..=PROTOCOL_VERSION).contains(&version)
+}
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub enum Message {
     ServerMessage(ServerMessage),
     ClientMessage((Uuid, ClientMessage)),
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub enum ClientMessage {
     SetVideoMeta(VideoMeta),
     Play(f64),
@@ -24,20 +48,222 @@ pub enum ClientMessage {
     RequestCall(Uuid, bool, bool),
 
     RequestVideoShare(Uuid),
+
+    /// Host only, answered with [`ServerMessage::OverlayToken`].
+    CreateOverlayToken,
+
+    /// Changes the sender's display name, the server relays it to everyone including the
+    /// sender once cleaned up.
+    SetName(String),
+
+    /// Changes the sender's colour and avatar, relayed to everyone like [`Self::SetName`].
+    SetAppearance(Appearance),
+}
+
+impl ClientMessage {
+    /// Messages a [`crate::UserRole::Spectator`] may send, everything else controls playback
+    /// or sets up calls.
+    pub fn is_allowed_for_spectator(&self) -> bool {
+        matches!(
+            self,
+            Self::Chat(_) | Self::SetVideoMeta(_) | Self::SetName(_) | Self::SetAppearance(_)
+        )
+    }
+
+    /// Every value [`Self::kind`] can return.
+    pub const KINDS: &'static [&'static str] = &[
+        "set_video_meta",
+        "play",
+        "pause",
+        "seek",
+        "update",
+        "chat",
+        "send_session_desc",
+        "received_session_desc",
+        "exchange_candidate",
+        "request_call",
+        "request_video_share",
+        "create_overlay_token",
+        "set_name",
+        "set_appearance",
+    ];
+
+    /// Variant name in snake case, used as a metric label and rate limit key.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Self::SetVideoMeta(_) => "set_video_meta",
+            Self::Play(_) => "play",
+            Self::Pause(_) => "pause",
+            Self::Seek(..) => "seek",
+            Self::Update(_) => "update",
+            Self::Chat(_) => "chat",
+            Self::SendSessionDesc(..) => "send_session_desc",
+            Self::ReceivedSessionDesc(_) => "received_session_desc",
+            Self::ExchangeCandidate(..) => "exchange_candidate",
+            Self::RequestCall(..) => "request_call",
+            Self::RequestVideoShare(_) => "request_video_share",
+            Self::CreateOverlayToken => "create_overlay_token",
+            Self::SetName(_) => "set_name",
+            Self::SetAppearance(_) => "set_appearance",
+        }
+    }
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub enum ServerMessage {
     RoomCreated(RoomJoinInfo),
     RoomJoined(RoomJoinInfo),
     UserJoined(UserJoined),
     UserLeft(UserLeft),
 
-    Error(String),
+    Error(ErrorCode),
     // RtcConfig(RtcConfig),
+    /// Token for [`crate::endpoints::ROOM_EVENTS`].
+    OverlayToken(String),
+    /// Announcement from the server operator.
+    Notice(String),
+    /// The server is about to restart, the socket closes with [`CLOSE_CODE_SERVER_RESTART`]
+    /// shortly after and rooms are kept for this many seconds once it is back.
+    Restarting(u64),
+    /// A message was dropped because its sender exceeded the limit for that kind.
+    RateLimited(RateLimited),
+    /// A message was dropped because one of its fields failed validation.
+    Invalid(ValidationError),
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct RateLimited {
+    /// [`ClientMessage::kind`] of the dropped message.
+    pub kind: String,
+    /// When the next message of this kind will be accepted.
+    pub retry_after_ms: u64,
+}
+
+/// Why the server refused something, sent in [`ServerMessage::Error`] and as the close code
+/// of sockets it refuses or ends. The text is a fallback, clients show their own wording.
+#[derive(Error, Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub enum ErrorCode {
+    #[error("Your app is outdated, please update")]
+    VersionMismatch,
+    /// The close reason holds the [`ValidationError`].
+    #[error("Name or room id is not valid")]
+    InvalidParams,
+    #[error("Room does not exist")]
+    RoomNotFound,
+    #[error("Room is full, try creating new room")]
+    RoomFull,
+    #[error("Room password is incorrect")]
+    WrongPassword,
+    #[error("Token is invalid")]
+    InvalidToken,
+    #[error("Only the host can do this")]
+    NotHost,
+    #[error("Room was closed")]
+    RoomClosed,
+    #[error("Too many messages")]
+    RateLimited,
+    #[error("Spectators cannot control playback or call")]
+    SpectatorNotAllowed,
+    #[error("Cant send vc request, Try after some time")]
+    CallCooldown,
+    #[error("Something went wrong on the server")]
+    ServerError,
+}
+
+impl ErrorCode {
+    const ALL: [Self; 12] = [
+        Self::VersionMismatch,
+        Self::InvalidParams,
+        Self::RoomNotFound,
+        Self::RoomFull,
+        Self::WrongPassword,
+        Self::InvalidToken,
+        Self::NotHost,
+        Self::RoomClosed,
+        Self::RateLimited,
+        Self::SpectatorNotAllowed,
+        Self::CallCooldown,
+        Self::ServerError,
+    ];
+
+    /// Websocket close code, in the private 4000-4999 range.
+    pub fn close_code(self) -> u16 {
+        match self {
+            Self::VersionMismatch => CLOSE_CODE_VERSION_MISMATCH,
+            Self::InvalidParams => 4002,
+            Self::RoomNotFound => 4003,
+            Self::RoomFull => 4004,
+            Self::WrongPassword => 4005,
+            Self::InvalidToken => 4006,
+            Self::NotHost => 4007,
+            Self::RoomClosed => 4008,
+            Self::RateLimited => 4009,
+            Self::SpectatorNotAllowed => 4010,
+            Self::CallCooldown => 4011,
+            Self::ServerError => 4012,
+        }
+    }
+
+    pub fn from_close_code(code: u16) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|error| error.close_code() == code)
+    }
+}
+
+/// Part of a message or connection parameter checked by the server.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub enum Field {
+    Name,
+    RoomId,
+    Password,
+    Chat,
+    VideoName,
+    VideoDuration,
+    Position,
+    SessionDesc,
+    IceCandidate,
+    Color,
+    Avatar,
+}
+
+impl Display for Field {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            Field::Name => "name",
+            Field::RoomId => "room id",
+            Field::Password => "password",
+            Field::Chat => "chat message",
+            Field::VideoName => "video name",
+            Field::VideoDuration => "video duration",
+            Field::Position => "playback position",
+            Field::SessionDesc => "session description",
+            Field::IceCandidate => "ice candidate",
+            Field::Color => "colour",
+            Field::Avatar => "avatar",
+        };
+        f.write_str(name)
+    }
+}
+
+#[derive(Error, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub enum ValidationError {
+    #[error("{0} cannot be empty")]
+    Empty(Field),
+    /// Limit in characters for text, bytes for signalling data.
+    #[error("{0} is longer than {1}")]
+    TooLong(Field, usize),
+    #[error("{0} is not valid")]
+    Invalid(Field),
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub struct UserJoined {
     pub new_user: Uuid,
     pub users: Vec<UserMeta>,
@@ -45,6 +271,7 @@ pub struct UserJoined {
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub struct UserLeft {
     pub user_left: Uuid,
     pub users: Vec<UserMeta>,
@@ -52,29 +279,49 @@ pub struct UserLeft {
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub struct RoomJoinInfo {
     pub room_id: String,
     pub user_id: Uuid,
     pub users: Vec<UserMeta>,
     pub player_status: PlayerStatus,
-    pub rtc_config: RtcConfig,
+    /// `None` for spectators and in sync-only mode, neither can join calls.
+    pub rtc_config: Option<RtcConfig>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub struct RtcConfig {
-    pub stun: String,
-    pub turn: String,
-    pub turn_user: String,
-    pub turn_creds: String,
+    pub stun: Vec<String>,
+    /// `None` when the server has no TURN relay configured (STUN-only mode).
+    pub turn: Option<TurnConfig>,
+}
+
+impl RtcConfig {
+    /// Returns `true` if a TURN relay is available for peers behind strict NATs.
+    #[must_use]
+    pub fn has_relay(&self) -> bool {
+        self.turn.is_some()
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct TurnConfig {
+    pub url: String,
+    pub user: String,
+    pub creds: String,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub enum OfferReason {
     VideoCall,
     VideoShare(Vec<String>),
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub struct RTCSessionDesc {
     pub typ: String,
     pub sdp: String,
@@ -82,6 +329,7 @@ pub struct RTCSessionDesc {
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub struct VideoMeta {
     pub name: String,
     pub duration: Option<f64>,
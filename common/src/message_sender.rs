@@ -1,18 +1,31 @@
 use tracing::warn;
 
-use crate::message::Message;
+use crate::{
+    encoding::{Encoding, Frame},
+    message::Message,
+};
 
 pub trait MessageSender {
     #[allow(async_fn_in_trait)]
     async fn send_binary(&mut self, data: Vec<u8>);
 
+    #[allow(async_fn_in_trait)]
+    async fn send_text(&mut self, data: String);
+
     #[allow(async_fn_in_trait)]
     async fn send_message(&mut self, message: &Message) {
-        let data = bincode::serialize(message);
-        match data {
-            Ok(data) => {
+        self.send_encoded(message, Encoding::Binary).await;
+    }
+
+    #[allow(async_fn_in_trait)]
+    async fn send_encoded(&mut self, message: &Message, encoding: Encoding) {
+        match encoding.encode(message) {
+            Ok(Frame::Binary(data)) => {
                 self.send_binary(data).await;
             }
+            Ok(Frame::Text(data)) => {
+                self.send_text(data).await;
+            }
             Err(err) => {
                 warn!("Failed to serialize message {err:#?}");
             }
@@ -27,4 +40,10 @@ impl MessageSender for axum::extract::ws::WebSocket {
             warn!("Failed to send message {err:#?}")
         }
     }
+
+    async fn send_text(&mut self, data: String) {
+        if let Err(err) = self.send(axum::extract::ws::Message::Text(data)).await {
+            warn!("Failed to send message {err:#?}")
+        }
+    }
 }
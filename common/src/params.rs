@@ -1,12 +1,32 @@
 use serde::{Deserialize, Serialize};
 
+use crate::{encoding::Encoding, UserRole};
+
 #[derive(Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub struct HostParams {
     pub name: String,
+    /// Missing for clients older than the handshake, which are always rejected.
+    #[serde(default)]
+    pub protocol_version: u32,
+    #[serde(default)]
+    pub encoding: Encoding,
 }
 
 #[derive(Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub struct JoinParams {
     pub name: String,
     pub room_id: String,
+    #[serde(default)]
+    pub protocol_version: u32,
+    #[serde(default)]
+    pub encoding: Encoding,
+    #[serde(default)]
+    pub password: Option<String>,
+    /// Claims host of a room created through the API.
+    #[serde(default)]
+    pub host_token: Option<String>,
+    #[serde(default)]
+    pub role: UserRole,
 }
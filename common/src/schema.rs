@@ -0,0 +1,40 @@
+//! JSON Schema of the websocket protocol for integrations written in other languages.
+
+use schemars::{gen::SchemaSettings, schema::RootSchema};
+
+use crate::{
+    encoding::Encoding,
+    message::Message,
+    params::{HostParams, JoinParams},
+    version::VersionInfo,
+};
+
+/// Schema rooted at [`Message`], with the connection parameters and `/version` response
+/// included in `definitions`.
+pub fn protocol_schema() -> RootSchema {
+    let mut generator = SchemaSettings::draft07().into_generator();
+    generator.subschema_for::<HostParams>();
+    generator.subschema_for::<JoinParams>();
+    generator.subschema_for::<Encoding>();
+    generator.subschema_for::<VersionInfo>();
+    generator.into_root_schema_for::<Message>()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::protocol_schema;
+
+    /// The committed schema is what integrations are built against, regenerate it with
+    /// `cargo run -p common --features schema --bin protocol-schema > common/protocol-schema.json`
+    /// when the protocol changes on purpose.
+    #[test]
+    fn matches_committed_schema() {
+        let committed: serde_json::Value =
+            serde_json::from_str(include_str!("../protocol-schema.json")).unwrap();
+        let current = serde_json::to_value(protocol_schema()).unwrap();
+        assert!(
+            current == committed,
+            "protocol schema changed, regenerate common/protocol-schema.json"
+        );
+    }
+}
@@ -1,4 +1,6 @@
 use rand::{distributions::Alphanumeric, Rng};
+#[cfg(feature = "ssr")]
+use subtle::ConstantTimeEq;
 
 pub fn generate_random_string(length: usize) -> String {
     let mut rng = rand::thread_rng();
@@ -8,3 +10,9 @@ pub fn generate_random_string(length: usize) -> String {
         .collect();
     result.to_lowercase()
 }
+
+/// Compares in constant time so response times do not reveal how much of a secret matched.
+#[cfg(feature = "ssr")]
+pub fn token_matches(given: Option<&str>, expected: &str) -> bool {
+    given.is_some_and(|given| bool::from(given.as_bytes().ct_eq(expected.as_bytes())))
+}
@@ -0,0 +1,28 @@
+use serde::{Deserialize, Serialize};
+
+/// Returned by [`crate::endpoints::VERSION`], clients use it to check a server is reachable.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct VersionInfo {
+    pub name: String,
+    pub version: String,
+    pub protocol_version: u32,
+    /// Oldest client protocol the server accepts, missing on older servers.
+    #[serde(default)]
+    pub min_protocol_version: Option<u32>,
+    /// Commit the server was built from, when known at build time.
+    #[serde(default)]
+    pub git_sha: Option<String>,
+    /// Cargo profile of the build, `release` or `debug`.
+    #[serde(default)]
+    pub build_profile: Option<String>,
+}
+
+impl VersionInfo {
+    /// Returns `true` if the server accepts clients speaking `protocol_version`.
+    #[must_use]
+    pub fn accepts(&self, protocol_version: u32) -> bool {
+        let min = self.min_protocol_version.unwrap_or(self.protocol_version);
+        (min..=self.protocol_version).contains(&protocol_version)
+    }
+}
@@ -46,9 +46,6 @@ pub fn hydrate() {
 
     set_global_default(subscriber).expect("Failed to set global default subscriber");
 
-    let endpoint = Endpoint {
-        main_endpoint: std::borrow::Cow::Borrowed(""),
-    };
     let log_provider = LogProvider { logs };
 
     let provider = tauri_provider::FullScreenProvider {
@@ -95,7 +92,7 @@ pub fn hydrate() {
     };
 
     leptos::mount_to_body(move || {
-        provide_context(endpoint);
+        provide_context(Endpoint::new(""));
         provide_context(log_provider);
         view! { <App /> }
     });
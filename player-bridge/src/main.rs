@@ -0,0 +1,132 @@
+//! Keeps a local mpv in sync with a room.
+//!
+//! Start mpv with `--input-ipc-server=/tmp/tvmate-mpv` and run
+//! `tvmate-bridge --ipc /tmp/tvmate-mpv <room>`. Play, pause and seeks in either
+//! direction are mirrored, and the loaded file is reported as the selected video.
+
+mod mpv;
+mod sync;
+
+use std::{path::PathBuf, time::Duration};
+
+use clap::Parser;
+use client::{ClientError, RoomClient};
+use mpv::{MpvError, PlayerEvent};
+use sync::Bridge;
+use thiserror::Error;
+use tokio::sync::broadcast::error::RecvError;
+use tracing::{info, level_filters::LevelFilter, warn};
+use tracing_subscriber::EnvFilter;
+
+/// Same cadence as the web player's throttled position updates.
+const UPDATE_INTERVAL: Duration = Duration::from_secs(3);
+
+#[derive(Parser)]
+#[command(
+    name = "tvmate-bridge",
+    version,
+    about = "Drive mpv from a TVMate room"
+)]
+struct Args {
+    /// Room code to join.
+    room: String,
+
+    /// Path of mpv's `--input-ipc-server` socket (or named pipe on Windows).
+    #[arg(long, env = "TVMATE_MPV_IPC", default_value = "/tmp/tvmate-mpv")]
+    ipc: PathBuf,
+
+    /// Websocket url of the server.
+    #[arg(
+        long,
+        env = "TVMATE_SERVER",
+        default_value = "wss://tvmate.deepgaurav.com"
+    )]
+    server: String,
+
+    /// Name shown to the other users in the room.
+    #[arg(long, env = "TVMATE_NAME", default_value = "mpv")]
+    name: String,
+}
+
+#[derive(Error, Debug)]
+enum BridgeError {
+    #[error(transparent)]
+    Client(#[from] ClientError),
+
+    #[error(transparent)]
+    Mpv(#[from] MpvError),
+}
+
+#[tokio::main]
+async fn main() {
+    tracing_subscriber::fmt()
+        .with_env_filter(
+            EnvFilter::builder()
+                .with_default_directive(LevelFilter::INFO.into())
+                .from_env_lossy(),
+        )
+        .init();
+
+    let args = Args::parse();
+    if let Err(err) = run(args).await {
+        eprintln!("error: {err}");
+        std::process::exit(1);
+    }
+}
+
+async fn run(args: Args) -> Result<(), BridgeError> {
+    let (mut mpv, mut events) = mpv::connect(&args.ipc).await?;
+    let room = RoomClient::join(&args.server, args.name, args.room).await?;
+    info!("Joined room {}", room.room_id());
+
+    let mut bridge = Bridge::new(room.user_id());
+    let mut messages = room.subscribe();
+    let mut update = tokio::time::interval(UPDATE_INTERVAL);
+
+    // Catch up with the room before mirroring anything back.
+    for command in bridge.sync_to(&room.state().player_status) {
+        mpv.apply(command).await?;
+    }
+
+    loop {
+        tokio::select! {
+            message = messages.recv() => {
+                match message {
+                    Ok(message) => {
+                        for command in bridge.on_room(&message) {
+                            mpv.apply(command).await?;
+                        }
+                    }
+                    Err(RecvError::Lagged(skipped)) => {
+                        warn!("Bridge lagged, skipped {skipped} messages");
+                    }
+                    Err(RecvError::Closed) => {
+                        info!("Disconnected from room");
+                        break;
+                    }
+                }
+            }
+            event = events.recv() => {
+                let Some(event) = event else {
+                    info!("mpv closed the ipc connection");
+                    break;
+                };
+                if event == PlayerEvent::Shutdown {
+                    info!("mpv is shutting down");
+                    break;
+                }
+                for message in bridge.on_player(event) {
+                    room.send(message).await?;
+                }
+            }
+            _ = update.tick() => {
+                if let Some(message) = bridge.update() {
+                    room.send(message).await?;
+                }
+            }
+            _ = tokio::signal::ctrl_c() => break,
+        }
+    }
+    room.close().await;
+    Ok(())
+}
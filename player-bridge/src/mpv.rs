@@ -0,0 +1,306 @@
+//! Client for mpv's JSON IPC (`mpv --input-ipc-server=<path>`).
+//!
+//! Every line on the socket is one JSON object, either a reply carrying the `request_id`
+//! of a command or an `event`. Properties are observed so their changes arrive as
+//! `property-change` events.
+
+use std::path::Path;
+
+use serde::Deserialize;
+use serde_json::{json, Value};
+use thiserror::Error;
+use tokio::{
+    io::{AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt, BufReader, WriteHalf},
+    sync::mpsc,
+};
+use tracing::{debug, warn};
+
+/// Property observers, the id is echoed back in `property-change` events.
+const OBSERVED: [(u64, &str); 4] = [
+    (1, "pause"),
+    (2, "time-pos"),
+    (3, "duration"),
+    (4, "filename"),
+];
+
+#[derive(Error, Debug)]
+pub enum MpvError {
+    #[error("cannot connect to mpv ipc socket: {0}")]
+    Connect(std::io::Error),
+    #[error("cannot write to mpv: {0}")]
+    Write(#[from] std::io::Error),
+    #[error(transparent)]
+    Json(#[from] serde_json::Error),
+}
+
+/// What mpv reported, already narrowed to the properties the bridge cares about.
+#[derive(Debug, Clone, PartialEq)]
+pub enum PlayerEvent {
+    Pause(bool),
+    TimePos(f64),
+    Duration(f64),
+    Filename(String),
+    FileLoaded,
+    /// Playback resumed after a seek or after loading a file.
+    PlaybackRestart,
+    Shutdown,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PlayerCommand {
+    SetPause(bool),
+    SeekTo(f64),
+}
+
+#[derive(Debug, Deserialize)]
+struct RawMessage {
+    event: Option<String>,
+    name: Option<String>,
+    data: Option<Value>,
+    request_id: Option<u64>,
+    error: Option<String>,
+}
+
+impl RawMessage {
+    fn into_event(self) -> Option<PlayerEvent> {
+        match self.event.as_deref()? {
+            "property-change" => {
+                let data = self.data?;
+                match self.name.as_deref()? {
+                    "pause" => data.as_bool().map(PlayerEvent::Pause),
+                    "time-pos" => data.as_f64().map(PlayerEvent::TimePos),
+                    "duration" => data.as_f64().map(PlayerEvent::Duration),
+                    "filename" => data.as_str().map(|f| PlayerEvent::Filename(f.to_string())),
+                    _ => None,
+                }
+            }
+            "file-loaded" => Some(PlayerEvent::FileLoaded),
+            "playback-restart" => Some(PlayerEvent::PlaybackRestart),
+            "shutdown" => Some(PlayerEvent::Shutdown),
+            _ => None,
+        }
+    }
+}
+
+/// Write side of the connection, events are delivered on the receiver returned by [`connect`].
+pub struct Mpv<S> {
+    writer: WriteHalf<S>,
+    next_request_id: u64,
+}
+
+#[cfg(unix)]
+type IpcStream = tokio::net::UnixStream;
+#[cfg(windows)]
+type IpcStream = tokio::net::windows::named_pipe::NamedPipeClient;
+
+#[cfg(unix)]
+async fn open(path: &Path) -> std::io::Result<IpcStream> {
+    tokio::net::UnixStream::connect(path).await
+}
+
+#[cfg(windows)]
+async fn open(path: &Path) -> std::io::Result<IpcStream> {
+    tokio::net::windows::named_pipe::ClientOptions::new().open(path)
+}
+
+/// Connects to the socket at `path` and starts observing playback properties.
+pub async fn connect(
+    path: &Path,
+) -> Result<(Mpv<IpcStream>, mpsc::Receiver<PlayerEvent>), MpvError> {
+    let stream = open(path).await.map_err(MpvError::Connect)?;
+    let (mut mpv, events) = Mpv::new(stream);
+    for (id, property) in OBSERVED {
+        mpv.command(vec![json!("observe_property"), json!(id), json!(property)])
+            .await?;
+    }
+    Ok((mpv, events))
+}
+
+impl<S> Mpv<S>
+where
+    S: AsyncRead + AsyncWrite + Send + 'static,
+{
+    /// Splits `stream`, spawning a task that parses everything mpv writes.
+    pub fn new(stream: S) -> (Self, mpsc::Receiver<PlayerEvent>) {
+        let (reader, writer) = tokio::io::split(stream);
+        let (tx, rx) = mpsc::channel(32);
+        tokio::spawn(async move {
+            let mut lines = BufReader::new(reader).lines();
+            loop {
+                let line = match lines.next_line().await {
+                    Ok(Some(line)) => line,
+                    Ok(None) => break,
+                    Err(err) => {
+                        warn!("Failed to read from mpv {err:?}");
+                        break;
+                    }
+                };
+                let message = match serde_json::from_str::<RawMessage>(&line) {
+                    Ok(message) => message,
+                    Err(err) => {
+                        warn!("Invalid message from mpv {err:?}: {line}");
+                        continue;
+                    }
+                };
+                if let (Some(request_id), Some(error)) = (message.request_id, &message.error) {
+                    if error != "success" {
+                        warn!("mpv request {request_id} failed: {error}");
+                    }
+                    continue;
+                }
+                if let Some(event) = message.into_event() {
+                    debug!("mpv event {event:?}");
+                    if tx.send(event).await.is_err() {
+                        break;
+                    }
+                }
+            }
+        });
+        (
+            Self {
+                writer,
+                next_request_id: 100,
+            },
+            rx,
+        )
+    }
+
+    async fn command(&mut self, command: Vec<Value>) -> Result<(), MpvError> {
+        self.next_request_id += 1;
+        let mut line = serde_json::to_vec(&json!({
+            "command": command,
+            "request_id": self.next_request_id,
+        }))?;
+        line.push(b'\n');
+        self.writer.write_all(&line).await?;
+        Ok(())
+    }
+
+    pub async fn apply(&mut self, command: PlayerCommand) -> Result<(), MpvError> {
+        match command {
+            PlayerCommand::SetPause(pause) => {
+                self.command(vec![json!("set_property"), json!("pause"), json!(pause)])
+                    .await
+            }
+            PlayerCommand::SeekTo(time) => {
+                self.command(vec![json!("seek"), json!(time), json!("absolute+exact")])
+                    .await
+            }
+        }
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use tokio::{io::Lines, net::UnixListener};
+
+    /// Stands in for mpv, accepting one connection on a fresh socket.
+    async fn fake_mpv() -> (
+        Mpv<IpcStream>,
+        mpsc::Receiver<PlayerEvent>,
+        Lines<BufReader<tokio::io::ReadHalf<IpcStream>>>,
+        WriteHalf<IpcStream>,
+    ) {
+        let dir = std::env::temp_dir().join(format!("tvmate-bridge-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("mpv.sock");
+        let listener = UnixListener::bind(&path).unwrap();
+        let accept = tokio::spawn(async move { listener.accept().await.unwrap().0 });
+        let (mpv, events) = connect(&path).await.unwrap();
+        let peer = accept.await.unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+        let (reader, writer) = tokio::io::split(peer);
+        (mpv, events, BufReader::new(reader).lines(), writer)
+    }
+
+    async fn next_command(lines: &mut Lines<BufReader<tokio::io::ReadHalf<IpcStream>>>) -> Value {
+        let line = lines.next_line().await.unwrap().unwrap();
+        serde_json::from_str(&line).unwrap()
+    }
+
+    #[tokio::test]
+    async fn observes_properties_on_connect() {
+        let (_mpv, _events, mut lines, _writer) = fake_mpv().await;
+        let mut request_ids = vec![];
+        for (id, property) in OBSERVED {
+            let command = next_command(&mut lines).await;
+            assert_eq!(
+                command["command"],
+                json!(["observe_property", id, property])
+            );
+            request_ids.push(command["request_id"].as_u64().unwrap());
+        }
+        assert!(request_ids.windows(2).all(|ids| ids[0] < ids[1]));
+    }
+
+    #[tokio::test]
+    async fn frames_commands_as_lines() {
+        let (mut mpv, _events, mut lines, _writer) = fake_mpv().await;
+        for _ in OBSERVED {
+            next_command(&mut lines).await;
+        }
+        mpv.apply(PlayerCommand::SetPause(true)).await.unwrap();
+        mpv.apply(PlayerCommand::SeekTo(42.5)).await.unwrap();
+        assert_eq!(
+            next_command(&mut lines).await["command"],
+            json!(["set_property", "pause", true])
+        );
+        assert_eq!(
+            next_command(&mut lines).await["command"],
+            json!(["seek", 42.5, "absolute+exact"])
+        );
+    }
+
+    #[tokio::test]
+    async fn parses_events() {
+        // Dropping both halves of the peer ends the stream once everything is read.
+        let (_mpv, mut events, _, mut writer) = fake_mpv().await;
+        let output = concat!(
+            r#"{"request_id":101,"error":"success","data":null}"#,
+            "\n",
+            r#"{"request_id":102,"error":"property unavailable"}"#,
+            "\n",
+            "not json\n",
+            r#"{"event":"property-change","id":1,"name":"pause","data":false}"#,
+            "\n",
+            r#"{"event":"property-change","id":2,"name":"time-pos","data":12.25}"#,
+            "\n",
+            r#"{"event":"property-change","id":2,"name":"time-pos"}"#,
+            "\n",
+            r#"{"event":"property-change","id":3,"name":"duration","data":3600.0}"#,
+            "\n",
+            r#"{"event":"property-change","id":4,"name":"filename","data":"movie.mkv"}"#,
+            "\n",
+            r#"{"event":"property-change","id":9,"name":"volume","data":50}"#,
+            "\n",
+            r#"{"event":"file-loaded"}"#,
+            "\n",
+            r#"{"event":"playback-restart"}"#,
+            "\n",
+            r#"{"event":"idle"}"#,
+            "\n",
+            r#"{"event":"shutdown"}"#,
+            "\n",
+        );
+        writer.write_all(output.as_bytes()).await.unwrap();
+        drop(writer);
+
+        let mut received = vec![];
+        while let Some(event) = events.recv().await {
+            received.push(event);
+        }
+        assert_eq!(
+            received,
+            vec![
+                PlayerEvent::Pause(false),
+                PlayerEvent::TimePos(12.25),
+                PlayerEvent::Duration(3600.0),
+                PlayerEvent::Filename("movie.mkv".to_string()),
+                PlayerEvent::FileLoaded,
+                PlayerEvent::PlaybackRestart,
+                PlayerEvent::Shutdown,
+            ]
+        );
+    }
+}
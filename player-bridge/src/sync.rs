@@ -0,0 +1,243 @@
+use common::{
+    message::{ClientMessage, Message, VideoMeta},
+    PlayerStatus,
+};
+use uuid::Uuid;
+
+use crate::mpv::{PlayerCommand, PlayerEvent};
+
+/// Drift tolerated on periodic updates before seeking, same as the web player.
+const UPDATE_TOLERANCE: f64 = 15.0;
+/// Drift tolerated when someone plays or pauses.
+const PLAY_PAUSE_TOLERANCE: f64 = 1.0;
+
+/// Translates between room messages and player state.
+///
+/// Commands applied on behalf of the room make mpv report the same change back, those
+/// reports are recognised and swallowed so they do not bounce back into the room.
+pub struct Bridge {
+    user_id: Uuid,
+    paused: bool,
+    time_pos: Option<f64>,
+    duration: Option<f64>,
+    filename: Option<String>,
+    expected_pause: Option<bool>,
+    expected_restarts: usize,
+}
+
+impl Bridge {
+    pub fn new(user_id: Uuid) -> Self {
+        Self {
+            user_id,
+            paused: true,
+            time_pos: None,
+            duration: None,
+            filename: None,
+            expected_pause: None,
+            expected_restarts: 0,
+        }
+    }
+
+    fn set_pause(&mut self, pause: bool, commands: &mut Vec<PlayerCommand>) {
+        if self.paused != pause {
+            self.paused = pause;
+            self.expected_pause = Some(pause);
+            commands.push(PlayerCommand::SetPause(pause));
+        }
+    }
+
+    fn seek(&mut self, time: f64, tolerance: f64, commands: &mut Vec<PlayerCommand>) {
+        if self
+            .time_pos
+            .is_some_and(|current| (current - time).abs() <= tolerance)
+        {
+            return;
+        }
+        self.time_pos = Some(time);
+        self.expected_restarts += 1;
+        commands.push(PlayerCommand::SeekTo(time));
+    }
+
+    /// Commands to bring a freshly connected player to the room's pause state, the position
+    /// follows with the next periodic update once a file is loaded.
+    pub fn sync_to(&mut self, status: &PlayerStatus) -> Vec<PlayerCommand> {
+        let mut commands = vec![];
+        // Force the command, mpv's initial value is not known yet.
+        self.paused = !status.is_paused();
+        self.set_pause(status.is_paused(), &mut commands);
+        commands
+    }
+
+    /// Commands to bring the player in line with `message`.
+    pub fn on_room(&mut self, message: &Message) -> Vec<PlayerCommand> {
+        let mut commands = vec![];
+        let Message::ClientMessage((from, message)) = message else {
+            return commands;
+        };
+        if *from == self.user_id {
+            return commands;
+        }
+        match message {
+            ClientMessage::Play(time) => {
+                self.seek(*time, PLAY_PAUSE_TOLERANCE, &mut commands);
+                self.set_pause(false, &mut commands);
+            }
+            ClientMessage::Pause(time) => {
+                self.set_pause(true, &mut commands);
+                self.seek(*time, PLAY_PAUSE_TOLERANCE, &mut commands);
+            }
+            ClientMessage::Seek(time, is_playing) => {
+                self.seek(*time, 0.0, &mut commands);
+                self.set_pause(!is_playing, &mut commands);
+            }
+            ClientMessage::Update(time) => {
+                self.seek(*time, UPDATE_TOLERANCE, &mut commands);
+            }
+            _ => {}
+        }
+        commands
+    }
+
+    /// Messages telling the room what the local user did in the player.
+    pub fn on_player(&mut self, event: PlayerEvent) -> Vec<ClientMessage> {
+        match event {
+            PlayerEvent::Pause(pause) => {
+                if self.expected_pause == Some(pause) {
+                    self.expected_pause = None;
+                    self.paused = pause;
+                    return vec![];
+                }
+                // Reports that predate our own command are stale.
+                if self.expected_pause.is_some() || self.paused == pause {
+                    return vec![];
+                }
+                self.paused = pause;
+                let time = self.time_pos.unwrap_or_default();
+                if pause {
+                    vec![ClientMessage::Pause(time)]
+                } else {
+                    vec![ClientMessage::Play(time)]
+                }
+            }
+            PlayerEvent::TimePos(time) => {
+                self.time_pos = Some(time);
+                vec![]
+            }
+            PlayerEvent::Duration(duration) => {
+                self.duration = Some(duration);
+                self.video_meta().into_iter().collect()
+            }
+            PlayerEvent::Filename(filename) => {
+                self.filename = Some(filename);
+                self.video_meta().into_iter().collect()
+            }
+            PlayerEvent::FileLoaded => {
+                // The restart that follows loading is not a user seek.
+                self.expected_restarts += 1;
+                vec![]
+            }
+            PlayerEvent::PlaybackRestart => {
+                if self.expected_restarts > 0 {
+                    self.expected_restarts -= 1;
+                    return vec![];
+                }
+                vec![ClientMessage::Seek(
+                    self.time_pos.unwrap_or_default(),
+                    !self.paused,
+                )]
+            }
+            PlayerEvent::Shutdown => vec![],
+        }
+    }
+
+    /// Periodic position report while playing, mirrors the web player's throttled updates.
+    pub fn update(&self) -> Option<ClientMessage> {
+        if self.paused {
+            return None;
+        }
+        self.time_pos.map(ClientMessage::Update)
+    }
+
+    fn video_meta(&self) -> Option<ClientMessage> {
+        self.filename.as_ref().map(|name| {
+            ClientMessage::SetVideoMeta(VideoMeta {
+                name: name.clone(),
+                duration: self.duration,
+            })
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn from(user: Uuid, message: ClientMessage) -> Message {
+        Message::ClientMessage((user, message))
+    }
+
+    /// A bridge already playing at `time`, as after a user pressed play.
+    fn playing_at(time: f64) -> (Bridge, Uuid) {
+        let mut bridge = Bridge::new(Uuid::new_v4());
+        bridge.on_player(PlayerEvent::TimePos(time));
+        bridge.on_player(PlayerEvent::Pause(false));
+        (bridge, Uuid::new_v4())
+    }
+
+    #[test]
+    fn ignores_own_messages() {
+        let (mut bridge, _) = playing_at(10.0);
+        let own = from(bridge.user_id, ClientMessage::Pause(10.0));
+        assert!(bridge.on_room(&own).is_empty());
+    }
+
+    #[test]
+    fn swallows_pause_echo() {
+        let (mut bridge, other) = playing_at(10.0);
+        let commands = bridge.on_room(&from(other, ClientMessage::Pause(10.5)));
+        assert_eq!(commands, vec![PlayerCommand::SetPause(true)]);
+        assert!(bridge.on_player(PlayerEvent::Pause(true)).is_empty());
+
+        // Once the echo is consumed the user's own play goes to the room again.
+        let messages = bridge.on_player(PlayerEvent::Pause(false));
+        assert!(matches!(messages[..], [ClientMessage::Play(time)] if time == 10.0));
+    }
+
+    #[test]
+    fn ignores_reports_older_than_own_command() {
+        let (mut bridge, other) = playing_at(10.0);
+        bridge.on_room(&from(other, ClientMessage::Pause(10.0)));
+        // mpv reports a change that was in flight before our pause arrived.
+        assert!(bridge.on_player(PlayerEvent::Pause(false)).is_empty());
+        assert!(bridge.on_player(PlayerEvent::Pause(true)).is_empty());
+        assert!(bridge.update().is_none());
+    }
+
+    #[test]
+    fn swallows_seek_echo() {
+        let (mut bridge, other) = playing_at(10.0);
+        let commands = bridge.on_room(&from(other, ClientMessage::Seek(100.0, true)));
+        assert_eq!(commands, vec![PlayerCommand::SeekTo(100.0)]);
+        assert!(bridge.on_player(PlayerEvent::PlaybackRestart).is_empty());
+
+        bridge.on_player(PlayerEvent::TimePos(200.0));
+        let messages = bridge.on_player(PlayerEvent::PlaybackRestart);
+        assert!(matches!(messages[..], [ClientMessage::Seek(time, true)] if time == 200.0));
+    }
+
+    #[test]
+    fn swallows_restart_after_file_load() {
+        let (mut bridge, _) = playing_at(0.0);
+        bridge.on_player(PlayerEvent::FileLoaded);
+        assert!(bridge.on_player(PlayerEvent::PlaybackRestart).is_empty());
+    }
+
+    #[test]
+    fn tolerates_update_drift() {
+        let (mut bridge, other) = playing_at(10.0);
+        let update = from(other, ClientMessage::Update(10.0 + UPDATE_TOLERANCE));
+        assert!(bridge.on_room(&update).is_empty());
+        let update = from(other, ClientMessage::Update(30.0));
+        assert_eq!(bridge.on_room(&update), vec![PlayerCommand::SeekTo(30.0)]);
+    }
+}
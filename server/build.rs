@@ -0,0 +1,26 @@
+//! Embeds build info for the `/version` endpoint.
+
+use std::process::Command;
+
+fn main() {
+    // Docker builds have no `.git`, the commit can be passed in instead.
+    println!("cargo:rerun-if-env-changed=TVMATE_GIT_SHA");
+    println!("cargo:rerun-if-changed=../.git/HEAD");
+    println!("cargo:rerun-if-changed=../.git/refs/heads");
+
+    let git_sha = std::env::var("TVMATE_GIT_SHA").ok().or_else(|| {
+        Command::new("git")
+            .args(["rev-parse", "--short", "HEAD"])
+            .output()
+            .ok()
+            .filter(|output| output.status.success())
+            .and_then(|output| String::from_utf8(output.stdout).ok())
+            .map(|sha| sha.trim().to_string())
+    });
+    if let Some(git_sha) = git_sha.filter(|sha| !sha.is_empty()) {
+        println!("cargo:rustc-env=TVMATE_GIT_SHA={git_sha}");
+    }
+    if let Ok(profile) = std::env::var("PROFILE") {
+        println!("cargo:rustc-env=TVMATE_BUILD_PROFILE={profile}");
+    }
+}
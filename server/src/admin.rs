@@ -0,0 +1,115 @@
+//! JSON endpoints for operators under `/admin/api`, every request needs
+//! `Authorization: Bearer <admin.token>` and the whole surface is off while no token is set.
+
+use axum::{
+    extract::{Path, State},
+    http::{HeaderMap, StatusCode},
+    Json,
+};
+use common::{
+    api::{NoticeRequest, RoomStatus},
+    message::{Message, ServerMessage},
+    util::token_matches,
+    RoomProviderError,
+};
+use tracing::info;
+
+use crate::{
+    api::{bearer_token, ApiError},
+    webhook::{RoomCloseReason, WebhookEvent},
+    AppState,
+};
+
+pub fn authorize(app_state: &AppState, headers: &HeaderMap) -> Result<(), ApiError> {
+    let token = app_state
+        .config
+        .admin
+        .token
+        .as_deref()
+        .ok_or(ApiError::Disabled)?;
+    if token_matches(bearer_token(headers), token) {
+        Ok(())
+    } else {
+        Err(ApiError::Unauthorized)
+    }
+}
+
+fn notice(request: NoticeRequest) -> Result<Message, ApiError> {
+    let message = request.message.trim();
+    if message.is_empty() {
+        return Err(ApiError::BadRequest("notice cannot be empty".to_string()));
+    }
+    Ok(Message::ServerMessage(ServerMessage::Notice(
+        message.to_string(),
+    )))
+}
+
+pub async fn list_rooms(
+    State(app_state): State<AppState>,
+    headers: HeaderMap,
+) -> Result<Json<Vec<RoomStatus>>, ApiError> {
+    authorize(&app_state, &headers)?;
+    Ok(Json(app_state.rooms.list_rooms().await))
+}
+
+pub async fn get_room(
+    State(app_state): State<AppState>,
+    Path(room_id): Path<String>,
+    headers: HeaderMap,
+) -> Result<Json<RoomStatus>, ApiError> {
+    authorize(&app_state, &headers)?;
+    let rooms = &app_state.rooms;
+    let status = rooms
+        .with_room(&room_id, |room| rooms.status(&room_id, room))
+        .await
+        .ok_or(RoomProviderError::RoomDoesntExist)?;
+    Ok(Json(status))
+}
+
+pub async fn close_room(
+    State(app_state): State<AppState>,
+    Path(room_id): Path<String>,
+    headers: HeaderMap,
+) -> Result<StatusCode, ApiError> {
+    authorize(&app_state, &headers)?;
+    if !app_state.rooms.close_room(&room_id).await {
+        return Err(RoomProviderError::RoomDoesntExist.into());
+    }
+    app_state.webhooks.emit(WebhookEvent::RoomClosed {
+        room_id: room_id.to_lowercase(),
+        reason: RoomCloseReason::Admin,
+    });
+    info!("Room {room_id} closed by admin");
+    Ok(StatusCode::NO_CONTENT)
+}
+
+pub async fn room_notice(
+    State(app_state): State<AppState>,
+    Path(room_id): Path<String>,
+    headers: HeaderMap,
+    Json(request): Json<NoticeRequest>,
+) -> Result<StatusCode, ApiError> {
+    authorize(&app_state, &headers)?;
+    let message = notice(request)?;
+    if app_state.rooms.with_room(&room_id, |_| ()).await.is_none() {
+        return Err(RoomProviderError::RoomDoesntExist.into());
+    }
+    app_state
+        .rooms
+        .broadcast_msg_excluding(&room_id, message, &[])
+        .await;
+    info!("Admin sent notice to room {room_id}");
+    Ok(StatusCode::NO_CONTENT)
+}
+
+pub async fn global_notice(
+    State(app_state): State<AppState>,
+    headers: HeaderMap,
+    Json(request): Json<NoticeRequest>,
+) -> Result<StatusCode, ApiError> {
+    authorize(&app_state, &headers)?;
+    let message = notice(request)?;
+    app_state.rooms.broadcast_all(message).await;
+    info!("Admin sent notice to all rooms");
+    Ok(StatusCode::NO_CONTENT)
+}
@@ -0,0 +1,187 @@
+use std::time::Instant;
+
+use axum::{
+    extract::{Path, Query, State},
+    http::{header::AUTHORIZATION, HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use common::{
+    api::{CreateRoomRequest, CreateRoomResponse, RoomStatus},
+    util::{generate_random_string, token_matches},
+    RoomOptions, RoomProviderError,
+};
+use serde::Deserialize;
+use thiserror::Error;
+use tracing::info;
+
+use crate::{
+    webhook::{RoomCloseReason, WebhookEvent},
+    AppState,
+};
+
+const HOST_TOKEN_LENGTH: usize = 32;
+
+#[derive(Error, Debug)]
+pub enum ApiError {
+    #[error("missing or invalid bearer token")]
+    Unauthorized,
+
+    #[error("not enabled on this server")]
+    Disabled,
+
+    #[error("{0}")]
+    BadRequest(String),
+
+    #[error("{0} not found")]
+    NotFound(&'static str),
+
+    #[error("storage error")]
+    Io(#[from] std::io::Error),
+
+    #[error("server is shutting down")]
+    ShuttingDown,
+
+    #[error("too many requests, try again later")]
+    RateLimited,
+
+    #[error("{0} quota is used up, try again later")]
+    QuotaExhausted(&'static str),
+
+    #[error(transparent)]
+    RoomProviderError(#[from] RoomProviderError),
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        let status = match &self {
+            ApiError::Unauthorized => StatusCode::UNAUTHORIZED,
+            ApiError::Disabled => StatusCode::NOT_FOUND,
+            ApiError::BadRequest(_) => StatusCode::BAD_REQUEST,
+            ApiError::NotFound(_) => StatusCode::NOT_FOUND,
+            ApiError::Io(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            ApiError::ShuttingDown | ApiError::QuotaExhausted(_) => StatusCode::SERVICE_UNAVAILABLE,
+            ApiError::RateLimited => StatusCode::TOO_MANY_REQUESTS,
+            ApiError::RoomProviderError(err) => match err {
+                RoomProviderError::RoomDoesntExist => StatusCode::NOT_FOUND,
+                RoomProviderError::WrongPassword
+                | RoomProviderError::InvalidHostToken
+                | RoomProviderError::InvalidOverlayToken
+                | RoomProviderError::NotHost => StatusCode::FORBIDDEN,
+                RoomProviderError::RoomFull => StatusCode::CONFLICT,
+                RoomProviderError::KeyGenerationFailed
+                | RoomProviderError::RTCConfigGenerationFailed
+                | RoomProviderError::TimeError(_)
+                | RoomProviderError::HmacError(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            },
+        };
+        (status, self.to_string()).into_response()
+    }
+}
+
+pub fn bearer_token(headers: &HeaderMap) -> Option<&str> {
+    headers
+        .get(AUTHORIZATION)?
+        .to_str()
+        .ok()?
+        .strip_prefix("Bearer ")
+        .map(str::trim)
+}
+
+pub async fn create_room(
+    State(app_state): State<AppState>,
+    headers: HeaderMap,
+    Json(request): Json<CreateRoomRequest>,
+) -> Result<Json<CreateRoomResponse>, ApiError> {
+    if app_state.shutdown.is_draining() {
+        return Err(ApiError::ShuttingDown);
+    }
+    if let Some(create_token) = &app_state.config.api.create_token {
+        if !token_matches(bearer_token(&headers), create_token) {
+            return Err(ApiError::Unauthorized);
+        }
+    }
+    let max_room_size = app_state.rooms.settings().max_room_size;
+    if let Some(capacity) = request.capacity {
+        if capacity == 0 || capacity > max_room_size {
+            return Err(ApiError::BadRequest(format!(
+                "capacity must be between 1 and {max_room_size}"
+            )));
+        }
+    }
+    let ttl = app_state.config.rooms.unclaimed_room_ttl();
+    let host_token = generate_random_string(HOST_TOKEN_LENGTH);
+    let room_id = app_state
+        .rooms
+        .create_room(RoomOptions {
+            capacity: request.capacity,
+            password: request.password.filter(|password| !password.is_empty()),
+            host_token: Some(host_token.clone()),
+            keep_until: Some(Instant::now() + ttl),
+        })
+        .await?;
+    info!("Created room {room_id} through api");
+    app_state.webhooks.emit(WebhookEvent::RoomCreated {
+        room_id: room_id.clone(),
+        host: None,
+    });
+    Ok(Json(CreateRoomResponse {
+        room_id,
+        host_token,
+        expires_in_secs: ttl.as_secs(),
+    }))
+}
+
+#[derive(Deserialize)]
+pub struct RoomStatusQuery {
+    password: Option<String>,
+}
+
+/// Password protected rooms need the password or the host token.
+pub async fn get_room(
+    State(app_state): State<AppState>,
+    Path(room_id): Path<String>,
+    headers: HeaderMap,
+    Query(query): Query<RoomStatusQuery>,
+) -> Result<Json<RoomStatus>, ApiError> {
+    let rooms = &app_state.rooms;
+    let token = bearer_token(&headers);
+    let status = rooms
+        .with_room(&room_id, |room| {
+            let authorized = match &room.options.password {
+                None => true,
+                Some(password) => {
+                    token_matches(query.password.as_deref(), password)
+                        || room
+                            .options
+                            .host_token
+                            .as_deref()
+                            .is_some_and(|host_token| token_matches(token, host_token))
+                }
+            };
+            if !authorized {
+                return Err(RoomProviderError::WrongPassword);
+            }
+            Ok(rooms.status(&room_id, room))
+        })
+        .await
+        .ok_or(RoomProviderError::RoomDoesntExist)??;
+    Ok(Json(status))
+}
+
+pub async fn delete_room(
+    State(app_state): State<AppState>,
+    Path(room_id): Path<String>,
+    headers: HeaderMap,
+) -> Result<StatusCode, ApiError> {
+    let token = bearer_token(&headers).ok_or(ApiError::Unauthorized)?;
+    app_state.rooms.authorize_host(&room_id, token).await?;
+    if app_state.rooms.close_room(&room_id).await {
+        app_state.webhooks.emit(WebhookEvent::RoomClosed {
+            room_id: room_id.to_lowercase(),
+            reason: RoomCloseReason::Host,
+        });
+    }
+    info!("Closed room {room_id} through api");
+    Ok(StatusCode::NO_CONTENT)
+}
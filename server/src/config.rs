@@ -0,0 +1,962 @@
+use std::{
+    collections::HashMap,
+    fmt::Display,
+    net::{IpAddr, SocketAddr},
+    path::PathBuf,
+    str::FromStr,
+    time::Duration,
+};
+
+use common::{message::ClientMessage, RoomSettings, RtcMode, RtcSettings};
+use serde::Deserialize;
+use thiserror::Error;
+
+use crate::webhook::WebhookEventKind;
+
+/// Used when `TVMATE_CONFIG` is not set, a missing default file is not an error.
+const DEFAULT_CONFIG_PATH: &str = "tvmate.toml";
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub leptos: LeptosConfig,
+    pub rooms: RoomsConfig,
+    pub rate_limit: RateLimitConfig,
+    pub limits: LimitsConfig,
+    pub rtc: RtcSection,
+    pub stun_server: StunServerConfig,
+    pub api: ApiConfig,
+    pub webhooks: WebhooksConfig,
+    pub admin: AdminConfig,
+    pub metrics: MetricsConfig,
+    pub logging: LoggingConfig,
+    pub diagnostics: DiagnosticsConfig,
+    pub shutdown: ShutdownConfig,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LeptosConfig {
+    /// File holding the `[[workspace.metadata.leptos]]` section.
+    pub config_file: String,
+    /// Overrides `site-addr` from the leptos metadata.
+    pub site_addr: Option<SocketAddr>,
+}
+
+impl Default for LeptosConfig {
+    fn default() -> Self {
+        Self {
+            config_file: "Cargo.toml".to_string(),
+            site_addr: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RoomsConfig {
+    pub max_room_size: usize,
+    /// Spectators allowed per room on top of `max_room_size`.
+    pub max_spectators: usize,
+    /// Minimum time between two call requests from the same user.
+    pub call_request_cooldown_secs: u64,
+    /// Outgoing message buffer for every connected user.
+    pub message_channel_size: usize,
+    /// How long a room created through the API is kept while nobody is in it.
+    pub unclaimed_room_ttl_secs: u64,
+}
+
+impl Default for RoomsConfig {
+    fn default() -> Self {
+        Self {
+            max_room_size: 2,
+            max_spectators: 20,
+            call_request_cooldown_secs: 60,
+            message_channel_size: 10,
+            unclaimed_room_ttl_secs: 3600,
+        }
+    }
+}
+
+impl RoomsConfig {
+    pub fn call_request_cooldown(&self) -> Duration {
+        Duration::from_secs(self.call_request_cooldown_secs)
+    }
+
+    pub fn unclaimed_room_ttl(&self) -> Duration {
+        Duration::from_secs(self.unclaimed_room_ttl_secs)
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RateLimitConfig {
+    pub enabled: bool,
+    /// Dropped messages within `violation_window_secs` before the socket is closed.
+    pub disconnect_after: u32,
+    pub violation_window_secs: u64,
+    /// Limit for kinds without a built-in or configured one.
+    pub default: RateLimit,
+    /// Limits by [`ClientMessage::kind`], replacing the built-in ones.
+    pub kinds: HashMap<String, RateLimit>,
+}
+
+impl Default for RateLimitConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            disconnect_after: 50,
+            violation_window_secs: 10,
+            default: RateLimit {
+                per_sec: 10.0,
+                burst: 20,
+            },
+            kinds: HashMap::new(),
+        }
+    }
+}
+
+impl RateLimitConfig {
+    pub fn violation_window(&self) -> Duration {
+        Duration::from_secs(self.violation_window_secs)
+    }
+
+    pub fn limit(&self, kind: &str) -> RateLimit {
+        self.kinds
+            .get(kind)
+            .copied()
+            .or_else(|| RateLimit::builtin(kind))
+            .unwrap_or(self.default)
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LimitsConfig {
+    /// Largest websocket message accepted, bigger ones close the connection.
+    pub max_message_kb: usize,
+    pub max_name_chars: usize,
+    pub max_chat_chars: usize,
+    pub max_video_name_chars: usize,
+    pub max_sdp_bytes: usize,
+    pub max_ice_candidate_bytes: usize,
+    /// Uploaded avatar images, 0 only allows emoji avatars.
+    pub max_avatar_kb: usize,
+}
+
+impl Default for LimitsConfig {
+    fn default() -> Self {
+        Self {
+            max_message_kb: 64,
+            max_name_chars: 32,
+            max_chat_chars: 1000,
+            max_video_name_chars: 256,
+            max_sdp_bytes: 32 * 1024,
+            max_ice_candidate_bytes: 1024,
+            max_avatar_kb: 8,
+        }
+    }
+}
+
+impl LimitsConfig {
+    pub fn max_message_bytes(&self) -> usize {
+        self.max_message_kb * 1024
+    }
+
+    pub fn max_avatar_bytes(&self) -> usize {
+        self.max_avatar_kb * 1024
+    }
+}
+
+/// Token bucket, holds up to `burst` messages and refills `per_sec` of them every second.
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RateLimit {
+    pub per_sec: f64,
+    pub burst: u32,
+}
+
+impl RateLimit {
+    /// Kinds a user triggers by hand get tighter limits than the default, ICE candidates
+    /// arrive in bursts while a call is set up.
+    fn builtin(kind: &str) -> Option<Self> {
+        let (per_sec, burst) = match kind {
+            "chat" => (1.0, 5),
+            "set_video_meta" => (1.0, 5),
+            "play" | "pause" | "seek" => (4.0, 10),
+            "request_call" | "request_video_share" => (0.5, 3),
+            "create_overlay_token" => (0.1, 2),
+            "set_name" | "set_appearance" => (0.2, 3),
+            "exchange_candidate" => (20.0, 100),
+            _ => return None,
+        };
+        Some(Self { per_sec, burst })
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RtcSection {
+    /// Defaults to `full` when a TURN secret is configured and `stun-only` otherwise.
+    pub mode: Option<RtcMode>,
+    pub stun_urls: Vec<String>,
+    pub turn_url: String,
+    pub turn_secret: Option<String>,
+    pub turn_ttl_secs: u64,
+}
+
+impl Default for RtcSection {
+    fn default() -> Self {
+        let defaults = RtcSettings::default();
+        Self {
+            mode: None,
+            stun_urls: defaults.stun_urls,
+            turn_url: defaults.turn_url,
+            turn_secret: None,
+            turn_ttl_secs: defaults.turn_ttl.as_secs(),
+        }
+    }
+}
+
+impl RtcSection {
+    pub fn mode(&self) -> RtcMode {
+        match self.mode {
+            Some(mode) => mode,
+            None if self.turn_secret.is_some() => RtcMode::Full,
+            None => RtcMode::StunOnly,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct StunServerConfig {
+    /// Enables the embedded STUN responder on this address.
+    pub bind_addr: Option<SocketAddr>,
+    /// Url advertised to clients, derived from `bind_addr` when it is a concrete address.
+    pub public_url: Option<String>,
+}
+
+impl StunServerConfig {
+    pub fn advertised_url(&self) -> Option<String> {
+        let bind_addr = self.bind_addr?;
+        self.public_url
+            .clone()
+            .or_else(|| Some(format!("stun:{bind_addr}")))
+    }
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ApiConfig {
+    /// Bearer token required to create rooms through the API, creation is open when unset.
+    pub create_token: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AdminConfig {
+    /// Bearer token for the admin API and dashboard, both are disabled when unset.
+    pub token: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct MetricsConfig {
+    /// Serves Prometheus metrics on `/metrics`.
+    pub enabled: bool,
+    /// Bearer token required to scrape, scraping is open when unset.
+    pub token: Option<String>,
+}
+
+impl Default for MetricsConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            token: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ShutdownConfig {
+    /// Time between announcing the restart and closing every socket.
+    pub notice_secs: u64,
+    /// Connections still open this long after the sockets were closed are dropped.
+    pub drain_timeout_secs: u64,
+    /// Rooms are saved here on shutdown and restored on start, nothing survives when unset.
+    /// Holds room passwords and host tokens in plain text, it is written with mode 0600.
+    pub state_file: Option<PathBuf>,
+    /// How long restored rooms wait for their users to come back.
+    pub resume_window_secs: u64,
+}
+
+impl Default for ShutdownConfig {
+    fn default() -> Self {
+        Self {
+            notice_secs: 2,
+            drain_timeout_secs: 10,
+            state_file: None,
+            resume_window_secs: 120,
+        }
+    }
+}
+
+impl ShutdownConfig {
+    pub fn notice(&self) -> Duration {
+        Duration::from_secs(self.notice_secs)
+    }
+
+    pub fn drain_timeout(&self) -> Duration {
+        Duration::from_secs(self.drain_timeout_secs)
+    }
+
+    pub fn resume_window(&self) -> Duration {
+        Duration::from_secs(self.resume_window_secs)
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DiagnosticsConfig {
+    /// Accepts diagnostic bundles from users on `/api/diagnostics`.
+    pub enabled: bool,
+    /// Directory bundles are written to, one `<code>.json` per upload.
+    pub dir: PathBuf,
+    /// Largest accepted upload, logs make up most of a bundle.
+    pub max_bundle_kb: usize,
+    /// Oldest bundles are deleted once more than this many are stored.
+    pub max_bundles: usize,
+    /// Oldest bundles are deleted once together they take more than this.
+    pub max_total_mb: u64,
+    /// Uploads accepted from one address per `upload_window_secs`, more get a 429.
+    pub uploads_per_ip: usize,
+    /// Uploads accepted from everyone per `upload_window_secs`, more get a 503.
+    pub uploads_per_window: usize,
+    pub upload_window_secs: u64,
+    /// Reverse proxies whose `X-Forwarded-For` header names the uploading address.
+    /// Without them every upload through a proxy counts against the proxy's address.
+    pub trusted_proxies: Vec<IpAddr>,
+}
+
+impl Default for DiagnosticsConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            dir: PathBuf::from("diagnostics"),
+            max_bundle_kb: 2048,
+            max_bundles: 1000,
+            max_total_mb: 512,
+            uploads_per_ip: 5,
+            uploads_per_window: 100,
+            upload_window_secs: 3600,
+            trusted_proxies: vec![],
+        }
+    }
+}
+
+impl DiagnosticsConfig {
+    /// `None` when `max_bundle_kb` overflows, which [`Config::validate`] rejects.
+    pub fn max_bundle_bytes(&self) -> Option<usize> {
+        self.max_bundle_kb.checked_mul(1024)
+    }
+
+    /// `None` when `max_total_mb` overflows, which [`Config::validate`] rejects.
+    pub fn max_total_bytes(&self) -> Option<u64> {
+        self.max_total_mb.checked_mul(1024 * 1024)
+    }
+
+    pub fn upload_window(&self) -> Duration {
+        Duration::from_secs(self.upload_window_secs)
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogFormat {
+    #[default]
+    Text,
+    /// One JSON object per line, with the fields of every enclosing span.
+    Json,
+}
+
+#[derive(Error, Debug)]
+#[error("unknown log format {0:?}, expected one of text, json")]
+pub struct ParseLogFormatError(String);
+
+impl FromStr for LogFormat {
+    type Err = ParseLogFormatError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "text" => Ok(Self::Text),
+            "json" => Ok(Self::Json),
+            _ => Err(ParseLogFormatError(s.to_string())),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LoggingConfig {
+    pub format: LogFormat,
+    /// OTLP gRPC endpoint traces are exported to, needs the `otel` feature.
+    pub otlp_endpoint: Option<String>,
+    /// `service.name` reported with exported traces.
+    pub service_name: String,
+}
+
+impl Default for LoggingConfig {
+    fn default() -> Self {
+        Self {
+            format: LogFormat::Text,
+            otlp_endpoint: None,
+            service_name: "tvmate".to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct WebhooksConfig {
+    pub targets: Vec<WebhookTarget>,
+    /// Events waiting for delivery, newer events are dropped while it is full.
+    pub queue_size: usize,
+    /// Deliveries per event and target, including the first one.
+    pub max_attempts: u32,
+    pub timeout_secs: u64,
+    /// Deliveries running at once per target, newer events are dropped while it is reached.
+    pub max_in_flight: usize,
+}
+
+impl Default for WebhooksConfig {
+    fn default() -> Self {
+        Self {
+            targets: vec![],
+            queue_size: 256,
+            max_attempts: 5,
+            timeout_secs: 10,
+            max_in_flight: 32,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct WebhookTarget {
+    pub url: String,
+    /// Key for the HMAC-SHA256 signature of every payload.
+    pub secret: String,
+    /// Events sent to this target, all of them when empty.
+    #[serde(default)]
+    pub events: Vec<WebhookEventKind>,
+}
+
+#[derive(Error, Debug)]
+pub enum ConfigError {
+    #[error("cannot read config file {path:?}: {source}")]
+    Read {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+
+    #[error("cannot parse config file {path:?}: {source}")]
+    Parse {
+        path: PathBuf,
+        source: toml::de::Error,
+    },
+
+    #[error("invalid value {value:?} for {name}: {reason}")]
+    Env {
+        name: &'static str,
+        value: String,
+        reason: String,
+    },
+
+    #[error("invalid configuration, {0}")]
+    Invalid(String),
+}
+
+impl Config {
+    /// Loads the config file named by `TVMATE_CONFIG` (or `tvmate.toml` if present),
+    /// applies environment overrides and validates the result.
+    pub fn load() -> Result<Self, ConfigError> {
+        let mut config = match std::env::var("TVMATE_CONFIG") {
+            Ok(path) => Self::from_file(path.into())?,
+            Err(_) => {
+                let path = PathBuf::from(DEFAULT_CONFIG_PATH);
+                if path.exists() {
+                    Self::from_file(path)?
+                } else {
+                    Self::default()
+                }
+            }
+        };
+        config.apply_env()?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    fn from_file(path: PathBuf) -> Result<Self, ConfigError> {
+        let data = std::fs::read_to_string(&path).map_err(|source| ConfigError::Read {
+            path: path.clone(),
+            source,
+        })?;
+        toml::from_str(&data).map_err(|source| ConfigError::Parse { path, source })
+    }
+
+    fn apply_env(&mut self) -> Result<(), ConfigError> {
+        env_override("TVMATE_LEPTOS_CONFIG", &mut self.leptos.config_file)?;
+        env_override_opt("TVMATE_SITE_ADDR", &mut self.leptos.site_addr)?;
+
+        env_override("TVMATE_MAX_ROOM_SIZE", &mut self.rooms.max_room_size)?;
+        env_override("TVMATE_MAX_SPECTATORS", &mut self.rooms.max_spectators)?;
+        env_override(
+            "TVMATE_CALL_REQUEST_COOLDOWN_SECS",
+            &mut self.rooms.call_request_cooldown_secs,
+        )?;
+        env_override(
+            "TVMATE_MESSAGE_CHANNEL_SIZE",
+            &mut self.rooms.message_channel_size,
+        )?;
+        env_override(
+            "TVMATE_UNCLAIMED_ROOM_TTL_SECS",
+            &mut self.rooms.unclaimed_room_ttl_secs,
+        )?;
+
+        env_override("TVMATE_RATE_LIMIT_ENABLED", &mut self.rate_limit.enabled)?;
+        env_override(
+            "TVMATE_RATE_LIMIT_DISCONNECT_AFTER",
+            &mut self.rate_limit.disconnect_after,
+        )?;
+        env_override(
+            "TVMATE_RATE_LIMIT_VIOLATION_WINDOW_SECS",
+            &mut self.rate_limit.violation_window_secs,
+        )?;
+
+        env_override("TVMATE_MAX_MESSAGE_KB", &mut self.limits.max_message_kb)?;
+        env_override("TVMATE_MAX_NAME_CHARS", &mut self.limits.max_name_chars)?;
+        env_override("TVMATE_MAX_CHAT_CHARS", &mut self.limits.max_chat_chars)?;
+
+        env_override_opt("TVMATE_RTC_MODE", &mut self.rtc.mode)?;
+        if let Some(urls) = env_value("TVMATE_STUN_URLS") {
+            self.rtc.stun_urls = urls
+                .split(',')
+                .map(str::trim)
+                .filter(|url| !url.is_empty())
+                .map(str::to_string)
+                .collect();
+        }
+        env_override("TVMATE_TURN_URL", &mut self.rtc.turn_url)?;
+        // `TURN_SECRET` predates the config file and is kept for existing deployments.
+        env_override_opt("TURN_SECRET", &mut self.rtc.turn_secret)?;
+        env_override_opt("TVMATE_TURN_SECRET", &mut self.rtc.turn_secret)?;
+        env_override("TVMATE_TURN_TTL_SECS", &mut self.rtc.turn_ttl_secs)?;
+
+        env_override_opt("TVMATE_STUN_BIND_ADDR", &mut self.stun_server.bind_addr)?;
+        env_override_opt("TVMATE_STUN_PUBLIC_URL", &mut self.stun_server.public_url)?;
+
+        env_override_opt("TVMATE_API_CREATE_TOKEN", &mut self.api.create_token)?;
+        env_override_opt("TVMATE_ADMIN_TOKEN", &mut self.admin.token)?;
+
+        env_override("TVMATE_LOG_FORMAT", &mut self.logging.format)?;
+        env_override_opt("TVMATE_OTLP_ENDPOINT", &mut self.logging.otlp_endpoint)?;
+        env_override("TVMATE_SERVICE_NAME", &mut self.logging.service_name)?;
+
+        env_override(
+            "TVMATE_SHUTDOWN_NOTICE_SECS",
+            &mut self.shutdown.notice_secs,
+        )?;
+        env_override(
+            "TVMATE_SHUTDOWN_DRAIN_TIMEOUT_SECS",
+            &mut self.shutdown.drain_timeout_secs,
+        )?;
+        env_override_opt("TVMATE_STATE_FILE", &mut self.shutdown.state_file)?;
+        env_override(
+            "TVMATE_RESUME_WINDOW_SECS",
+            &mut self.shutdown.resume_window_secs,
+        )?;
+
+        env_override("TVMATE_DIAGNOSTICS_ENABLED", &mut self.diagnostics.enabled)?;
+        env_override("TVMATE_DIAGNOSTICS_DIR", &mut self.diagnostics.dir)?;
+        env_override(
+            "TVMATE_DIAGNOSTICS_MAX_BUNDLE_KB",
+            &mut self.diagnostics.max_bundle_kb,
+        )?;
+        env_override(
+            "TVMATE_DIAGNOSTICS_MAX_BUNDLES",
+            &mut self.diagnostics.max_bundles,
+        )?;
+        env_override(
+            "TVMATE_DIAGNOSTICS_MAX_TOTAL_MB",
+            &mut self.diagnostics.max_total_mb,
+        )?;
+        env_override(
+            "TVMATE_DIAGNOSTICS_UPLOADS_PER_IP",
+            &mut self.diagnostics.uploads_per_ip,
+        )?;
+        env_override(
+            "TVMATE_DIAGNOSTICS_UPLOADS_PER_WINDOW",
+            &mut self.diagnostics.uploads_per_window,
+        )?;
+        env_override(
+            "TVMATE_DIAGNOSTICS_UPLOAD_WINDOW_SECS",
+            &mut self.diagnostics.upload_window_secs,
+        )?;
+        if let Some(proxies) = env_value("TVMATE_DIAGNOSTICS_TRUSTED_PROXIES") {
+            self.diagnostics.trusted_proxies = proxies
+                .split(',')
+                .map(str::trim)
+                .filter(|proxy| !proxy.is_empty())
+                .map(|proxy| parse_env("TVMATE_DIAGNOSTICS_TRUSTED_PROXIES", proxy.to_string()))
+                .collect::<Result<_, _>>()?;
+        }
+
+        env_override("TVMATE_METRICS_ENABLED", &mut self.metrics.enabled)?;
+        env_override_opt("TVMATE_METRICS_TOKEN", &mut self.metrics.token)?;
+
+        env_override("TVMATE_WEBHOOK_QUEUE_SIZE", &mut self.webhooks.queue_size)?;
+        env_override(
+            "TVMATE_WEBHOOK_MAX_ATTEMPTS",
+            &mut self.webhooks.max_attempts,
+        )?;
+        env_override(
+            "TVMATE_WEBHOOK_TIMEOUT_SECS",
+            &mut self.webhooks.timeout_secs,
+        )?;
+        env_override(
+            "TVMATE_WEBHOOK_MAX_IN_FLIGHT",
+            &mut self.webhooks.max_in_flight,
+        )?;
+        Ok(())
+    }
+
+    fn validate(&self) -> Result<(), ConfigError> {
+        if self.rooms.max_room_size == 0 {
+            return Err(ConfigError::Invalid(
+                "rooms.max_room_size must be at least 1".to_string(),
+            ));
+        }
+        if self.rooms.message_channel_size == 0 {
+            return Err(ConfigError::Invalid(
+                "rooms.message_channel_size must be at least 1".to_string(),
+            ));
+        }
+        if let Some(kind) = self
+            .rate_limit
+            .kinds
+            .keys()
+            .find(|kind| !ClientMessage::KINDS.contains(&kind.as_str()))
+        {
+            return Err(ConfigError::Invalid(format!(
+                "rate_limit.kinds.{kind} is not a message kind, expected one of {:?}",
+                ClientMessage::KINDS
+            )));
+        }
+        let limits =
+            std::iter::once(&self.rate_limit.default).chain(self.rate_limit.kinds.values());
+        for limit in limits {
+            if !limit.per_sec.is_finite() || limit.per_sec <= 0.0 || limit.burst == 0 {
+                return Err(ConfigError::Invalid(format!(
+                    "rate limit {limit:?} needs per_sec above 0 and a burst of at least 1"
+                )));
+            }
+        }
+        if self.rate_limit.disconnect_after == 0 {
+            return Err(ConfigError::Invalid(
+                "rate_limit.disconnect_after must be at least 1".to_string(),
+            ));
+        }
+        let limits = &self.limits;
+        if [
+            limits.max_name_chars,
+            limits.max_chat_chars,
+            limits.max_video_name_chars,
+            limits.max_sdp_bytes,
+            limits.max_ice_candidate_bytes,
+        ]
+        .contains(&0)
+        {
+            return Err(ConfigError::Invalid(
+                "every limits.max_* must be at least 1".to_string(),
+            ));
+        }
+        if limits.max_sdp_bytes >= limits.max_message_bytes() {
+            return Err(ConfigError::Invalid(format!(
+                "limits.max_sdp_bytes must be below limits.max_message_kb ({} bytes) or calls cannot connect",
+                limits.max_message_bytes()
+            )));
+        }
+        if limits.max_avatar_kb >= limits.max_message_kb {
+            return Err(ConfigError::Invalid(format!(
+                "limits.max_avatar_kb must be below limits.max_message_kb ({})",
+                limits.max_message_kb
+            )));
+        }
+        if self.rtc.mode() == RtcMode::Full {
+            if self
+                .rtc
+                .turn_secret
+                .as_deref()
+                .unwrap_or_default()
+                .is_empty()
+            {
+                return Err(ConfigError::Invalid(
+                    "rtc.mode is full but no TURN secret is configured, set rtc.turn_secret or TURN_SECRET".to_string(),
+                ));
+            }
+            if self.rtc.turn_url.is_empty() {
+                return Err(ConfigError::Invalid(
+                    "rtc.mode is full but rtc.turn_url is empty".to_string(),
+                ));
+            }
+            if self.rtc.turn_ttl_secs == 0 {
+                return Err(ConfigError::Invalid(
+                    "rtc.turn_ttl_secs must be at least 1".to_string(),
+                ));
+            }
+        }
+        if self.rtc.mode() != RtcMode::SyncOnly
+            && self.rtc.stun_urls.is_empty()
+            && self.stun_server.bind_addr.is_none()
+        {
+            return Err(ConfigError::Invalid(format!(
+                "rtc.mode is {:?} but no stun urls are configured",
+                self.rtc.mode()
+            )));
+        }
+        if let Some(bind_addr) = self.stun_server.bind_addr {
+            if bind_addr.ip().is_unspecified() && self.stun_server.public_url.is_none() {
+                return Err(ConfigError::Invalid(format!(
+                    "stun_server.bind_addr {bind_addr} is unspecified, set stun_server.public_url to advertise it"
+                )));
+            }
+        }
+        if self.admin.token.as_deref().is_some_and(str::is_empty) {
+            return Err(ConfigError::Invalid(
+                "admin.token cannot be empty, remove it to disable the admin api".to_string(),
+            ));
+        }
+        if self.metrics.token.as_deref().is_some_and(str::is_empty) {
+            return Err(ConfigError::Invalid(
+                "metrics.token cannot be empty, remove it to allow open scraping".to_string(),
+            ));
+        }
+        if self.diagnostics.max_bundle_kb == 0 {
+            return Err(ConfigError::Invalid(
+                "diagnostics.max_bundle_kb must be at least 1".to_string(),
+            ));
+        }
+        if self.diagnostics.max_bundles == 0 {
+            return Err(ConfigError::Invalid(
+                "diagnostics.max_bundles must be at least 1".to_string(),
+            ));
+        }
+        let (Some(max_bundle), Some(max_total)) = (
+            self.diagnostics.max_bundle_bytes(),
+            self.diagnostics.max_total_bytes(),
+        ) else {
+            return Err(ConfigError::Invalid(
+                "diagnostics.max_bundle_kb and max_total_mb are too large".to_string(),
+            ));
+        };
+        if max_total < max_bundle as u64 {
+            return Err(ConfigError::Invalid(
+                "diagnostics.max_total_mb must hold at least one bundle of max_bundle_kb"
+                    .to_string(),
+            ));
+        }
+        if self.diagnostics.uploads_per_ip == 0 || self.diagnostics.uploads_per_window == 0 {
+            return Err(ConfigError::Invalid(
+                "diagnostics.uploads_per_ip and uploads_per_window must be at least 1".to_string(),
+            ));
+        }
+        if self.webhooks.queue_size == 0 {
+            return Err(ConfigError::Invalid(
+                "webhooks.queue_size must be at least 1".to_string(),
+            ));
+        }
+        if self.webhooks.max_attempts == 0 {
+            return Err(ConfigError::Invalid(
+                "webhooks.max_attempts must be at least 1".to_string(),
+            ));
+        }
+        if self.webhooks.max_in_flight == 0 {
+            return Err(ConfigError::Invalid(
+                "webhooks.max_in_flight must be at least 1".to_string(),
+            ));
+        }
+        for target in &self.webhooks.targets {
+            if !target.url.starts_with("http://") && !target.url.starts_with("https://") {
+                return Err(ConfigError::Invalid(format!(
+                    "webhook url {:?} must start with http:// or https://",
+                    target.url
+                )));
+            }
+            if target.secret.is_empty() {
+                return Err(ConfigError::Invalid(format!(
+                    "webhook {} has an empty secret",
+                    target.url
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    pub fn room_settings(&self) -> RoomSettings {
+        let mut stun_urls = self.rtc.stun_urls.clone();
+        if let Some(url) = self.stun_server.advertised_url() {
+            stun_urls.insert(0, url);
+        }
+        RoomSettings {
+            max_room_size: self.rooms.max_room_size,
+            max_spectators: self.rooms.max_spectators,
+            rtc: RtcSettings {
+                mode: self.rtc.mode(),
+                stun_urls,
+                turn_url: self.rtc.turn_url.clone(),
+                turn_secret: self.rtc.turn_secret.clone(),
+                turn_ttl: Duration::from_secs(self.rtc.turn_ttl_secs),
+            },
+        }
+    }
+}
+
+fn env_value(name: &'static str) -> Option<String> {
+    std::env::var(name).ok().filter(|value| !value.is_empty())
+}
+
+fn parse_env<T>(name: &'static str, value: String) -> Result<T, ConfigError>
+where
+    T: FromStr,
+    T::Err: Display,
+{
+    value.parse().map_err(|err: T::Err| ConfigError::Env {
+        name,
+        reason: err.to_string(),
+        value,
+    })
+}
+
+fn env_override<T>(name: &'static str, target: &mut T) -> Result<(), ConfigError>
+where
+    T: FromStr,
+    T::Err: Display,
+{
+    if let Some(value) = env_value(name) {
+        *target = parse_env(name, value)?;
+    }
+    Ok(())
+}
+
+fn env_override_opt<T>(name: &'static str, target: &mut Option<T>) -> Result<(), ConfigError>
+where
+    T: FromStr,
+    T::Err: Display,
+{
+    if let Some(value) = env_value(name) {
+        *target = Some(parse_env(name, value)?);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(toml: &str) -> Result<Config, toml::de::Error> {
+        toml::from_str(toml)
+    }
+
+    fn assert_invalid(config: Config) {
+        assert!(
+            matches!(config.validate(), Err(ConfigError::Invalid(_))),
+            "{config:?}"
+        );
+    }
+
+    #[test]
+    fn parses_example() {
+        let config = parse(include_str!("../../tvmate.example.toml")).unwrap();
+        config.validate().unwrap();
+        assert_eq!(config.rooms.max_room_size, 2);
+        assert_eq!(config.rate_limit.default.burst, 20);
+    }
+
+    #[test]
+    fn rejects_unknown_keys() {
+        assert!(parse("[rooms]\nmax_room_sise = 4\n").is_err());
+        assert!(parse("[room]\nmax_room_size = 4\n").is_err());
+        assert_eq!(
+            parse("[rooms]\nmax_room_size = 4\n")
+                .unwrap()
+                .rooms
+                .max_room_size,
+            4
+        );
+    }
+
+    // The only test touching the environment, tests run in parallel in one process.
+    #[test]
+    fn applies_env_overrides() {
+        std::env::set_var("TVMATE_MAX_ROOM_SIZE", "5");
+        std::env::set_var("TURN_SECRET", "legacy-secret");
+        std::env::remove_var("TVMATE_TURN_SECRET");
+        let mut config = Config::default();
+        assert_eq!(config.rtc.mode(), RtcMode::StunOnly);
+        config.apply_env().unwrap();
+        assert_eq!(config.rooms.max_room_size, 5);
+        assert_eq!(config.rtc.turn_secret.as_deref(), Some("legacy-secret"));
+        assert_eq!(config.rtc.mode(), RtcMode::Full);
+
+        // The new name wins over the legacy one.
+        std::env::set_var("TVMATE_TURN_SECRET", "new-secret");
+        config.apply_env().unwrap();
+        assert_eq!(config.rtc.turn_secret.as_deref(), Some("new-secret"));
+
+        std::env::set_var("TVMATE_MAX_ROOM_SIZE", "many");
+        assert!(matches!(
+            config.apply_env(),
+            Err(ConfigError::Env {
+                name: "TVMATE_MAX_ROOM_SIZE",
+                ..
+            })
+        ));
+        for name in ["TVMATE_MAX_ROOM_SIZE", "TURN_SECRET", "TVMATE_TURN_SECRET"] {
+            std::env::remove_var(name);
+        }
+    }
+
+    #[test]
+    fn validates() {
+        Config::default().validate().unwrap();
+
+        let mut config = Config::default();
+        config.rooms.max_room_size = 0;
+        assert_invalid(config);
+
+        let mut config = Config::default();
+        config.rate_limit.kinds.insert(
+            "shout".to_string(),
+            RateLimit {
+                per_sec: 1.0,
+                burst: 1,
+            },
+        );
+        assert_invalid(config);
+
+        let mut config = Config::default();
+        config.diagnostics.max_bundle_kb = 2048;
+        config.diagnostics.max_total_mb = 1;
+        assert_invalid(config);
+
+        let mut config = Config::default();
+        config.diagnostics.max_total_mb = u64::MAX;
+        assert_invalid(config);
+
+        let mut config = Config::default();
+        config.diagnostics.max_bundle_kb = usize::MAX;
+        assert_invalid(config);
+    }
+}
@@ -0,0 +1,331 @@
+//! Diagnostic bundles uploaded by users, stored as `<code>.json` under `diagnostics.dir`
+//! so a bug report only needs to quote the code.
+//!
+//! Uploads need no account, so they are limited per address and in total over
+//! `diagnostics.upload_window_secs`, and the oldest bundles are deleted once the directory
+//! holds more than `diagnostics.max_bundles` or `diagnostics.max_total_mb`. Uploads through
+//! one of `diagnostics.trusted_proxies` count against the address in `X-Forwarded-For`.
+
+use std::{
+    collections::{HashMap, VecDeque},
+    net::{IpAddr, SocketAddr},
+    path::PathBuf,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use axum::{
+    extract::{ConnectInfo, Path, State},
+    http::{header::CONTENT_TYPE, HeaderMap},
+    response::{IntoResponse, Response},
+    Json,
+};
+use common::{
+    api::{DiagnosticsBundle, DiagnosticsResponse},
+    util::generate_random_string,
+};
+use serde::Serialize;
+use tokio::io::AsyncWriteExt;
+use tracing::{info, warn};
+
+use crate::{api::ApiError, config::DiagnosticsConfig, AppState};
+
+const CODE_LENGTH: usize = 8;
+const FORWARDED_FOR: &str = "x-forwarded-for";
+
+#[derive(Serialize)]
+struct StoredBundle<'a> {
+    code: &'a str,
+    /// Seconds since the unix epoch.
+    received_at: u64,
+    #[serde(flatten)]
+    bundle: &'a DiagnosticsBundle,
+}
+
+/// Recent upload times, overall and by address.
+#[derive(Clone, Default)]
+pub struct UploadLimiter {
+    inner: Arc<Mutex<Uploads>>,
+}
+
+#[derive(Default)]
+struct Uploads {
+    all: VecDeque<Instant>,
+    by_ip: HashMap<IpAddr, VecDeque<Instant>>,
+}
+
+fn expire(times: &mut VecDeque<Instant>, now: Instant, window: Duration) {
+    while times
+        .front()
+        .is_some_and(|time| now.duration_since(*time) >= window)
+    {
+        times.pop_front();
+    }
+}
+
+impl UploadLimiter {
+    /// Counts an upload from `ip` unless it goes over either limit.
+    fn check(&self, config: &DiagnosticsConfig, ip: IpAddr) -> Result<(), ApiError> {
+        let now = Instant::now();
+        let window = config.upload_window();
+        let mut uploads = self.inner.lock().unwrap_or_else(|err| err.into_inner());
+        expire(&mut uploads.all, now, window);
+        uploads.by_ip.retain(|_, times| {
+            expire(times, now, window);
+            !times.is_empty()
+        });
+        if uploads
+            .by_ip
+            .get(&ip)
+            .is_some_and(|times| times.len() >= config.uploads_per_ip)
+        {
+            return Err(ApiError::RateLimited);
+        }
+        if uploads.all.len() >= config.uploads_per_window {
+            return Err(ApiError::QuotaExhausted("diagnostics"));
+        }
+        uploads.all.push_back(now);
+        uploads.by_ip.entry(ip).or_default().push_back(now);
+        Ok(())
+    }
+}
+
+/// The address an upload counts against, the peer unless it is a trusted proxy.
+///
+/// Proxies append to `X-Forwarded-For`, so the client is the last hop not added by one of them.
+fn client_ip(config: &DiagnosticsConfig, peer: IpAddr, headers: &HeaderMap) -> IpAddr {
+    if !config.trusted_proxies.contains(&peer) {
+        return peer;
+    }
+    let hops: Option<Vec<IpAddr>> = headers
+        .get_all(FORWARDED_FOR)
+        .iter()
+        .flat_map(|value| value.to_str().unwrap_or_default().split(','))
+        .map(|hop| hop.trim().parse().ok())
+        .collect();
+    hops.unwrap_or_default()
+        .into_iter()
+        .rev()
+        .find(|hop| !config.trusted_proxies.contains(hop))
+        .unwrap_or(peer)
+}
+
+fn bundle_path(dir: &std::path::Path, code: &str) -> PathBuf {
+    dir.join(format!("{code}.json"))
+}
+
+/// Deletes the oldest bundles until the directory is within both caps.
+async fn evict(config: &DiagnosticsConfig) -> std::io::Result<()> {
+    let mut bundles = vec![];
+    let mut entries = tokio::fs::read_dir(&config.dir).await?;
+    while let Some(entry) = entries.next_entry().await? {
+        let path = entry.path();
+        if path
+            .extension()
+            .is_some_and(|extension| extension == "json")
+        {
+            let metadata = entry.metadata().await?;
+            bundles.push((metadata.modified()?, metadata.len(), path));
+        }
+    }
+    bundles.sort_unstable_by(|a, b| b.0.cmp(&a.0));
+
+    let max_total = config.max_total_bytes().unwrap_or(u64::MAX);
+    let mut total = 0;
+    for (index, (_, len, path)) in bundles.into_iter().enumerate() {
+        total += len;
+        if index < config.max_bundles && total <= max_total {
+            continue;
+        }
+        match tokio::fs::remove_file(&path).await {
+            Ok(()) => info!("Deleted diagnostics {path:?} to stay within quota"),
+            // Removed by a concurrent upload.
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => {}
+            Err(err) => return Err(err),
+        }
+    }
+    Ok(())
+}
+
+pub async fn upload(
+    State(app_state): State<AppState>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    Json(bundle): Json<DiagnosticsBundle>,
+) -> Result<Json<DiagnosticsResponse>, ApiError> {
+    let config = &app_state.config.diagnostics;
+    if !config.enabled {
+        return Err(ApiError::Disabled);
+    }
+    app_state
+        .uploads
+        .check(config, client_ip(config, addr.ip(), &headers))?;
+    tokio::fs::create_dir_all(&config.dir).await?;
+    let received_at = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|time| time.as_secs())
+        .unwrap_or_default();
+
+    // Codes are short enough to read out loud, so retry the rare collision.
+    let mut tries = 5;
+    let (code, mut file) = loop {
+        let code = generate_random_string(CODE_LENGTH).to_uppercase();
+        match tokio::fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(bundle_path(&config.dir, &code))
+            .await
+        {
+            Ok(file) => break (code, file),
+            Err(err) if err.kind() == std::io::ErrorKind::AlreadyExists && tries > 0 => {
+                tries -= 1;
+            }
+            Err(err) => return Err(err.into()),
+        }
+    };
+
+    let data = serde_json::to_vec_pretty(&StoredBundle {
+        code: &code,
+        received_at,
+        bundle: &bundle,
+    })
+    .map_err(std::io::Error::from)?;
+    file.write_all(&data).await?;
+    drop(file);
+    if let Err(err) = evict(config).await {
+        warn!("Cannot prune diagnostics in {:?} {err:?}", config.dir);
+    }
+
+    info!(
+        "Stored diagnostics {code} for room {:?}",
+        bundle.room.as_ref().map(|room| &room.room_id)
+    );
+    Ok(Json(DiagnosticsResponse { code }))
+}
+
+/// Serves a stored bundle to the admin API.
+pub async fn get_bundle(
+    State(app_state): State<AppState>,
+    Path(code): Path<String>,
+    headers: HeaderMap,
+) -> Result<Response, ApiError> {
+    crate::admin::authorize(&app_state, &headers)?;
+    let code = code.to_uppercase();
+    if code.len() != CODE_LENGTH || !code.chars().all(|c| c.is_ascii_alphanumeric()) {
+        return Err(ApiError::BadRequest("invalid diagnostics code".to_string()));
+    }
+    match tokio::fs::read(bundle_path(&app_state.config.diagnostics.dir, &code)).await {
+        Ok(data) => Ok(([(CONTENT_TYPE, "application/json")], data).into_response()),
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
+            Err(ApiError::NotFound("diagnostics"))
+        }
+        Err(err) => Err(err.into()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{io::Write, net::Ipv4Addr};
+
+    use super::*;
+
+    #[test]
+    fn limits_uploads_per_address_and_in_total() {
+        let config = DiagnosticsConfig {
+            uploads_per_ip: 2,
+            uploads_per_window: 3,
+            ..Default::default()
+        };
+        let limiter = UploadLimiter::default();
+        let (first, second) = (
+            IpAddr::from(Ipv4Addr::new(192, 0, 2, 1)),
+            IpAddr::from(Ipv4Addr::new(192, 0, 2, 2)),
+        );
+        limiter.check(&config, first).unwrap();
+        limiter.check(&config, first).unwrap();
+        assert!(matches!(
+            limiter.check(&config, first),
+            Err(ApiError::RateLimited)
+        ));
+        limiter.check(&config, second).unwrap();
+        assert!(matches!(
+            limiter.check(&config, second),
+            Err(ApiError::QuotaExhausted(_))
+        ));
+    }
+
+    #[test]
+    fn forgets_uploads_after_the_window() {
+        let config = DiagnosticsConfig {
+            uploads_per_ip: 1,
+            upload_window_secs: 0,
+            ..Default::default()
+        };
+        let limiter = UploadLimiter::default();
+        let ip = IpAddr::from(Ipv4Addr::LOCALHOST);
+        limiter.check(&config, ip).unwrap();
+        limiter.check(&config, ip).unwrap();
+    }
+
+    #[test]
+    fn trusts_forwarded_for_only_from_proxies() {
+        let proxy = IpAddr::from(Ipv4Addr::new(10, 0, 0, 1));
+        let client = IpAddr::from(Ipv4Addr::new(192, 0, 2, 1));
+        let config = DiagnosticsConfig {
+            trusted_proxies: vec![proxy],
+            ..Default::default()
+        };
+        let mut headers = HeaderMap::new();
+        // The first hop is whatever the client sent, only the ones after it are trustworthy.
+        headers.insert(FORWARDED_FOR, "203.0.113.9, 192.0.2.1".parse().unwrap());
+        assert_eq!(client_ip(&config, proxy, &headers), client);
+        assert_eq!(client_ip(&config, client, &headers), client);
+
+        headers.insert(FORWARDED_FOR, "192.0.2.1, 10.0.0.1".parse().unwrap());
+        assert_eq!(client_ip(&config, proxy, &headers), client);
+
+        headers.insert(FORWARDED_FOR, "not an address".parse().unwrap());
+        assert_eq!(client_ip(&config, proxy, &headers), proxy);
+        assert_eq!(client_ip(&config, proxy, &HeaderMap::new()), proxy);
+    }
+
+    #[tokio::test]
+    async fn evicts_oldest_bundles() {
+        let dir = std::env::temp_dir().join(format!("tvmate-diagnostics-{}", uuid::Uuid::new_v4()));
+        tokio::fs::create_dir_all(&dir).await.unwrap();
+        let config = DiagnosticsConfig {
+            dir: dir.clone(),
+            max_bundles: 2,
+            ..Default::default()
+        };
+        // Set explicitly, filesystems with coarse timestamps could give all three the same one.
+        let now = SystemTime::now();
+        for (age, code) in [(3, "OLDEST"), (2, "OLDER"), (1, "NEWEST")] {
+            let mut file = std::fs::File::create(bundle_path(&dir, code)).unwrap();
+            file.write_all(b"{}").unwrap();
+            file.set_modified(now - Duration::from_secs(age * 60))
+                .unwrap();
+        }
+        tokio::fs::write(dir.join("notes.txt"), b"kept")
+            .await
+            .unwrap();
+
+        evict(&config).await.unwrap();
+        let mut left = vec![];
+        let mut entries = tokio::fs::read_dir(&dir).await.unwrap();
+        while let Some(entry) = entries.next_entry().await.unwrap() {
+            left.push(entry.file_name().into_string().unwrap());
+        }
+        left.sort();
+        assert_eq!(left, ["NEWEST.json", "OLDER.json", "notes.txt"]);
+
+        // The size cap applies on its own too.
+        let config = DiagnosticsConfig {
+            max_total_mb: 0,
+            ..config
+        };
+        evict(&config).await.unwrap();
+        assert!(!bundle_path(&dir, "NEWEST").exists());
+        tokio::fs::remove_dir_all(&dir).await.unwrap();
+    }
+}
@@ -1,78 +1,149 @@
+use std::{convert::Infallible, net::SocketAddr, sync::Arc, time::Duration};
+
 use app::*;
 use axum::{
     body::Body,
-    extract::{FromRef, Request, State},
+    extract::{DefaultBodyLimit, FromRef, Request, State},
     response::{IntoResponse, Response},
-    routing::get,
+    routing::{get, post},
     Router,
 };
-use common::{endpoints, RoomProvider};
+use common::{endpoints, RoomProvider, RtcMode};
+use config::Config;
 use fileserv::file_and_error_handler;
 use leptos::*;
-use leptos_axum::{generate_route_list, handle_server_fns_with_context, LeptosRoutes};
+use leptos_axum::{
+    generate_route_list_with_exclusions_and_ssg_and_context, handle_server_fns_with_context,
+    LeptosRoutes,
+};
 use leptos_router::RouteListing;
-use logging::warn;
+use metrics_exporter_prometheus::PrometheusHandle;
 use room::{host_room, join_room};
-use tower_http::compression::CompressionLayer;
-use tracing::{info, level_filters::LevelFilter};
-use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt, EnvFilter};
+use shutdown::Shutdown;
+use tower_http::{compression::CompressionLayer, cors::CorsLayer};
+use tracing::info;
+use webhook::{RoomCloseReason, WebhookEvent, Webhooks};
 
+pub mod admin;
+pub mod api;
+pub mod config;
+pub mod diagnostics;
 pub mod fileserv;
+pub mod metrics;
+pub mod overlay;
 pub mod room;
+pub mod shutdown;
+pub mod status;
+pub mod stun;
+pub mod telemetry;
+pub mod webhook;
 
 #[derive(FromRef, Clone)]
 pub struct AppState {
     leptos_options: LeptosOptions,
     routes: Vec<RouteListing>,
     pub rooms: RoomProvider,
+    pub config: Arc<Config>,
+    pub webhooks: Webhooks,
+    pub metrics: PrometheusHandle,
+    pub shutdown: Shutdown,
+    pub uploads: diagnostics::UploadLimiter,
+    pub writable_dirs: Arc<status::WritableDirs>,
 }
 
 #[tokio::main]
 async fn main() {
     let _ = dotenvy::dotenv();
-    let subscriber = tracing_subscriber::registry()
-        .with(tracing_subscriber::fmt::layer())
-        .with(
-            EnvFilter::builder()
-                .with_default_directive(LevelFilter::INFO.into())
-                .from_env_lossy(),
-        );
-    if cfg!(target_os = "linux") {
-        match tracing_journald::layer() {
-            Ok(journald_layer) => {
-                if let Err(err) = subscriber.with(journald_layer).try_init() {
-                    warn!("Cannot initialize tracing {err:#?}")
-                }
-            }
-
-            Err(err) => {
-                warn!("Cant get journald_layer {err:#?}");
-                if let Err(err) = subscriber.try_init() {
-                    warn!("Cannot initialize tracing {err:#?}")
-                }
-            }
+    let config = match Config::load() {
+        Ok(config) => Arc::new(config),
+        Err(err) => {
+            eprintln!("{err}");
+            std::process::exit(1);
         }
-    } else {
-        subscriber.init();
-    }
+    };
+    let _telemetry = telemetry::init(&config.logging);
 
     // Setting get_configuration(None) means we'll be using cargo-leptos's env values
     // For deployment these variables are:
     // <https://github.com/leptos-rs/start-axum#executing-a-server-on-a-remote-machine-without-the-toolchain>
     // Alternately a file can be specified such as Some("Cargo.toml")
     // The file would need to be included with the executable when moved to deployment
-    let conf = get_configuration(Some("Cargo.toml")).await.unwrap();
-    let leptos_options = conf.leptos_options;
+    let conf = get_configuration(Some(config.leptos.config_file.as_str()))
+        .await
+        .unwrap();
+    let mut leptos_options = conf.leptos_options;
+    if let Some(site_addr) = config.leptos.site_addr {
+        leptos_options.site_addr = site_addr;
+    }
     let addr = leptos_options.site_addr;
-    let routes = generate_route_list(App);
+    // Rendering the route list runs the pages, which expect the same context as a request.
+    let (routes, _) = generate_route_list_with_exclusions_and_ssg_and_context(App, None, || {
+        provide_context(Endpoint::new(""));
+    });
 
     let compression = CompressionLayer::new();
 
+    if let Some(bind_addr) = config.stun_server.bind_addr {
+        let socket = tokio::net::UdpSocket::bind(bind_addr)
+            .await
+            .unwrap_or_else(|err| panic!("Cannot bind stun responder on {bind_addr} {err}"));
+        tokio::spawn(async move {
+            if let Err(err) = stun::serve(socket).await {
+                tracing::error!("STUN responder stopped {err:?}");
+            }
+        });
+    }
+
+    let room_settings = config.room_settings();
+    info!(
+        "Starting with rtc mode {:?}, stun {:?}",
+        room_settings.rtc.mode, room_settings.rtc.stun_urls
+    );
+    if room_settings.rtc.mode != RtcMode::Full {
+        tracing::warn!("No TURN relay configured, calls may fail between networks");
+    }
+
+    let webhooks = Webhooks::start(&config.webhooks);
+    if !config.webhooks.targets.is_empty() {
+        info!(
+            "Sending webhooks to {} targets",
+            config.webhooks.targets.len()
+        );
+    }
+
+    let writable_dirs = Arc::new(status::WritableDirs::probe(&config).await);
     let app_state = AppState {
         leptos_options,
         routes: routes.clone(),
-        rooms: RoomProvider::new(),
+        rooms: RoomProvider::new(room_settings),
+        config,
+        webhooks,
+        metrics: metrics::install(),
+        shutdown: Shutdown::default(),
+        uploads: diagnostics::UploadLimiter::default(),
+        writable_dirs,
     };
+    shutdown::restore(&app_state).await;
+
+    // Rooms created through the api stay around empty until claimed or expired.
+    let rooms = app_state.rooms.clone();
+    let expired_webhooks = app_state.webhooks.clone();
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(Duration::from_secs(60));
+        loop {
+            interval.tick().await;
+            let removed = rooms.remove_expired().await;
+            if !removed.is_empty() {
+                info!("Removed {} expired rooms", removed.len());
+            }
+            for room_id in removed {
+                expired_webhooks.emit(WebhookEvent::RoomClosed {
+                    room_id,
+                    reason: RoomCloseReason::Expired,
+                });
+            }
+        }
+    });
     // build our application with a route
     let app = Router::new()
         .route(
@@ -82,17 +153,73 @@ async fn main() {
         .leptos_routes_with_handler(routes, get(leptos_routes_handler))
         .route(endpoints::HOST_ROOM, get(host_room))
         .route(endpoints::JOIN_ROOM, get(join_room))
+        .route(endpoints::API_ROOMS, post(api::create_room))
+        .route(
+            endpoints::API_ROOM,
+            get(api::get_room).delete(api::delete_room),
+        )
+        .route(endpoints::ADMIN_ROOMS, get(admin::list_rooms))
+        .route(
+            endpoints::ADMIN_ROOM,
+            get(admin::get_room).delete(admin::close_room),
+        )
+        .route(endpoints::ADMIN_ROOM_NOTICE, post(admin::room_notice))
+        .route(endpoints::ADMIN_NOTICE, post(admin::global_notice))
+        .route(
+            endpoints::ROOM_EVENTS,
+            get(overlay::room_events).layer(CorsLayer::permissive()),
+        )
+        .route(
+            endpoints::DIAGNOSTICS,
+            post(diagnostics::upload)
+                .layer::<_, Infallible>(DefaultBodyLimit::max(
+                    app_state
+                        .config
+                        .diagnostics
+                        .max_bundle_bytes()
+                        .unwrap_or(usize::MAX),
+                ))
+                .layer(CorsLayer::permissive()),
+        )
+        .route(endpoints::ADMIN_DIAGNOSTICS, get(diagnostics::get_bundle))
+        .route(endpoints::METRICS, get(metrics::scrape))
+        .route(
+            endpoints::VERSION,
+            get(status::version).layer(CorsLayer::permissive()),
+        )
+        .route(endpoints::HEALTHZ, get(status::healthz))
+        .route(endpoints::READYZ, get(status::readyz))
         .fallback(file_and_error_handler)
         .layer(compression)
-        .with_state(app_state);
+        .with_state(app_state.clone());
 
     // run our app with hyper
     // `axum::Server` is a re-export of `hyper::Server`
     info!("listening on http://{}", &addr);
     let listener = tokio::net::TcpListener::bind(addr).await.unwrap();
-    axum::serve(listener, app.into_make_service())
-        .await
-        .unwrap();
+    let drain_timeout = app_state.config.shutdown.drain_timeout();
+    let shutdown = app_state.shutdown.clone();
+    let rooms = app_state.rooms.clone();
+    // Diagnostics uploads are limited by the peer address.
+    let server = axum::serve(
+        listener,
+        app.into_make_service_with_connect_info::<SocketAddr>(),
+    )
+    .with_graceful_shutdown(shutdown::run(app_state));
+    tokio::select! {
+        _ = async {
+            server.await.unwrap();
+            // Upgraded websockets are not tracked by the server, wait for them separately.
+            shutdown::drained(&rooms).await;
+        } => {},
+        _ = async {
+            shutdown.closing().await;
+            tokio::time::sleep(drain_timeout).await;
+        } => {
+            tracing::warn!("Connections still open after {drain_timeout:?}, exiting anyway");
+        }
+    }
+    info!("Server stopped");
 }
 
 async fn leptos_routes_handler(
@@ -103,9 +230,7 @@ async fn leptos_routes_handler(
         app_state.leptos_options.clone(),
         app_state.routes.clone(),
         move || {
-            let endpoint = Endpoint {
-                main_endpoint: std::borrow::Cow::Borrowed(""),
-            };
+            let endpoint = Endpoint::new("");
             provide_context(endpoint);
             provide_context(app_state.rooms.clone());
         },
@@ -120,9 +245,7 @@ async fn server_fn_handler(
 ) -> impl IntoResponse {
     handle_server_fns_with_context(
         move || {
-            let endpoint = Endpoint {
-                main_endpoint: std::borrow::Cow::Borrowed(""),
-            };
+            let endpoint = Endpoint::new("");
             provide_context(endpoint);
             provide_context(app_state.rooms.clone());
         },
@@ -0,0 +1,168 @@
+//! Prometheus metrics served on [`endpoints::METRICS`](common::endpoints::METRICS).
+//! Room and user gauges are computed on every scrape, everything else is recorded as it happens.
+
+use std::time::{Duration, Instant};
+
+use axum::{
+    extract::State,
+    http::{header::CONTENT_TYPE, HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+};
+use common::{
+    encoding::Encoding, message::Message, util::token_matches, RoomProvider, RoomProviderError,
+    UserRole,
+};
+use metrics::{counter, describe_counter, describe_gauge, describe_histogram, gauge, histogram};
+use metrics_exporter_prometheus::{Matcher, PrometheusBuilder, PrometheusHandle};
+
+use crate::{api::bearer_token, AppState};
+
+const ROOMS_ACTIVE: &str = "tvmate_rooms_active";
+const USERS_CONNECTED: &str = "tvmate_users_connected";
+const CLIENT_MESSAGES: &str = "tvmate_client_messages_total";
+const BROADCAST_SECONDS: &str = "tvmate_broadcast_duration_seconds";
+const DECODE_ERRORS: &str = "tvmate_decode_errors_total";
+const JOINS_REJECTED: &str = "tvmate_joins_rejected_total";
+const WEBSOCKET_SECONDS: &str = "tvmate_websocket_duration_seconds";
+const RATE_LIMITED: &str = "tvmate_rate_limited_total";
+const WEBHOOKS_DROPPED: &str = "tvmate_webhooks_dropped_total";
+
+/// Broadcasts only push into per user channels, anything above a few milliseconds means lock contention.
+const BROADCAST_BUCKETS: &[f64] = &[0.0001, 0.0005, 0.001, 0.005, 0.01, 0.05, 0.1, 0.5];
+/// Watch sessions range from a refused join to a whole evening.
+const WEBSOCKET_BUCKETS: &[f64] = &[
+    1.0, 10.0, 60.0, 300.0, 900.0, 1800.0, 3600.0, 7200.0, 14400.0,
+];
+
+/// Installs the global recorder, must run once before anything is recorded.
+pub fn install() -> PrometheusHandle {
+    let handle = PrometheusBuilder::new()
+        .set_buckets_for_metric(
+            Matcher::Full(BROADCAST_SECONDS.to_string()),
+            BROADCAST_BUCKETS,
+        )
+        .and_then(|builder| {
+            builder.set_buckets_for_metric(
+                Matcher::Full(WEBSOCKET_SECONDS.to_string()),
+                WEBSOCKET_BUCKETS,
+            )
+        })
+        .and_then(PrometheusBuilder::install_recorder)
+        .unwrap_or_else(|err| panic!("Cannot install metrics recorder {err}"));
+
+    describe_gauge!(ROOMS_ACTIVE, "Rooms currently open");
+    describe_gauge!(USERS_CONNECTED, "Users currently in a room, by role");
+    describe_counter!(CLIENT_MESSAGES, "Messages received from clients, by kind");
+    describe_histogram!(
+        BROADCAST_SECONDS,
+        metrics::Unit::Seconds,
+        "Time to hand a message to everyone in a room"
+    );
+    describe_counter!(DECODE_ERRORS, "Websocket frames that could not be decoded");
+    describe_counter!(JOINS_REJECTED, "Refused joins, by reason");
+    describe_counter!(
+        RATE_LIMITED,
+        "Client messages dropped by the rate limiter, by kind"
+    );
+    describe_counter!(
+        WEBHOOKS_DROPPED,
+        "Webhook events dropped before delivery, by reason"
+    );
+    describe_histogram!(
+        WEBSOCKET_SECONDS,
+        metrics::Unit::Seconds,
+        "How long room websockets stay connected, by role"
+    );
+    handle
+}
+
+pub async fn scrape(State(app_state): State<AppState>, headers: HeaderMap) -> Response {
+    let config = &app_state.config.metrics;
+    if !config.enabled {
+        return StatusCode::NOT_FOUND.into_response();
+    }
+    if let Some(token) = config.token.as_deref() {
+        if !token_matches(bearer_token(&headers), token) {
+            return StatusCode::UNAUTHORIZED.into_response();
+        }
+    }
+
+    let rooms = app_state.rooms.list_rooms().await;
+    let (mut members, mut spectators) = (0, 0);
+    for user in rooms.iter().flat_map(|room| &room.users) {
+        match user.role {
+            UserRole::Member => members += 1,
+            UserRole::Spectator => spectators += 1,
+        }
+    }
+    gauge!(ROOMS_ACTIVE).set(rooms.len() as f64);
+    gauge!(USERS_CONNECTED, "role" => role_label(UserRole::Member)).set(members as f64);
+    gauge!(USERS_CONNECTED, "role" => role_label(UserRole::Spectator)).set(spectators as f64);
+
+    (
+        [(CONTENT_TYPE, "text/plain; version=0.0.4")],
+        app_state.metrics.render(),
+    )
+        .into_response()
+}
+
+fn role_label(role: UserRole) -> &'static str {
+    match role {
+        UserRole::Member => "member",
+        UserRole::Spectator => "spectator",
+    }
+}
+
+pub fn client_message(message: &common::message::ClientMessage) {
+    counter!(CLIENT_MESSAGES, "kind" => message.kind()).increment(1);
+}
+
+pub fn rate_limited(kind: &'static str) {
+    counter!(RATE_LIMITED, "kind" => kind).increment(1);
+}
+
+pub fn webhook_dropped(reason: &'static str) {
+    counter!(WEBHOOKS_DROPPED, "reason" => reason).increment(1);
+}
+
+pub fn decode_error(encoding: Encoding) {
+    let encoding = match encoding {
+        Encoding::Binary => "binary",
+        Encoding::Json => "json",
+    };
+    counter!(DECODE_ERRORS, "encoding" => encoding).increment(1);
+}
+
+pub fn join_rejected(error: &RoomProviderError) {
+    let reason = match error {
+        RoomProviderError::KeyGenerationFailed => "key_generation_failed",
+        RoomProviderError::RoomDoesntExist => "room_doesnt_exist",
+        RoomProviderError::RoomFull => "room_full",
+        RoomProviderError::WrongPassword => "wrong_password",
+        RoomProviderError::InvalidHostToken => "invalid_host_token",
+        RoomProviderError::InvalidOverlayToken => "invalid_overlay_token",
+        RoomProviderError::NotHost => "not_host",
+        RoomProviderError::RTCConfigGenerationFailed => "rtc_config_generation_failed",
+        RoomProviderError::TimeError(_) => "time_error",
+        RoomProviderError::HmacError(_) => "hmac_error",
+    };
+    counter!(JOINS_REJECTED, "reason" => reason).increment(1);
+}
+
+pub fn websocket_closed(role: UserRole, lifetime: Duration) {
+    histogram!(WEBSOCKET_SECONDS, "role" => role_label(role)).record(lifetime.as_secs_f64());
+}
+
+/// [`RoomProvider::broadcast_msg_excluding`] with its duration recorded.
+pub async fn broadcast(
+    rooms: &RoomProvider,
+    room_id: &str,
+    message: Message,
+    exclude: &[uuid::Uuid],
+) {
+    let start = Instant::now();
+    rooms
+        .broadcast_msg_excluding(room_id, message, exclude)
+        .await;
+    histogram!(BROADCAST_SECONDS).record(start.elapsed().as_secs_f64());
+}
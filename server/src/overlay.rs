@@ -0,0 +1,71 @@
+//! Read-only server-sent-events feed of a room for stream overlays.
+//!
+//! The stream opens with a `status` event carrying the room's [`RoomStatus`], followed by
+//! a `message` event for every chat, playback change and join or leave, encoded like the
+//! JSON websocket frames. Subscribers are not users and do not take a slot in the room.
+
+use std::{convert::Infallible, time::Duration};
+
+use axum::{
+    extract::{Path, Query, State},
+    response::sse::{Event, KeepAlive, Sse},
+};
+use common::{api::RoomStatus, message::Message};
+use futures::{stream, Stream, StreamExt};
+use serde::Deserialize;
+use tokio::sync::broadcast::error::RecvError;
+use tracing::{info, warn};
+
+use crate::{api::ApiError, AppState};
+
+#[derive(Deserialize)]
+pub struct RoomEventsQuery {
+    token: String,
+}
+
+pub async fn room_events(
+    State(app_state): State<AppState>,
+    Path(room_id): Path<String>,
+    Query(query): Query<RoomEventsQuery>,
+) -> Result<Sse<impl Stream<Item = Result<Event, Infallible>>>, ApiError> {
+    let (status, rx) = app_state
+        .rooms
+        .subscribe_overlay(&room_id, &query.token)
+        .await?;
+    info!("Overlay subscribed to room {room_id}");
+    let snapshot = stream::once(async move { status_event(&status) });
+    let messages = stream::unfold(rx, |mut rx| async move {
+        loop {
+            match rx.recv().await {
+                Ok(message) => return Some((message_event(&message), rx)),
+                Err(RecvError::Lagged(skipped)) => {
+                    warn!("Overlay lagged, skipped {skipped} messages");
+                }
+                // The room was closed.
+                Err(RecvError::Closed) => return None,
+            }
+        }
+    });
+    Ok(Sse::new(snapshot.chain(messages).map(Ok))
+        .keep_alive(KeepAlive::new().interval(Duration::from_secs(15))))
+}
+
+fn status_event(status: &RoomStatus) -> Event {
+    Event::default()
+        .event("status")
+        .json_data(status)
+        .unwrap_or_else(|err| {
+            warn!("Cannot serialize room status {err:?}");
+            Event::default().comment("status unavailable")
+        })
+}
+
+fn message_event(message: &Message) -> Event {
+    Event::default()
+        .event("message")
+        .json_data(message)
+        .unwrap_or_else(|err| {
+            warn!("Cannot serialize overlay message {err:?}");
+            Event::default().comment("message unavailable")
+        })
+}
@@ -7,17 +7,32 @@ use axum::{
     response::{IntoResponse, Response},
 };
 use common::{
-    message::{ClientMessage, Message, UserJoined, UserLeft},
+    encoding::Encoding,
+    message::{
+        is_protocol_supported, ClientMessage, ErrorCode, Message, UserJoined, UserLeft,
+        ValidationError, CLOSE_CODE_SERVER_RESTART, PROTOCOL_VERSION,
+    },
     message_sender::MessageSender,
     params::{HostParams, JoinParams},
-    PlayerStatus, RoomProviderError, User, UserMeta, UserState,
+    PlayerStatus, RoomProviderError, User, UserMeta, UserRole, UserState,
 };
 use leptos::logging::warn;
 use thiserror::Error;
-use tracing::info;
+use tracing::{info, instrument};
 use uuid::Uuid;
 
-use crate::AppState;
+use crate::{
+    config::LimitsConfig,
+    metrics,
+    shutdown::RESTART_REASON,
+    webhook::{self, RoomCloseReason, WebhookEvent},
+    AppState,
+};
+
+mod rate_limit;
+mod validation;
+
+use rate_limit::{Decision, RateLimiter};
 
 #[derive(Error, Debug)]
 pub enum RoomJoinError {
@@ -25,19 +40,72 @@ pub enum RoomJoinError {
     RoomProviderError(#[from] RoomProviderError),
 }
 
+/// Accepts the socket only to close it, browsers do not expose http error bodies to websocket clients.
+fn reject_websocket(ws: WebSocketUpgrade, code: u16, reason: String) -> Response {
+    ws.on_upgrade(move |mut sock| async move {
+        if let Err(err) = sock
+            .send(axum::extract::ws::Message::Close(Some(CloseFrame {
+                code,
+                reason: reason.into(),
+            })))
+            .await
+        {
+            warn!("Cant send close {err:?}");
+        }
+    })
+}
+
+/// Clients retry once the server is back, like for a socket closed by the shutdown.
+fn reject_draining(ws: WebSocketUpgrade) -> Response {
+    reject_websocket(ws, CLOSE_CODE_SERVER_RESTART, RESTART_REASON.to_string())
+}
+
+fn reject_with(ws: WebSocketUpgrade, error: ErrorCode) -> Response {
+    reject_websocket(ws, error.close_code(), error.to_string())
+}
+
+fn reject_invalid(ws: WebSocketUpgrade, error: ValidationError) -> Response {
+    info!("Rejecting client with invalid params, {error}");
+    // The validation error names the field, more useful than the generic text.
+    reject_websocket(ws, ErrorCode::InvalidParams.close_code(), error.to_string())
+}
+
+/// Frames above the limit error out of `recv` and end the connection.
+fn limit_size(ws: WebSocketUpgrade, limits: &LimitsConfig) -> WebSocketUpgrade {
+    ws.max_message_size(limits.max_message_bytes())
+        .max_frame_size(limits.max_message_bytes())
+}
+
+fn reject_incompatible(ws: WebSocketUpgrade, protocol_version: u32) -> Response {
+    info!("Rejecting client with protocol {protocol_version}, server speaks {PROTOCOL_VERSION}");
+    reject_with(ws, ErrorCode::VersionMismatch)
+}
+
 #[axum::debug_handler]
 pub async fn host_room(
     State(app_state): State<AppState>,
-    Query(host_params): Query<HostParams>,
+    Query(mut host_params): Query<HostParams>,
     ws: WebSocketUpgrade,
 ) -> Result<Response, RoomJoinError> {
-    let (tx, rx) = tokio::sync::mpsc::channel(10);
+    let ws = limit_size(ws, &app_state.config.limits);
+    if !is_protocol_supported(host_params.protocol_version) {
+        return Ok(reject_incompatible(ws, host_params.protocol_version));
+    }
+    if app_state.shutdown.is_draining() {
+        return Ok(reject_draining(ws));
+    }
+    if let Err(error) = validation::clean_host_params(&app_state.config.limits, &mut host_params) {
+        return Ok(reject_invalid(ws, error));
+    }
+    let (tx, rx) = tokio::sync::mpsc::channel(app_state.config.rooms.message_channel_size);
     let user_id = Uuid::new_v4();
     let user = User {
         meta: UserMeta {
             id: user_id,
             name: host_params.name,
             state: common::UserState::VideoNotSelected,
+            role: UserRole::Member,
+            appearance: Default::default(),
         },
         sender: tx,
         last_chat_request: None,
@@ -48,26 +116,52 @@ pub async fn host_room(
         Ok(r) => r,
         Err(er) => {
             warn!("Failed to create room {er:?}");
-            return Err(er.into());
+            metrics::join_rejected(&er);
+            return Ok(reject_with(ws, ErrorCode::from(&er)));
         }
     };
+    app_state.webhooks.emit(WebhookEvent::RoomCreated {
+        room_id: room_id.room_id.clone(),
+        host: room_id.users.first().cloned(),
+    });
+    let encoding = host_params.encoding;
     Ok(ws.on_upgrade(move |mut msgs| async move {
-        msgs.send_message(&Message::ServerMessage(
-            common::message::ServerMessage::RoomCreated(room_id.clone()),
-        ))
+        msgs.send_encoded(
+            &Message::ServerMessage(common::message::ServerMessage::RoomCreated(room_id.clone())),
+            encoding,
+        )
         .await;
 
-        handle_websocket(app_state, &room_id.room_id, user_id, msgs, rx).await;
+        handle_websocket(
+            app_state,
+            &room_id.room_id,
+            user_id,
+            msgs,
+            rx,
+            encoding,
+            UserRole::Member,
+        )
+        .await;
     }))
 }
 
 #[axum::debug_handler]
 pub async fn join_room(
     State(app_state): State<AppState>,
-    Query(join_params): Query<JoinParams>,
+    Query(mut join_params): Query<JoinParams>,
     ws: WebSocketUpgrade,
 ) -> Result<Response, RoomJoinError> {
-    let (tx, rx) = tokio::sync::mpsc::channel(10); // 10 is random here.
+    let ws = limit_size(ws, &app_state.config.limits);
+    if !is_protocol_supported(join_params.protocol_version) {
+        return Ok(reject_incompatible(ws, join_params.protocol_version));
+    }
+    if app_state.shutdown.is_draining() {
+        return Ok(reject_draining(ws));
+    }
+    if let Err(error) = validation::clean_join_params(&app_state.config.limits, &mut join_params) {
+        return Ok(reject_invalid(ws, error));
+    }
+    let (tx, rx) = tokio::sync::mpsc::channel(app_state.config.rooms.message_channel_size);
     let user_id = Uuid::new_v4();
     let user = User {
         meta: UserMeta {
@@ -75,6 +169,8 @@ pub async fn join_room(
             name: join_params.name,
 
             state: common::UserState::VideoNotSelected,
+            role: join_params.role,
+            appearance: Default::default(),
         },
         sender: tx,
         last_chat_request: None,
@@ -82,56 +178,65 @@ pub async fn join_room(
 
     let join_info = match app_state
         .rooms
-        .join_room(&join_params.room_id.to_lowercase(), user)
+        .join_room(
+            &join_params.room_id.to_lowercase(),
+            user,
+            join_params.password.as_deref(),
+            join_params.host_token.as_deref(),
+        )
         .await
     {
         Ok(info) => info,
         Err(error) => {
-            return Ok(ws.on_upgrade(move |mut sock| async move {
-                if let Err(err) = sock
-                    .send(axum::extract::ws::Message::Close(Some(CloseFrame {
-                        code: ws::close_code::POLICY,
-                        reason: error.to_string().into(),
-                    })))
-                    .await
-                {
-                    warn!("Cant send close {err:?}");
-                }
-            }))
+            metrics::join_rejected(&error);
+            return Ok(reject_with(ws, ErrorCode::from(&error)));
         }
     };
     let room_id = join_params.room_id;
+    if let Some(user) = join_info.users.iter().find(|u| u.id == join_info.user_id) {
+        app_state.webhooks.emit(WebhookEvent::UserJoined {
+            room_id: join_info.room_id.clone(),
+            user: user.clone(),
+        });
+    }
     if let Some(player_status) = app_state.rooms.get_room_player_status(&room_id).await {
-        app_state
-            .rooms
-            .broadcast_msg_excluding(
-                &room_id,
-                Message::ServerMessage(common::message::ServerMessage::UserJoined(UserJoined {
-                    new_user: join_info.user_id,
-                    users: join_info.users.clone(),
-                    player_status,
-                })),
-                &[join_info.user_id],
-            )
-            .await;
+        metrics::broadcast(
+            &app_state.rooms,
+            &room_id,
+            Message::ServerMessage(common::message::ServerMessage::UserJoined(UserJoined {
+                new_user: join_info.user_id,
+                users: join_info.users.clone(),
+                player_status,
+            })),
+            &[join_info.user_id],
+        )
+        .await;
     }
+    let encoding = join_params.encoding;
+    let role = join_params.role;
     Ok(ws.on_upgrade(move |mut msgs| async move {
-        msgs.send_message(&Message::ServerMessage(
-            common::message::ServerMessage::RoomJoined(join_info),
-        ))
+        msgs.send_encoded(
+            &Message::ServerMessage(common::message::ServerMessage::RoomJoined(join_info)),
+            encoding,
+        )
         .await;
 
-        handle_websocket(app_state, &room_id, user_id, msgs, rx).await;
+        handle_websocket(app_state, &room_id, user_id, msgs, rx, encoding, role).await;
     }))
 }
 
+#[instrument(skip_all, fields(room_id = %room_id.to_lowercase(), %user_id, ?role))]
 async fn handle_websocket(
     app_state: AppState,
     room_id: &str,
     user_id: Uuid,
     mut socket: WebSocket,
     mut rx: tokio::sync::mpsc::Receiver<Message>,
+    mut encoding: Encoding,
+    role: UserRole,
 ) {
+    let connected_at = std::time::Instant::now();
+    let mut rate_limiter = RateLimiter::default();
     loop {
         tokio::select! {
             msg = socket.recv() => {
@@ -139,140 +244,232 @@ async fn handle_websocket(
                     Some(msg) => {
                         match msg {
                             Ok(msg) => {
-                                match msg {
-                                    axum::extract::ws::Message::Text(_) => {
-                                        //ignore
+                                let frame = match msg {
+                                    axum::extract::ws::Message::Text(text) => {
+                                        Some((Encoding::Json, Encoding::Json.decode(text.as_bytes())))
                                     },
                                     axum::extract::ws::Message::Binary(data) => {
-                                        let data = bincode::deserialize::<Message>(&data[..]);
-                                        match data {
-                                            Ok(original_message) => {
-                                                match &original_message {
-                                                    Message::ServerMessage(_) => {
-                                                        //ignore
+                                        Some((Encoding::Binary, Encoding::Binary.decode(&data[..])))
+                                    },
+                                    axum::extract::ws::Message::Ping(_) | axum::extract::ws::Message::Pong(_) => {
+                                        None
+                                    },
+                                    axum::extract::ws::Message::Close(_) => {
+                                        info!("Received Close from socket disconnecting {user_id}");
+                                        break;
+                                    },
+                                };
+                                if let Some((frame_encoding, data)) = frame {
+                                    // Reply in whatever encoding the client last spoke.
+                                    encoding = frame_encoding;
+                                    match data {
+                                        Ok(mut original_message) => {
+                                            if let Message::ClientMessage((_, message)) = &mut original_message {
+                                                metrics::client_message(message);
+                                                match rate_limiter.check(&app_state.config.rate_limit, message.kind()) {
+                                                    Decision::Allow => {},
+                                                    Decision::Reject { retry_after, notify } => {
+                                                        metrics::rate_limited(message.kind());
+                                                        if notify {
+                                                            let limited = common::message::RateLimited {
+                                                                kind: message.kind().to_string(),
+                                                                retry_after_ms: u64::try_from(retry_after.as_millis()).unwrap_or(u64::MAX),
+                                                            };
+                                                            socket.send_encoded(&Message::ServerMessage(common::message::ServerMessage::RateLimited(limited)), encoding).await;
+                                                        }
+                                                        continue;
                                                     },
-                                                    Message::ClientMessage((sender_id, message)) => {
-                                                        if sender_id == &user_id {
-                                                            match message {
-                                                                common::message::ClientMessage::Chat(_) => {
-                                                                    app_state.rooms.broadcast_msg_excluding(room_id, original_message, &[user_id]).await;
+                                                    Decision::Disconnect => {
+                                                        info!("Disconnecting {user_id}, kept sending while rate limited");
+                                                        if let Err(err) = socket
+                                                            .send(ws::Message::Close(Some(CloseFrame {
+                                                                code: ErrorCode::RateLimited.close_code(),
+                                                                reason: ErrorCode::RateLimited.to_string().into(),
+                                                            })))
+                                                            .await
+                                                        {
+                                                            warn!("Cant send close {err:?}");
+                                                        }
+                                                        break;
+                                                    },
+                                                }
+                                                if let Err(err) = validation::clean_message(&app_state.config.limits, message) {
+                                                    info!("Dropping invalid {} from {user_id}: {err}", message.kind());
+                                                    socket.send_encoded(&Message::ServerMessage(common::message::ServerMessage::Invalid(err)), encoding).await;
+                                                    continue;
+                                                }
+                                            }
+                                            match &original_message {
+                                                Message::ServerMessage(_) => {
+                                                    //ignore
+                                                },
+                                                Message::ClientMessage((sender_id, message)) => {
+                                                    if sender_id == &user_id && role.is_spectator() && !message.is_allowed_for_spectator() {
+                                                        // Position updates are sent periodically, only explicit actions deserve an error.
+                                                        if !matches!(message, ClientMessage::Update(_)) {
+                                                            socket.send_encoded(&Message::ServerMessage(common::message::ServerMessage::Error(ErrorCode::SpectatorNotAllowed)), encoding).await;
+                                                        }
+                                                    } else if sender_id == &user_id {
+                                                        match message {
+                                                            common::message::ClientMessage::Chat(text) => {
+                                                                app_state.webhooks.emit(WebhookEvent::Chat {
+                                                                    room_id: room_id.to_lowercase(),
+                                                                    user_id,
+                                                                    message: text.clone(),
+                                                                });
+                                                                metrics::broadcast(&app_state.rooms, room_id, original_message, &[user_id]).await;
+                                                            }
+                                                            common::message::ClientMessage::SetVideoMeta(video_name) => {
+                                                                app_state.rooms.with_room_mut(room_id, |room|{
+                                                                    if let Some(user) = room.users.iter_mut().find(|u|u.meta.id == user_id)
+                                                                    {
+
+                                                                        user.meta.state = UserState::VideoSelected(video_name.clone());
+                                                                    }
+                                                                }).await;
+                                                                metrics::broadcast(&app_state.rooms, room_id, original_message, &[user_id]).await;
+                                                            },
+                                                            common::message::ClientMessage::SetName(name) => {
+                                                                app_state.rooms.with_room_mut(room_id, |room|{
+                                                                    if let Some(user) = room.users.iter_mut().find(|u|u.meta.id == user_id)
+                                                                    {
+                                                                        user.meta.name = name.clone();
+                                                                    }
+                                                                }).await;
+                                                                // The sender gets it back too, the name may have been cleaned up.
+                                                                metrics::broadcast(&app_state.rooms, room_id, original_message, &[]).await;
+                                                            },
+                                                            common::message::ClientMessage::SetAppearance(appearance) => {
+                                                                app_state.rooms.with_room_mut(room_id, |room|{
+                                                                    if let Some(user) = room.users.iter_mut().find(|u|u.meta.id == user_id)
+                                                                    {
+                                                                        user.meta.appearance = appearance.clone();
+                                                                    }
+                                                                }).await;
+                                                                metrics::broadcast(&app_state.rooms, room_id, original_message, &[]).await;
+                                                            },
+                                                            common::message::ClientMessage::Play(val) => {
+                                                                app_state.rooms.with_room_mut(room_id, |room|{
+                                                                    room.player_status = PlayerStatus::Playing(*val);
+                                                                }).await;
+                                                                app_state.webhooks.emit(WebhookEvent::PlaybackStarted {
+                                                                    room_id: room_id.to_lowercase(),
+                                                                    user_id,
+                                                                    time: *val,
+                                                                });
+                                                                metrics::broadcast(&app_state.rooms, room_id, original_message, &[user_id]).await;
+                                                            },
+                                                            common::message::ClientMessage::Pause(val) => {
+                                                                let ended = app_state.rooms.with_room_mut(room_id, |room|{
+                                                                    let was_playing = !room.player_status.is_paused();
+                                                                    room.player_status = PlayerStatus::Paused(*val);
+                                                                    was_playing && room.users.iter().any(|u| u.meta.id == user_id && webhook::reached_end(&u.meta, *val))
+                                                                }).await.unwrap_or_default();
+                                                                app_state.webhooks.emit(WebhookEvent::PlaybackPaused {
+                                                                    room_id: room_id.to_lowercase(),
+                                                                    user_id,
+                                                                    time: *val,
+                                                                });
+                                                                if ended {
+                                                                    app_state.webhooks.emit(WebhookEvent::PlaybackEnded {
+                                                                        room_id: room_id.to_lowercase(),
+                                                                        user_id,
+                                                                        time: *val,
+                                                                    });
                                                                 }
-                                                                common::message::ClientMessage::SetVideoMeta(video_name) => {
-                                                                    app_state.rooms.with_room_mut(room_id, |room|{
-                                                                        if let Some(user) = room.users.iter_mut().find(|u|u.meta.id == user_id)
-                                                                        {
+                                                                metrics::broadcast(&app_state.rooms, room_id, original_message, &[user_id]).await;
+                                                            },
+                                                            common::message::ClientMessage::Seek(val,_) | common::message::ClientMessage::Update(val) => {
+                                                                app_state.rooms.with_room_mut(room_id, |room|{
+                                                                    match &mut room.player_status {
+                                                                        PlayerStatus::Paused(time) | PlayerStatus::Playing(time) => *time = *val,
+                                                                    }
+                                                                }).await;
+                                                                metrics::broadcast(&app_state.rooms, room_id, original_message, &[user_id]).await;
+                                                            },
+                                                            common::message::ClientMessage::SendSessionDesc(uuid, rtcsession_desc) => {
+                                                                info!("Sending description from {sender_id} to {uuid}");
+                                                                let sender = app_state.rooms.with_room(room_id, |room| {
+                                                                    room.users.iter().find(|user|user.meta.id == *uuid && !user.meta.role.is_spectator()).map(|user| user.sender.clone())
+                                                                }).await.flatten();
+                                                                if let Some(sender) = sender {
+                                                                    if let Err(err) = sender.send(Message::ClientMessage((*sender_id, ClientMessage::ReceivedSessionDesc(rtcsession_desc.clone())))).await{
+                                                                        warn!("Failed send session desc {err:?}");
+                                                                    }
+                                                                    info!("sent description from {sender_id} to {uuid}");
 
-                                                                            user.meta.state = UserState::VideoSelected(video_name.clone());
-                                                                        }
-                                                                    }).await;
-                                                                    app_state.rooms.broadcast_msg_excluding(room_id, original_message, &[user_id]).await;
-                                                                },
-                                                                common::message::ClientMessage::Play(val) => {
-                                                                    app_state.rooms.with_room_mut(room_id, |room|{
-                                                                        room.player_status = PlayerStatus::Playing(*val);
-                                                                    }).await;
-                                                                    app_state.rooms.broadcast_msg_excluding(room_id, original_message, &[user_id]).await;
-                                                                },
-                                                                common::message::ClientMessage::Pause(val) => {
-                                                                    app_state.rooms.with_room_mut(room_id, |room|{
-                                                                        room.player_status = PlayerStatus::Paused(*val);
-                                                                    }).await;
-                                                                    app_state.rooms.broadcast_msg_excluding(room_id, original_message, &[user_id]).await;
-                                                                },
-                                                                common::message::ClientMessage::Seek(val,_) | common::message::ClientMessage::Update(val) => {
-                                                                    app_state.rooms.with_room_mut(room_id, |room|{
-                                                                        match &mut room.player_status {
-                                                                            PlayerStatus::Paused(time) | PlayerStatus::Playing(time) => *time = *val,
-                                                                        }
-                                                                    }).await;
-                                                                    app_state.rooms.broadcast_msg_excluding(room_id, original_message, &[user_id]).await;
-                                                                },
-                                                                common::message::ClientMessage::SendSessionDesc(uuid, rtcsession_desc) => {
-                                                                    info!("Sending description from {sender_id} to {uuid}");
-                                                                    let sender = app_state.rooms.with_room(room_id, |room| {
-                                                                        room.users.iter().find(|user|user.meta.id == *uuid).map(|user| user.sender.clone())
-                                                                    }).await.flatten();
-                                                                    if let Some(sender) = sender {
-                                                                        if let Err(err) = sender.send(Message::ClientMessage((*sender_id, ClientMessage::ReceivedSessionDesc(rtcsession_desc.clone())))).await{
-                                                                            warn!("Failed send session desc {err:?}");
-                                                                        }
-                                                                        info!("sent description from {sender_id} to {uuid}");
+                                                                }else{
+                                                                    warn!("User {uuid} not found");
+                                                                }
+                                                            },
 
-                                                                    }else{
-                                                                        warn!("User {uuid} not found");
+                                                            common::message::ClientMessage::ExchangeCandidate(uuid, candidate) => {
+                                                                let sender = app_state.rooms.with_room(room_id, |room| {
+                                                                    room.users.iter().find(|user|user.meta.id == *uuid && !user.meta.role.is_spectator()).map(|user| user.sender.clone())
+                                                                }).await.flatten();
+                                                                if let Some(sender) = sender {
+                                                                    if let Err(err) = sender.send(Message::ClientMessage((*sender_id, ClientMessage::ExchangeCandidate(*sender_id,candidate.clone())))).await{
+                                                                        warn!("Failed send session desc {err:?}");
                                                                     }
-                                                                },
-
-                                                                common::message::ClientMessage::ExchangeCandidate(uuid, candidate) => {
-                                                                    let sender = app_state.rooms.with_room(room_id, |room| {
-                                                                        room.users.iter().find(|user|user.meta.id == *uuid).map(|user| user.sender.clone())
-                                                                    }).await.flatten();
-                                                                    if let Some(sender) = sender {
-                                                                        if let Err(err) = sender.send(Message::ClientMessage((*sender_id, ClientMessage::ExchangeCandidate(*sender_id,candidate.clone())))).await{
-                                                                            warn!("Failed send session desc {err:?}");
-                                                                        }
+                                                                }
+                                                            },
+                                                            common::message::ClientMessage::RequestVideoShare(uuid) => {
+                                                                let sender = app_state.rooms.with_room(room_id, |room| {
+                                                                    room.users.iter().find(|user|user.meta.id == *uuid && !user.meta.role.is_spectator()).map(|user| user.sender.clone())
+                                                                }).await.flatten();
+                                                                if let Some(sender) = sender {
+                                                                    if let Err(err) = sender.send(Message::ClientMessage((*sender_id, ClientMessage::RequestVideoShare(*sender_id)))).await{
+                                                                        warn!("Failed send session desc {err:?}");
                                                                     }
-                                                                },
-                                                                common::message::ClientMessage::RequestVideoShare(uuid) => {
-                                                                    let sender = app_state.rooms.with_room(room_id, |room| {
-                                                                        room.users.iter().find(|user|user.meta.id == *uuid).map(|user| user.sender.clone())
-                                                                    }).await.flatten();
-                                                                    if let Some(sender) = sender {
-                                                                        if let Err(err) = sender.send(Message::ClientMessage((*sender_id, ClientMessage::RequestVideoShare(*sender_id)))).await{
-                                                                            warn!("Failed send session desc {err:?}");
+                                                                }
+                                                            },
+                                                            common::message::ClientMessage::RequestCall(uuid, video,audio) => 'b:{
+                                                                if let Some((Some(last_send), sender)) = app_state.rooms.with_room(room_id,|room|{
+                                                                    room.users.iter().find(|user|user.meta.id == *sender_id).map(|u|(u.last_chat_request, u.sender.clone()))
+                                                                }).await.flatten() {
+                                                                    if std::time::Instant::now().duration_since(last_send) < app_state.config.rooms.call_request_cooldown() {
+                                                                        if let Err(err) = sender.send(Message::ServerMessage(common::message::ServerMessage::Error(ErrorCode::CallCooldown))).await{
+                                                                            warn!("Failed to send error {err:?}");
                                                                         }
+                                                                        info!("Frequent request, ignoring");
+                                                                        break 'b;
                                                                     }
-                                                                },
-                                                                common::message::ClientMessage::RequestCall(uuid, video,audio) => 'b:{
-                                                                    if let Some((Some(last_send), sender)) = app_state.rooms.with_room(room_id,|room|{
-                                                                        room.users.iter().find(|user|user.meta.id == *sender_id).map(|u|(u.last_chat_request, u.sender.clone()))
-                                                                    }).await.flatten() {
-                                                                        if std::time::Instant::now().duration_since(last_send) < std::time::Duration::from_secs(60) {
-                                                                            if let Err(err) = sender.send(Message::ServerMessage(common::message::ServerMessage::Error("Cant send vc request, Try after some time".to_string()))).await{
-                                                                                warn!("Failed to send error {err:?}");
-                                                                            }
-                                                                            info!("Frequent request, ignoring");
-                                                                            break 'b;
-                                                                        }
 
+                                                                }
+                                                                let sender = app_state.rooms.with_room_mut(room_id, |room| {
+                                                                    room.users.iter_mut().find(|user|user.meta.id == *uuid && !user.meta.role.is_spectator()).map(|user| {
+                                                                        user.last_chat_request = Some(std::time::Instant::now()) ;
+                                                                        user.sender.clone()
+                                                                    })
+                                                                }).await.flatten();
+                                                                if let Some(sender) = sender {
+                                                                    if let Err(err) = sender.send(Message::ClientMessage((*sender_id, ClientMessage::RequestCall(*sender_id, *video, *audio)))).await{
+                                                                        warn!("Failed send vc request {err:?}");
                                                                     }
-                                                                    let sender = app_state.rooms.with_room_mut(room_id, |room| {
-                                                                        room.users.iter_mut().find(|user|user.meta.id == *uuid).map(|user| {
-                                                                            user.last_chat_request = Some(std::time::Instant::now()) ;
-                                                                            user.sender.clone()
-                                                                        })
-                                                                    }).await.flatten();
-                                                                    if let Some(sender) = sender {
-                                                                        if let Err(err) = sender.send(Message::ClientMessage((*sender_id, ClientMessage::RequestCall(*sender_id, *video, *audio)))).await{
-                                                                            warn!("Failed send vc request {err:?}");
-                                                                        }
-                                                                    }else{
-                                                                        warn!("User doesnt exist, cant send vc request")
-                                                                    }
-                                                                },
-                                                                common::message::ClientMessage::ReceivedSessionDesc(_rtcsession_desc) => {
-                                                                    warn!("Shouldnt receive received desc");
-                                                                },
-                                                            }
+                                                                }else{
+                                                                    warn!("User doesnt exist, cant send vc request")
+                                                                }
+                                                            },
+                                                            common::message::ClientMessage::ReceivedSessionDesc(_rtcsession_desc) => {
+                                                                warn!("Shouldnt receive received desc");
+                                                            },
+                                                            common::message::ClientMessage::CreateOverlayToken => {
+                                                                let reply = match app_state.rooms.create_overlay_token(room_id, user_id).await {
+                                                                    Ok(token) => common::message::ServerMessage::OverlayToken(token),
+                                                                    Err(err) => common::message::ServerMessage::Error(ErrorCode::from(&err)),
+                                                                };
+                                                                socket.send_encoded(&Message::ServerMessage(reply), encoding).await;
+                                                            },
                                                         }
-                                                    },
-                                                }
-                                            },
-                                            Err(err) => {
-                                                warn!("Received msg decode error {err:#?}")
-                                            },
-                                        }
-                                    },
-                                    axum::extract::ws::Message::Ping(_) => {
-                                        //ignore
-                                    },
-                                    axum::extract::ws::Message::Pong(_) => {
-                                        //ignore
-                                    },
-                                    axum::extract::ws::Message::Close(_) => {
-                                        info!("Received Close from socket disconnecting {user_id}");
-                                        break;
-                                    },
+                                                    }
+                                                },
+                                            }
+                                        },
+                                        Err(err) => {
+                                            metrics::decode_error(frame_encoding);
+                                            warn!("Received msg decode error {err:#?}")
+                                        },
+                                    }
                                 }
                             }
                             Err(err) => {
@@ -287,36 +484,70 @@ async fn handle_websocket(
                     },
                 }
             }
+            _ = app_state.shutdown.closing() => {
+                info!("Server shutting down, disconnecting {user_id}");
+                if let Err(err) = socket
+                    .send(ws::Message::Close(Some(CloseFrame {
+                        code: CLOSE_CODE_SERVER_RESTART,
+                        reason: RESTART_REASON.into(),
+                    })))
+                    .await
+                {
+                    warn!("Cant send close {err:?}");
+                }
+                break;
+            }
             msg = rx.recv() => {
                 match msg {
                     Some(msg) => {
-                        socket.send_message(&msg).await;
+                        socket.send_encoded(&msg, encoding).await;
                     }
                     None => {
-                        // Sender dropped, room closed?
+                        // Sender dropped, the room was closed through the API.
                         info!("Received None from rx disconnecting {user_id}");
+                        if let Err(err) = socket
+                            .send(ws::Message::Close(Some(CloseFrame {
+                                code: ErrorCode::RoomClosed.close_code(),
+                                reason: ErrorCode::RoomClosed.to_string().into(),
+                            })))
+                            .await
+                        {
+                            warn!("Cant send close {err:?}");
+                        }
                         break;
                     }
                 }
             }
         }
     }
+    metrics::websocket_closed(role, connected_at.elapsed());
     let remaining_users = app_state.rooms.remove_user(room_id, user_id).await;
     info!("Disconnected user {user_id}");
+    app_state.webhooks.emit(WebhookEvent::UserLeft {
+        room_id: room_id.to_lowercase(),
+        user_id,
+    });
+    if remaining_users.as_ref().is_some_and(Vec::is_empty)
+        && app_state.rooms.with_room(room_id, |_| ()).await.is_none()
+    {
+        app_state.webhooks.emit(WebhookEvent::RoomClosed {
+            room_id: room_id.to_lowercase(),
+            reason: RoomCloseReason::Empty,
+        });
+    }
     if let Some(users) = remaining_users {
         if let Some(player_status) = app_state.rooms.get_room_player_status(room_id).await {
-            app_state
-                .rooms
-                .broadcast_msg_excluding(
-                    room_id,
-                    Message::ServerMessage(common::message::ServerMessage::UserLeft(UserLeft {
-                        user_left: user_id,
-                        users,
-                        player_status,
-                    })),
-                    &[user_id],
-                )
-                .await;
+            metrics::broadcast(
+                &app_state.rooms,
+                room_id,
+                Message::ServerMessage(common::message::ServerMessage::UserLeft(UserLeft {
+                    user_left: user_id,
+                    users,
+                    player_status,
+                })),
+                &[user_id],
+            )
+            .await;
         }
     }
 }
@@ -326,12 +557,17 @@ impl IntoResponse for RoomJoinError {
         match self {
             RoomJoinError::RoomProviderError(err) => match err {
                 RoomProviderError::KeyGenerationFailed
-                | RoomProviderError::RTCConfigGenerationFailed(_)
+                | RoomProviderError::RTCConfigGenerationFailed
                 | RoomProviderError::TimeError(_)
                 | RoomProviderError::HmacError(_) => {
                     (StatusCode::INTERNAL_SERVER_ERROR, format!("{err:#?}")).into_response()
                 }
-                RoomProviderError::RoomDoesntExist | RoomProviderError::RoomFull => {
+                RoomProviderError::RoomDoesntExist
+                | RoomProviderError::RoomFull
+                | RoomProviderError::WrongPassword
+                | RoomProviderError::InvalidHostToken
+                | RoomProviderError::InvalidOverlayToken
+                | RoomProviderError::NotHost => {
                     (StatusCode::BAD_REQUEST, format!("{err:#?}")).into_response()
                 }
             },
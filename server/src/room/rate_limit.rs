@@ -0,0 +1,218 @@
+//! Per user token buckets, one for every [`ClientMessage::kind`](common::message::ClientMessage::kind).
+
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+
+use crate::config::RateLimitConfig;
+
+pub enum Decision {
+    Allow,
+    /// Drop the message, `notify` is only set for the first drop in a row so a flooding
+    /// client is not answered with a flood.
+    Reject {
+        retry_after: Duration,
+        notify: bool,
+    },
+    /// Too many drops within the violation window, close the socket.
+    Disconnect,
+}
+
+struct Bucket {
+    tokens: f64,
+    updated_at: Instant,
+    limited: bool,
+}
+
+#[derive(Default)]
+pub struct RateLimiter {
+    buckets: HashMap<&'static str, Bucket>,
+    violations: u32,
+    window_start: Option<Instant>,
+}
+
+impl RateLimiter {
+    pub fn check(&mut self, config: &RateLimitConfig, kind: &'static str) -> Decision {
+        self.check_at(config, kind, Instant::now())
+    }
+
+    fn check_at(&mut self, config: &RateLimitConfig, kind: &'static str, now: Instant) -> Decision {
+        if !config.enabled {
+            return Decision::Allow;
+        }
+        let limit = config.limit(kind);
+        let burst = f64::from(limit.burst);
+        let bucket = self.buckets.entry(kind).or_insert(Bucket {
+            tokens: burst,
+            updated_at: now,
+            limited: false,
+        });
+        let elapsed = now.duration_since(bucket.updated_at).as_secs_f64();
+        bucket.tokens = (bucket.tokens + elapsed * limit.per_sec).min(burst);
+        bucket.updated_at = now;
+
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            bucket.limited = false;
+            return Decision::Allow;
+        }
+        let retry_after = Duration::from_secs_f64((1.0 - bucket.tokens) / limit.per_sec);
+        let notify = !bucket.limited;
+        bucket.limited = true;
+
+        let window_start = self.window_start.get_or_insert(now);
+        if now.duration_since(*window_start) > config.violation_window() {
+            *window_start = now;
+            self.violations = 0;
+        }
+        self.violations += 1;
+        if self.violations > config.disconnect_after {
+            Decision::Disconnect
+        } else {
+            Decision::Reject {
+                retry_after,
+                notify,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::config::RateLimit;
+
+    use super::*;
+
+    /// Two per second with a burst of three, closing on the third drop within ten seconds.
+    fn config() -> RateLimitConfig {
+        RateLimitConfig {
+            disconnect_after: 2,
+            violation_window_secs: 10,
+            kinds: [(
+                "chat".to_string(),
+                RateLimit {
+                    per_sec: 2.0,
+                    burst: 3,
+                },
+            )]
+            .into(),
+            ..Default::default()
+        }
+    }
+
+    fn allows_burst(limiter: &mut RateLimiter, config: &RateLimitConfig, now: Instant) {
+        for _ in 0..3 {
+            assert!(matches!(
+                limiter.check_at(config, "chat", now),
+                Decision::Allow
+            ));
+        }
+    }
+
+    #[test]
+    fn rejects_once_burst_is_used() {
+        let (config, now) = (config(), Instant::now());
+        let mut limiter = RateLimiter::default();
+        allows_burst(&mut limiter, &config, now);
+        assert!(matches!(
+            limiter.check_at(&config, "chat", now),
+            Decision::Reject { retry_after, notify: true } if retry_after == Duration::from_millis(500)
+        ));
+        // Only the first drop in a row is reported back.
+        assert!(matches!(
+            limiter.check_at(&config, "chat", now),
+            Decision::Reject { notify: false, .. }
+        ));
+        // Other kinds have their own bucket.
+        assert!(matches!(
+            limiter.check_at(&config, "play", now),
+            Decision::Allow
+        ));
+    }
+
+    #[test]
+    fn refills_over_time() {
+        let (config, now) = (config(), Instant::now());
+        let mut limiter = RateLimiter::default();
+        allows_burst(&mut limiter, &config, now);
+        assert!(matches!(
+            limiter.check_at(&config, "chat", now + Duration::from_millis(499)),
+            Decision::Reject { notify: true, .. }
+        ));
+        let later = now + Duration::from_millis(500);
+        assert!(matches!(
+            limiter.check_at(&config, "chat", later),
+            Decision::Allow
+        ));
+        // An allowed message ends the run of drops, so the next one is reported again.
+        assert!(matches!(
+            limiter.check_at(&config, "chat", later),
+            Decision::Reject { notify: true, .. }
+        ));
+        // The bucket never holds more than the burst.
+        let much_later = later + Duration::from_secs(60);
+        allows_burst(&mut limiter, &config, much_later);
+        assert!(matches!(
+            limiter.check_at(&config, "chat", much_later),
+            Decision::Reject { .. }
+        ));
+    }
+
+    #[test]
+    fn disconnects_after_repeated_violations() {
+        let (config, now) = (config(), Instant::now());
+        let mut limiter = RateLimiter::default();
+        allows_burst(&mut limiter, &config, now);
+        for _ in 0..2 {
+            assert!(matches!(
+                limiter.check_at(&config, "chat", now),
+                Decision::Reject { .. }
+            ));
+        }
+        assert!(matches!(
+            limiter.check_at(&config, "chat", now),
+            Decision::Disconnect
+        ));
+    }
+
+    #[test]
+    fn forgets_violations_after_the_window() {
+        let (config, now) = (config(), Instant::now());
+        let mut limiter = RateLimiter::default();
+        allows_burst(&mut limiter, &config, now);
+        for _ in 0..2 {
+            assert!(matches!(
+                limiter.check_at(&config, "chat", now),
+                Decision::Reject { .. }
+            ));
+        }
+        let later = now + config.violation_window() + Duration::from_secs(1);
+        allows_burst(&mut limiter, &config, later);
+        for _ in 0..2 {
+            assert!(matches!(
+                limiter.check_at(&config, "chat", later),
+                Decision::Reject { .. }
+            ));
+        }
+        assert!(matches!(
+            limiter.check_at(&config, "chat", later),
+            Decision::Disconnect
+        ));
+    }
+
+    #[test]
+    fn allows_everything_when_disabled() {
+        let config = RateLimitConfig {
+            enabled: false,
+            ..config()
+        };
+        let (mut limiter, now) = (RateLimiter::default(), Instant::now());
+        for _ in 0..100 {
+            assert!(matches!(
+                limiter.check_at(&config, "chat", now),
+                Decision::Allow
+            ));
+        }
+    }
+}
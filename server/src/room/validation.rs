@@ -0,0 +1,326 @@
+//! Checks on everything a client sends, text is NFC normalized and stripped of control
+//! characters before it is counted.
+
+use common::{
+    is_hex_color,
+    message::{ClientMessage, Field, OfferReason, ValidationError},
+    params::{HostParams, JoinParams},
+    Appearance, Avatar,
+};
+use unicode_normalization::UnicodeNormalization;
+
+use crate::config::LimitsConfig;
+
+const MAX_ROOM_ID_CHARS: usize = 64;
+const MAX_PASSWORD_CHARS: usize = 128;
+/// `offer`, `answer`, `pranswer` or `rollback`.
+const MAX_SDP_TYPE_BYTES: usize = 16;
+const MAX_SHARED_TRACKS: usize = 8;
+const MAX_TRACK_ID_BYTES: usize = 128;
+/// Room for emoji built from several code points, like flags or families.
+const MAX_AVATAR_EMOJI_CHARS: usize = 16;
+const AVATAR_IMAGE_PREFIXES: [&str; 4] = [
+    "data:image/png;base64,",
+    "data:image/jpeg;base64,",
+    "data:image/webp;base64,",
+    "data:image/gif;base64,",
+];
+
+/// Normalizes the text, drops control characters except newlines when `multiline` and trims.
+fn clean_text(text: &str, multiline: bool) -> String {
+    text.nfc()
+        .filter(|c| !c.is_control() || (multiline && *c == '\n'))
+        .collect::<String>()
+        .trim()
+        .to_string()
+}
+
+fn check_chars(field: Field, text: &str, max: usize) -> Result<(), ValidationError> {
+    if text.is_empty() {
+        return Err(ValidationError::Empty(field));
+    }
+    if text.chars().count() > max {
+        return Err(ValidationError::TooLong(field, max));
+    }
+    Ok(())
+}
+
+fn check_bytes(field: Field, data: &str, max: usize) -> Result<(), ValidationError> {
+    if data.len() > max {
+        return Err(ValidationError::TooLong(field, max));
+    }
+    Ok(())
+}
+
+fn check_time(field: Field, time: f64) -> Result<(), ValidationError> {
+    if !time.is_finite() || time < 0.0 {
+        return Err(ValidationError::Invalid(field));
+    }
+    Ok(())
+}
+
+pub fn clean_name(limits: &LimitsConfig, name: &str) -> Result<String, ValidationError> {
+    let name = clean_text(name, false);
+    check_chars(Field::Name, &name, limits.max_name_chars)?;
+    Ok(name)
+}
+
+pub fn clean_host_params(
+    limits: &LimitsConfig,
+    params: &mut HostParams,
+) -> Result<(), ValidationError> {
+    params.name = clean_name(limits, &params.name)?;
+    Ok(())
+}
+
+pub fn clean_join_params(
+    limits: &LimitsConfig,
+    params: &mut JoinParams,
+) -> Result<(), ValidationError> {
+    params.name = clean_name(limits, &params.name)?;
+    params.room_id = params.room_id.trim().to_string();
+    check_chars(Field::RoomId, &params.room_id, MAX_ROOM_ID_CHARS)?;
+    if let Some(password) = &params.password {
+        if password.chars().count() > MAX_PASSWORD_CHARS {
+            return Err(ValidationError::TooLong(
+                Field::Password,
+                MAX_PASSWORD_CHARS,
+            ));
+        }
+    }
+    Ok(())
+}
+
+fn clean_appearance(
+    limits: &LimitsConfig,
+    appearance: &mut Appearance,
+) -> Result<(), ValidationError> {
+    if let Some(color) = &mut appearance.color {
+        *color = color.to_ascii_lowercase();
+        if !is_hex_color(color) {
+            return Err(ValidationError::Invalid(Field::Color));
+        }
+    }
+    match &mut appearance.avatar {
+        Some(Avatar::Emoji(emoji)) => {
+            *emoji = clean_text(emoji, false);
+            check_chars(Field::Avatar, emoji, MAX_AVATAR_EMOJI_CHARS)
+        }
+        Some(Avatar::Image(url)) => {
+            check_bytes(Field::Avatar, url, limits.max_avatar_bytes())?;
+            let data = AVATAR_IMAGE_PREFIXES
+                .iter()
+                .find_map(|prefix| url.strip_prefix(prefix))
+                .ok_or(ValidationError::Invalid(Field::Avatar))?;
+            if data.is_empty()
+                || !data
+                    .bytes()
+                    .all(|b| b.is_ascii_alphanumeric() || matches!(b, b'+' | b'/' | b'='))
+            {
+                return Err(ValidationError::Invalid(Field::Avatar));
+            }
+            Ok(())
+        }
+        None => Ok(()),
+    }
+}
+
+/// Rejects malformed messages and cleans up the text of the rest in place.
+pub fn clean_message(
+    limits: &LimitsConfig,
+    message: &mut ClientMessage,
+) -> Result<(), ValidationError> {
+    match message {
+        ClientMessage::Chat(text) => {
+            *text = clean_text(text, true);
+            check_chars(Field::Chat, text, limits.max_chat_chars)
+        }
+        ClientMessage::SetVideoMeta(meta) => {
+            meta.name = clean_text(&meta.name, false);
+            check_chars(Field::VideoName, &meta.name, limits.max_video_name_chars)?;
+            match meta.duration {
+                Some(duration) => check_time(Field::VideoDuration, duration),
+                None => Ok(()),
+            }
+        }
+        ClientMessage::Play(time)
+        | ClientMessage::Pause(time)
+        | ClientMessage::Seek(time, _)
+        | ClientMessage::Update(time) => check_time(Field::Position, *time),
+        ClientMessage::SendSessionDesc(_, desc) | ClientMessage::ReceivedSessionDesc(desc) => {
+            check_bytes(Field::SessionDesc, &desc.sdp, limits.max_sdp_bytes)?;
+            check_bytes(Field::SessionDesc, &desc.typ, MAX_SDP_TYPE_BYTES)?;
+            if let OfferReason::VideoShare(tracks) = &desc.reason {
+                if tracks.len() > MAX_SHARED_TRACKS
+                    || tracks.iter().any(|track| track.len() > MAX_TRACK_ID_BYTES)
+                {
+                    return Err(ValidationError::Invalid(Field::SessionDesc));
+                }
+            }
+            Ok(())
+        }
+        ClientMessage::ExchangeCandidate(_, candidate) => check_bytes(
+            Field::IceCandidate,
+            candidate,
+            limits.max_ice_candidate_bytes,
+        ),
+        ClientMessage::SetName(name) => {
+            *name = clean_name(limits, name)?;
+            Ok(())
+        }
+        ClientMessage::SetAppearance(appearance) => clean_appearance(limits, appearance),
+        ClientMessage::RequestCall(..)
+        | ClientMessage::RequestVideoShare(_)
+        | ClientMessage::CreateOverlayToken => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use common::message::{RTCSessionDesc, VideoMeta};
+
+    use super::*;
+
+    fn chat(text: &str) -> Result<String, ValidationError> {
+        let mut message = ClientMessage::Chat(text.to_string());
+        clean_message(&LimitsConfig::default(), &mut message)?;
+        match message {
+            ClientMessage::Chat(text) => Ok(text),
+            _ => unreachable!(),
+        }
+    }
+
+    fn avatar(avatar: Avatar) -> Result<Appearance, ValidationError> {
+        let mut message = ClientMessage::SetAppearance(Appearance {
+            color: None,
+            avatar: Some(avatar),
+        });
+        clean_message(&LimitsConfig::default(), &mut message)?;
+        match message {
+            ClientMessage::SetAppearance(appearance) => Ok(appearance),
+            _ => unreachable!(),
+        }
+    }
+
+    fn shared_tracks(tracks: Vec<String>) -> Result<(), ValidationError> {
+        clean_message(
+            &LimitsConfig::default(),
+            &mut ClientMessage::ReceivedSessionDesc(RTCSessionDesc {
+                typ: "offer".to_string(),
+                sdp: "v=0".to_string(),
+                reason: OfferReason::VideoShare(tracks),
+            }),
+        )
+    }
+
+    #[test]
+    fn strips_control_characters() {
+        assert_eq!(chat("a\u{7}b\u{1b}[2Jc").unwrap(), "ab[2Jc");
+        assert_eq!(chat("one\ntwo\r\n").unwrap(), "one\ntwo");
+        assert_eq!(
+            clean_name(&LimitsConfig::default(), "first\nlast\t").unwrap(),
+            "firstlast"
+        );
+    }
+
+    #[test]
+    fn normalizes_before_counting() {
+        let limits = LimitsConfig {
+            max_name_chars: 1,
+            ..Default::default()
+        };
+        assert_eq!(clean_name(&limits, "e\u{301}").unwrap(), "é");
+    }
+
+    #[test]
+    fn rejects_names_empty_after_cleaning() {
+        let limits = LimitsConfig::default();
+        for name in ["", "   ", "\u{7}\u{8}", " \n\t "] {
+            assert_eq!(
+                clean_name(&limits, name),
+                Err(ValidationError::Empty(Field::Name))
+            );
+        }
+    }
+
+    #[test]
+    fn limits_length() {
+        let limits = LimitsConfig {
+            max_name_chars: 4,
+            ..Default::default()
+        };
+        assert_eq!(clean_name(&limits, "ábcd").unwrap(), "ábcd");
+        assert_eq!(
+            clean_name(&limits, "ábcde"),
+            Err(ValidationError::TooLong(Field::Name, 4))
+        );
+        let max = LimitsConfig::default().max_chat_chars;
+        assert!(chat(&"x".repeat(max)).is_ok());
+        assert_eq!(
+            chat(&"x".repeat(max + 1)),
+            Err(ValidationError::TooLong(Field::Chat, max))
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_positions() {
+        let limits = LimitsConfig::default();
+        for time in [-1.0, f64::NAN, f64::INFINITY, f64::NEG_INFINITY] {
+            for mut message in [
+                ClientMessage::Play(time),
+                ClientMessage::Pause(time),
+                ClientMessage::Seek(time, true),
+                ClientMessage::Update(time),
+            ] {
+                assert_eq!(
+                    clean_message(&limits, &mut message),
+                    Err(ValidationError::Invalid(Field::Position))
+                );
+            }
+            let mut message = ClientMessage::SetVideoMeta(VideoMeta {
+                name: "movie.mkv".to_string(),
+                duration: Some(time),
+            });
+            assert_eq!(
+                clean_message(&limits, &mut message),
+                Err(ValidationError::Invalid(Field::VideoDuration))
+            );
+        }
+        assert!(clean_message(&limits, &mut ClientMessage::Play(0.0)).is_ok());
+    }
+
+    #[test]
+    fn checks_avatar_images() {
+        assert!(avatar(Avatar::Image(
+            "data:image/png;base64,iVBORw0KGgo=".to_string()
+        ))
+        .is_ok());
+        for url in [
+            "data:image/svg+xml;base64,PHN2Zz4=",
+            "https://example.com/avatar.png",
+            "data:image/png;base64,",
+            "data:image/png;base64,iVBOR\"><script>",
+            "data:image/png;base64,iVBOR w0KGgo=",
+        ] {
+            assert_eq!(
+                avatar(Avatar::Image(url.to_string())),
+                Err(ValidationError::Invalid(Field::Avatar)),
+                "{url}"
+            );
+        }
+    }
+
+    #[test]
+    fn limits_shared_tracks() {
+        let track = |len| "t".repeat(len);
+        assert!(shared_tracks(vec![track(MAX_TRACK_ID_BYTES); MAX_SHARED_TRACKS]).is_ok());
+        assert_eq!(
+            shared_tracks(vec![track(1); MAX_SHARED_TRACKS + 1]),
+            Err(ValidationError::Invalid(Field::SessionDesc))
+        );
+        assert_eq!(
+            shared_tracks(vec![track(MAX_TRACK_ID_BYTES + 1)]),
+            Err(ValidationError::Invalid(Field::SessionDesc))
+        );
+    }
+}
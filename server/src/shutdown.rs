@@ -0,0 +1,284 @@
+//! Graceful shutdown: refuse new rooms, announce the restart, save rooms to
+//! `shutdown.state_file`, then close every socket with
+//! [`CLOSE_CODE_SERVER_RESTART`](common::message::CLOSE_CODE_SERVER_RESTART).
+
+use std::{
+    path::Path,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use common::{
+    message::{Message, ServerMessage},
+    RoomProvider, RoomSnapshot,
+};
+use tokio::{io::AsyncWriteExt, sync::watch};
+use tracing::{info, warn};
+
+use crate::AppState;
+
+#[derive(Clone)]
+pub struct Shutdown {
+    draining: Arc<AtomicBool>,
+    closing: watch::Sender<bool>,
+}
+
+impl Default for Shutdown {
+    fn default() -> Self {
+        Self {
+            draining: Arc::new(AtomicBool::new(false)),
+            closing: watch::channel(false).0,
+        }
+    }
+}
+
+impl Shutdown {
+    /// Set once a shutdown signal arrived, no new rooms or users are accepted after.
+    pub fn is_draining(&self) -> bool {
+        self.draining.load(Ordering::Relaxed)
+    }
+
+    /// Resolves once sockets should be closed.
+    pub async fn closing(&self) {
+        let mut closing = self.closing.subscribe();
+        // Errors only when the sender is gone, which also means we are done.
+        let _ = closing.wait_for(|closing| *closing).await;
+    }
+}
+
+/// Close reason sent with [`common::message::CLOSE_CODE_SERVER_RESTART`].
+pub const RESTART_REASON: &str = "Server is restarting";
+
+async fn signal() {
+    let ctrl_c = async {
+        if let Err(err) = tokio::signal::ctrl_c().await {
+            warn!("Cannot listen for ctrl-c {err:?}");
+            std::future::pending::<()>().await;
+        }
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()) {
+            Ok(mut signal) => {
+                signal.recv().await;
+            }
+            Err(err) => {
+                warn!("Cannot listen for SIGTERM {err:?}");
+                std::future::pending::<()>().await;
+            }
+        }
+    };
+
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => {},
+        _ = terminate => {},
+    }
+}
+
+/// Waits for SIGTERM or ctrl-c and walks through the shutdown, resolves once sockets are closing.
+pub async fn run(app_state: AppState) {
+    signal().await;
+    let shutdown = &app_state.shutdown;
+    let config = &app_state.config.shutdown;
+    info!("Shutting down, closing sockets in {}s", config.notice_secs);
+    shutdown.draining.store(true, Ordering::Relaxed);
+
+    let resume_window = if config.state_file.is_some() {
+        config.resume_window_secs
+    } else {
+        0
+    };
+    app_state
+        .rooms
+        .broadcast_all(Message::ServerMessage(ServerMessage::Restarting(
+            resume_window,
+        )))
+        .await;
+    tokio::time::sleep(config.notice()).await;
+
+    // Sockets remove their users as they close and empty rooms go with them, save first.
+    if let Some(path) = &config.state_file {
+        save(&app_state.rooms, path).await;
+    }
+    shutdown.closing.send_replace(true);
+}
+
+/// Resolves once every socket has left its room.
+pub async fn drained(rooms: &RoomProvider) {
+    while rooms
+        .list_rooms()
+        .await
+        .iter()
+        .any(|room| !room.users.is_empty())
+    {
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+}
+
+async fn save(rooms: &RoomProvider, path: &Path) {
+    let snapshot = rooms.snapshot().await;
+    let data = match serde_json::to_vec(&snapshot) {
+        Ok(data) => data,
+        Err(err) => {
+            warn!("Cannot serialize rooms {err:?}");
+            return;
+        }
+    };
+    match write_private(path, &data).await {
+        Ok(()) => info!("Saved {} rooms to {path:?}", snapshot.len()),
+        Err(err) => warn!("Cannot save rooms to {path:?} {err:?}"),
+    }
+}
+
+/// Writes to a sibling file only the owner can read, then renames it over `path`, so a crash
+/// mid-write leaves the previous file intact and room secrets are never world readable.
+async fn write_private(path: &Path, data: &[u8]) -> std::io::Result<()> {
+    let mut tmp_name = path.file_name().unwrap_or_default().to_os_string();
+    tmp_name.push(".tmp");
+    let tmp = path.with_file_name(tmp_name);
+    // Left behind by a crash, possibly with other permissions.
+    let _ = tokio::fs::remove_file(&tmp).await;
+
+    let mut options = tokio::fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    options.mode(0o600);
+    let result = async {
+        let mut file = options.open(&tmp).await?;
+        file.write_all(data).await?;
+        file.sync_all().await
+    }
+    .await;
+    match result {
+        Ok(()) => tokio::fs::rename(&tmp, path).await,
+        Err(err) => {
+            let _ = tokio::fs::remove_file(&tmp).await;
+            Err(err)
+        }
+    }
+}
+
+/// Restores rooms saved by the previous run, the file is removed so they are only restored once.
+pub async fn restore(app_state: &AppState) {
+    let config = &app_state.config.shutdown;
+    if let Some(path) = &config.state_file {
+        load(&app_state.rooms, path, config.resume_window()).await;
+    }
+}
+
+async fn load(rooms: &RoomProvider, path: &Path, keep_for: Duration) {
+    let data = match tokio::fs::read(path).await {
+        Ok(data) => data,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return,
+        Err(err) => {
+            warn!("Cannot read saved rooms from {path:?} {err:?}");
+            return;
+        }
+    };
+    match serde_json::from_slice::<Vec<RoomSnapshot>>(&data) {
+        Ok(snapshot) => {
+            let restored = rooms.restore(snapshot, keep_for).await;
+            info!("Restored {restored} rooms from {path:?}");
+        }
+        Err(err) => warn!("Cannot parse saved rooms in {path:?} {err:?}"),
+    }
+    if let Err(err) = tokio::fs::remove_file(path).await {
+        warn!("Cannot remove {path:?} {err:?}");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Instant;
+
+    use common::{
+        Appearance, PlayerStatus, RoomOptions, RoomProviderError, RoomSettings, RtcMode,
+        RtcSettings, User, UserMeta, UserRole, UserState,
+    };
+    use uuid::Uuid;
+
+    use super::*;
+
+    fn provider() -> RoomProvider {
+        RoomProvider::new(RoomSettings {
+            max_room_size: 3,
+            rtc: RtcSettings {
+                mode: RtcMode::SyncOnly,
+                ..Default::default()
+            },
+            ..Default::default()
+        })
+    }
+
+    fn user() -> User {
+        User {
+            meta: UserMeta {
+                id: Uuid::new_v4(),
+                name: "test".to_string(),
+                state: UserState::VideoNotSelected,
+                role: UserRole::Member,
+                appearance: Appearance::default(),
+            },
+            sender: tokio::sync::mpsc::channel(1).0,
+            last_chat_request: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn restores_saved_rooms() {
+        let path = std::env::temp_dir().join(format!("tvmate-rooms-{}.json", Uuid::new_v4()));
+        let rooms = provider();
+        let room_id = rooms
+            .create_room(RoomOptions {
+                capacity: Some(2),
+                password: Some("secret".to_string()),
+                host_token: Some("token".to_string()),
+                keep_until: Some(Instant::now() + Duration::from_secs(3600)),
+            })
+            .await
+            .unwrap();
+        rooms
+            .with_room_mut(&room_id, |room| {
+                room.player_status = PlayerStatus::Playing(42.0)
+            })
+            .await;
+        save(&rooms, &path).await;
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+
+        let restored = provider();
+        load(&restored, &path, Duration::from_secs(60)).await;
+        assert!(!path.exists());
+        let (player_status, options) = restored
+            .with_room(&room_id, |room| {
+                (room.player_status.clone(), room.options.clone())
+            })
+            .await
+            .unwrap();
+        assert!(matches!(player_status, PlayerStatus::Paused(time) if time == 42.0));
+        assert_eq!(options.capacity, Some(2));
+        // Kept for the resume window rather than the deadline of the previous run.
+        let keep_until = options.keep_until.unwrap();
+        assert!(keep_until <= Instant::now() + Duration::from_secs(60));
+        assert!(restored.remove_expired().await.is_empty());
+
+        let result = restored.join_room(&room_id, user(), None, None).await;
+        assert!(matches!(result, Err(RoomProviderError::WrongPassword)));
+        restored
+            .join_room(&room_id, user(), Some("secret"), None)
+            .await
+            .unwrap();
+        restored.authorize_host(&room_id, "token").await.unwrap();
+    }
+}
@@ -0,0 +1,186 @@
+use std::path::{Path, PathBuf};
+
+use axum::{extract::State, http::StatusCode, Json};
+use common::{
+    message::{MIN_SUPPORTED_PROTOCOL_VERSION, PROTOCOL_VERSION},
+    version::VersionInfo,
+    RtcMode,
+};
+use serde::Serialize;
+use tracing::warn;
+
+use crate::{
+    config::{Config, RtcSection},
+    AppState,
+};
+
+pub async fn version() -> Json<VersionInfo> {
+    Json(VersionInfo {
+        name: "tvmate".to_string(),
+        version: env!("CARGO_PKG_VERSION").to_string(),
+        protocol_version: PROTOCOL_VERSION,
+        min_protocol_version: Some(MIN_SUPPORTED_PROTOCOL_VERSION),
+        git_sha: option_env!("TVMATE_GIT_SHA").map(str::to_string),
+        build_profile: option_env!("TVMATE_BUILD_PROFILE").map(str::to_string),
+    })
+}
+
+/// Liveness probe, answers as long as the server handles requests.
+pub async fn healthz() -> &'static str {
+    "ok"
+}
+
+#[derive(Debug, Serialize)]
+pub struct Readiness {
+    pub ready: bool,
+    pub checks: Vec<ReadinessCheck>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ReadinessCheck {
+    pub name: &'static str,
+    pub ok: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl ReadinessCheck {
+    fn new(name: &'static str, result: Result<(), String>) -> Self {
+        Self {
+            name,
+            ok: result.is_ok(),
+            error: result.err(),
+        }
+    }
+}
+
+/// Readiness probe, answers 503 while shutting down or when something the config relies on
+/// is missing.
+pub async fn readyz(State(app_state): State<AppState>) -> (StatusCode, Json<Readiness>) {
+    let config = &app_state.config;
+    let mut checks = vec![
+        ReadinessCheck::new(
+            "accepting_rooms",
+            if app_state.shutdown.is_draining() {
+                Err("server is shutting down".to_string())
+            } else {
+                Ok(())
+            },
+        ),
+        ReadinessCheck::new("turn", check_turn(&config.rtc)),
+    ];
+    checks.extend(app_state.writable_dirs.checks().await);
+
+    let ready = checks.iter().all(|check| check.ok);
+    let status = if ready {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+    (status, Json(Readiness { ready, checks }))
+}
+
+fn check_turn(rtc: &RtcSection) -> Result<(), String> {
+    if rtc.mode() != RtcMode::Full {
+        return Ok(());
+    }
+    if rtc.turn_secret.as_deref().unwrap_or_default().is_empty() {
+        return Err("no TURN secret configured".to_string());
+    }
+    if rtc.turn_url.is_empty() {
+        return Err("no TURN url configured".to_string());
+    }
+    Ok(())
+}
+
+/// Directories the config writes to, test written once at startup since probes run often.
+#[derive(Debug, Default)]
+pub struct WritableDirs {
+    dirs: Vec<(&'static str, PathBuf, Result<(), String>)>,
+}
+
+impl WritableDirs {
+    pub async fn probe(config: &Config) -> Self {
+        let mut dirs = vec![];
+        if config.diagnostics.enabled {
+            dirs.push(("diagnostics_dir", config.diagnostics.dir.clone()));
+        }
+        if let Some(state_file) = &config.shutdown.state_file {
+            let dir = state_file
+                .parent()
+                .filter(|dir| !dir.as_os_str().is_empty())
+                .unwrap_or(Path::new("."));
+            dirs.push(("state_file_dir", dir.to_path_buf()));
+        }
+        let mut probed = Vec::with_capacity(dirs.len());
+        for (name, dir) in dirs {
+            let result = check_writable(&dir).await;
+            if let Err(err) = &result {
+                warn!("{name} is not writable, {err}");
+            }
+            probed.push((name, dir, result));
+        }
+        Self { dirs: probed }
+    }
+
+    async fn checks(&self) -> Vec<ReadinessCheck> {
+        let mut checks = Vec::with_capacity(self.dirs.len());
+        for (name, dir, startup) in &self.dirs {
+            let result = match startup {
+                Ok(()) => check_permissions(dir).await,
+                Err(err) => Err(err.clone()),
+            };
+            checks.push(ReadinessCheck::new(name, result));
+        }
+        checks
+    }
+}
+
+/// Catches a directory removed or made read only after startup, without writing to it.
+async fn check_permissions(dir: &Path) -> Result<(), String> {
+    let metadata = tokio::fs::metadata(dir)
+        .await
+        .map_err(|err| format!("cannot read {dir:?} {err}"))?;
+    if !metadata.is_dir() {
+        return Err(format!("{dir:?} is not a directory"));
+    }
+    if metadata.permissions().readonly() {
+        return Err(format!("{dir:?} is read only"));
+    }
+    Ok(())
+}
+
+/// Writes and removes a probe file, catches read only mounts and missing permissions.
+async fn check_writable(dir: &Path) -> Result<(), String> {
+    let probe = dir.join(".tvmate-readyz");
+    tokio::fs::create_dir_all(dir)
+        .await
+        .map_err(|err| format!("cannot create {dir:?} {err}"))?;
+    tokio::fs::write(&probe, b"")
+        .await
+        .map_err(|err| format!("cannot write to {dir:?} {err}"))?;
+    tokio::fs::remove_file(&probe)
+        .await
+        .map_err(|err| format!("cannot remove {probe:?} {err}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn rechecks_permissions_only() {
+        let dir = std::env::temp_dir().join(format!("tvmate-readyz-{}", uuid::Uuid::new_v4()));
+        let mut config = Config::default();
+        config.diagnostics.enabled = true;
+        config.diagnostics.dir = dir.clone();
+        let dirs = WritableDirs::probe(&config).await;
+        assert!(dirs.checks().await.iter().all(|check| check.ok));
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 0);
+
+        std::fs::remove_dir(&dir).unwrap();
+        let checks = dirs.checks().await;
+        assert_eq!(checks[0].name, "diagnostics_dir");
+        assert!(!checks[0].ok);
+    }
+}
@@ -0,0 +1,241 @@
+//! Minimal STUN binding responder (RFC 5389 subset).
+//!
+//! Only Binding Requests are understood, the reply carries a single
+//! XOR-MAPPED-ADDRESS so clients on a LAN can discover their address without
+//! running a separate STUN server. Every other message is silently dropped.
+
+use std::net::{IpAddr, SocketAddr};
+
+use thiserror::Error;
+use tokio::net::UdpSocket;
+use tracing::{debug, info, warn};
+
+pub const MAGIC_COOKIE: u32 = 0x2112_A442;
+
+const HEADER_LEN: usize = 20;
+const BINDING_REQUEST: u16 = 0x0001;
+const BINDING_SUCCESS: u16 = 0x0101;
+const XOR_MAPPED_ADDRESS: u16 = 0x0020;
+
+const FAMILY_IPV4: u8 = 0x01;
+const FAMILY_IPV6: u8 = 0x02;
+
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum StunError {
+    #[error("message shorter than stun header")]
+    TooShort,
+    #[error("first two bits of message type are not zero")]
+    NotStun,
+    #[error("magic cookie mismatch")]
+    BadMagicCookie,
+    #[error("declared length {declared} does not match payload {actual}")]
+    LengthMismatch { declared: usize, actual: usize },
+    #[error("unsupported message type {0:#06x}")]
+    UnsupportedType(u16),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BindingRequest {
+    pub transaction_id: [u8; 12],
+}
+
+pub fn parse_binding_request(data: &[u8]) -> Result<BindingRequest, StunError> {
+    if data.len() < HEADER_LEN {
+        return Err(StunError::TooShort);
+    }
+    let message_type = u16::from_be_bytes([data[0], data[1]]);
+    if message_type & 0xC000 != 0 {
+        return Err(StunError::NotStun);
+    }
+    let declared = u16::from_be_bytes([data[2], data[3]]) as usize;
+    let actual = data.len() - HEADER_LEN;
+    if declared != actual || declared % 4 != 0 {
+        return Err(StunError::LengthMismatch { declared, actual });
+    }
+    if u32::from_be_bytes([data[4], data[5], data[6], data[7]]) != MAGIC_COOKIE {
+        return Err(StunError::BadMagicCookie);
+    }
+    if message_type != BINDING_REQUEST {
+        return Err(StunError::UnsupportedType(message_type));
+    }
+    let mut transaction_id = [0; 12];
+    transaction_id.copy_from_slice(&data[8..HEADER_LEN]);
+    Ok(BindingRequest { transaction_id })
+}
+
+/// Encodes a Binding Success Response reporting `mapped` as the client's address.
+pub fn binding_success(transaction_id: &[u8; 12], mapped: SocketAddr) -> Vec<u8> {
+    let cookie = MAGIC_COOKIE.to_be_bytes();
+    let port = mapped.port() ^ (MAGIC_COOKIE >> 16) as u16;
+
+    let mut attribute = vec![0];
+    match mapped.ip() {
+        IpAddr::V4(ip) => {
+            attribute.push(FAMILY_IPV4);
+            attribute.extend_from_slice(&port.to_be_bytes());
+            attribute.extend(ip.octets().iter().zip(cookie).map(|(a, b)| a ^ b));
+        }
+        IpAddr::V6(ip) => {
+            attribute.push(FAMILY_IPV6);
+            attribute.extend_from_slice(&port.to_be_bytes());
+            let key = cookie.iter().chain(transaction_id.iter());
+            attribute.extend(ip.octets().iter().zip(key).map(|(a, b)| a ^ b));
+        }
+    }
+
+    let mut response = Vec::with_capacity(HEADER_LEN + 4 + attribute.len());
+    response.extend_from_slice(&BINDING_SUCCESS.to_be_bytes());
+    response.extend_from_slice(&((4 + attribute.len()) as u16).to_be_bytes());
+    response.extend_from_slice(&cookie);
+    response.extend_from_slice(transaction_id);
+    response.extend_from_slice(&XOR_MAPPED_ADDRESS.to_be_bytes());
+    response.extend_from_slice(&(attribute.len() as u16).to_be_bytes());
+    response.extend_from_slice(&attribute);
+    response
+}
+
+/// Answers binding requests on `socket` until the socket errors.
+pub async fn serve(socket: UdpSocket) -> std::io::Result<()> {
+    info!("STUN responder listening on {}", socket.local_addr()?);
+    let mut buf = [0; 1500];
+    loop {
+        let (len, peer) = socket.recv_from(&mut buf).await?;
+        match parse_binding_request(&buf[..len]) {
+            Ok(request) => {
+                let response = binding_success(&request.transaction_id, peer);
+                if let Err(err) = socket.send_to(&response, peer).await {
+                    warn!("Failed to send stun response to {peer} {err:?}");
+                }
+            }
+            Err(err) => {
+                debug!("Ignoring stun packet from {peer}: {err}");
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::{Ipv4Addr, Ipv6Addr};
+
+    use super::*;
+
+    /// RFC 5769 2.1, a binding request carrying SOFTWARE, PRIORITY, ICE-CONTROLLED, USERNAME,
+    /// MESSAGE-INTEGRITY and FINGERPRINT.
+    const SAMPLE_REQUEST: [u8; 108] = [
+        0x00, 0x01, 0x00, 0x58, 0x21, 0x12, 0xa4, 0x42, 0xb7, 0xe7, 0xa7, 0x01, 0xbc, 0x34, 0xd6,
+        0x86, 0xfa, 0x87, 0xdf, 0xae, 0x80, 0x22, 0x00, 0x10, 0x53, 0x54, 0x55, 0x4e, 0x20, 0x74,
+        0x65, 0x73, 0x74, 0x20, 0x63, 0x6c, 0x69, 0x65, 0x6e, 0x74, 0x00, 0x24, 0x00, 0x04, 0x6e,
+        0x00, 0x01, 0xff, 0x80, 0x29, 0x00, 0x08, 0x93, 0x2f, 0xf9, 0xb1, 0x51, 0x26, 0x3b, 0x36,
+        0x00, 0x06, 0x00, 0x09, 0x65, 0x76, 0x74, 0x6a, 0x3a, 0x68, 0x36, 0x76, 0x59, 0x20, 0x20,
+        0x20, 0x00, 0x08, 0x00, 0x14, 0x9a, 0xea, 0xa7, 0x0c, 0xbf, 0xd8, 0xcb, 0x56, 0x78, 0x1e,
+        0xf2, 0xb5, 0xb2, 0xd3, 0xf2, 0x49, 0xc1, 0xb5, 0x71, 0xa2, 0x80, 0x28, 0x00, 0x04, 0xe5,
+        0x7a, 0x3b, 0xcf,
+    ];
+
+    const SAMPLE_TRANSACTION_ID: [u8; 12] = [
+        0xb7, 0xe7, 0xa7, 0x01, 0xbc, 0x34, 0xd6, 0x86, 0xfa, 0x87, 0xdf, 0xae,
+    ];
+
+    /// XOR-MAPPED-ADDRESS of the RFC 5769 2.2 response, 192.0.2.1:32853.
+    const SAMPLE_IPV4_MAPPED: [u8; 12] = [
+        0x00, 0x20, 0x00, 0x08, 0x00, 0x01, 0xa1, 0x47, 0xe1, 0x12, 0xa6, 0x43,
+    ];
+
+    /// XOR-MAPPED-ADDRESS of the RFC 5769 2.3 response,
+    /// [2001:db8:1234:5678:11:2233:4455:6677]:32853.
+    const SAMPLE_IPV6_MAPPED: [u8; 24] = [
+        0x00, 0x20, 0x00, 0x14, 0x00, 0x02, 0xa1, 0x47, 0x01, 0x13, 0xa9, 0xfa, 0xa5, 0xd3, 0xf1,
+        0x79, 0xbc, 0x25, 0xf4, 0xb5, 0xbe, 0xd2, 0xb9, 0xd9,
+    ];
+
+    fn assert_response(response: &[u8], mapped: &[u8]) {
+        assert_eq!(response[0..2], BINDING_SUCCESS.to_be_bytes());
+        assert_eq!(
+            usize::from(u16::from_be_bytes([response[2], response[3]])),
+            response.len() - HEADER_LEN
+        );
+        assert_eq!(response[4..8], MAGIC_COOKIE.to_be_bytes());
+        assert_eq!(response[8..HEADER_LEN], SAMPLE_TRANSACTION_ID);
+        assert_eq!(&response[HEADER_LEN..], mapped);
+    }
+
+    #[test]
+    fn parses_rfc5769_request() {
+        let request = parse_binding_request(&SAMPLE_REQUEST).unwrap();
+        assert_eq!(request.transaction_id, SAMPLE_TRANSACTION_ID);
+    }
+
+    #[test]
+    fn encodes_ipv4_mapped_address() {
+        let mapped = SocketAddr::new(Ipv4Addr::new(192, 0, 2, 1).into(), 32853);
+        let response = binding_success(&SAMPLE_TRANSACTION_ID, mapped);
+        assert_response(&response, &SAMPLE_IPV4_MAPPED);
+    }
+
+    #[test]
+    fn encodes_ipv6_mapped_address() {
+        let ip = Ipv6Addr::new(0x2001, 0xdb8, 0x1234, 0x5678, 0x11, 0x2233, 0x4455, 0x6677);
+        let response = binding_success(&SAMPLE_TRANSACTION_ID, SocketAddr::new(ip.into(), 32853));
+        assert_response(&response, &SAMPLE_IPV6_MAPPED);
+    }
+
+    #[test]
+    fn rejects_wrong_magic_cookie() {
+        let mut request = SAMPLE_REQUEST;
+        request[4] = 0x42;
+        assert_eq!(
+            parse_binding_request(&request),
+            Err(StunError::BadMagicCookie)
+        );
+    }
+
+    #[test]
+    fn rejects_other_methods() {
+        // A binding indication.
+        let mut request = SAMPLE_REQUEST;
+        request[1] = 0x11;
+        assert_eq!(
+            parse_binding_request(&request),
+            Err(StunError::UnsupportedType(0x0011))
+        );
+        // Our own response must not be answered either.
+        let response = binding_success(
+            &SAMPLE_TRANSACTION_ID,
+            SocketAddr::new(Ipv4Addr::LOCALHOST.into(), 3478),
+        );
+        assert_eq!(
+            parse_binding_request(&response),
+            Err(StunError::UnsupportedType(BINDING_SUCCESS))
+        );
+    }
+
+    #[test]
+    fn rejects_length_mismatch() {
+        assert_eq!(
+            parse_binding_request(&SAMPLE_REQUEST[..100]),
+            Err(StunError::LengthMismatch {
+                declared: 88,
+                actual: 80
+            })
+        );
+        assert_eq!(
+            parse_binding_request(&SAMPLE_REQUEST[..HEADER_LEN - 1]),
+            Err(StunError::TooShort)
+        );
+    }
+
+    #[tokio::test]
+    async fn answers_over_udp() {
+        let socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let server = socket.local_addr().unwrap();
+        tokio::spawn(serve(socket));
+
+        let client = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        client.send_to(&SAMPLE_REQUEST, server).await.unwrap();
+        let mut buf = [0; 1500];
+        let len = client.recv(&mut buf).await.unwrap();
+        let expected = binding_success(&SAMPLE_TRANSACTION_ID, client.local_addr().unwrap());
+        assert_eq!(buf[..len], expected);
+    }
+}
@@ -0,0 +1,222 @@
+//! Sets up the global tracing subscriber: stdout in text or JSON, journald on linux and,
+//! with the `otel` feature, span export over OTLP.
+
+use tracing::{level_filters::LevelFilter, Subscriber};
+use tracing_subscriber::{
+    layer::SubscriberExt, registry::LookupSpan, util::SubscriberInitExt, EnvFilter, Layer,
+};
+
+use crate::config::{LogFormat, LoggingConfig};
+
+/// Flushes pending spans when dropped, keep it alive for the lifetime of the server.
+pub struct Telemetry {
+    #[cfg(feature = "otel")]
+    provider: Option<opentelemetry_sdk::trace::TracerProvider>,
+}
+
+impl Drop for Telemetry {
+    fn drop(&mut self) {
+        #[cfg(feature = "otel")]
+        if let Some(provider) = self.provider.take() {
+            if let Err(err) = provider.shutdown() {
+                eprintln!("Cannot flush traces {err}");
+            }
+        }
+    }
+}
+
+fn stdout_layer<S>(format: LogFormat) -> Box<dyn Layer<S> + Send + Sync>
+where
+    S: Subscriber + for<'span> LookupSpan<'span>,
+{
+    match format {
+        LogFormat::Text => tracing_subscriber::fmt::layer().boxed(),
+        LogFormat::Json => tracing_subscriber::fmt::layer()
+            .json()
+            .with_current_span(true)
+            .with_span_list(true)
+            .boxed(),
+    }
+}
+
+pub fn init(config: &LoggingConfig) -> Telemetry {
+    let journald = if cfg!(target_os = "linux") {
+        tracing_journald::layer()
+            .inspect_err(|err| eprintln!("Cant get journald_layer {err:#?}"))
+            .ok()
+    } else {
+        None
+    };
+    let subscriber = tracing_subscriber::registry()
+        .with(stdout_layer(config.format))
+        .with(journald)
+        .with(
+            EnvFilter::builder()
+                .with_default_directive(LevelFilter::INFO.into())
+                .from_env_lossy(),
+        );
+
+    #[cfg(feature = "otel")]
+    {
+        let (otel, provider) = match config.otlp_endpoint.as_deref().map(|endpoint| {
+            otel::layer(endpoint, &config.service_name)
+                .inspect_err(|err| eprintln!("Cannot export traces to {endpoint} {err}"))
+        }) {
+            Some(Ok((layer, provider))) => (Some(layer), Some(provider)),
+            _ => (None, None),
+        };
+        if let Err(err) = subscriber.with(otel).try_init() {
+            eprintln!("Cannot initialize tracing {err:#?}");
+        }
+        Telemetry { provider }
+    }
+
+    #[cfg(not(feature = "otel"))]
+    {
+        if let Err(err) = subscriber.try_init() {
+            eprintln!("Cannot initialize tracing {err:#?}");
+        }
+        if config.otlp_endpoint.is_some() {
+            tracing::warn!(
+                "logging.otlp_endpoint is set but the server was built without the otel feature"
+            );
+        }
+        Telemetry {}
+    }
+}
+
+#[cfg(feature = "otel")]
+mod otel {
+    use opentelemetry::{trace::TraceError, trace::TracerProvider as _, KeyValue};
+    use opentelemetry_otlp::WithExportConfig;
+    use opentelemetry_sdk::{runtime, trace::TracerProvider, Resource};
+    use tracing::Subscriber;
+    use tracing_subscriber::{registry::LookupSpan, Layer};
+
+    pub fn layer<S>(
+        endpoint: &str,
+        service_name: &str,
+    ) -> Result<(impl Layer<S>, TracerProvider), TraceError>
+    where
+        S: Subscriber + for<'span> LookupSpan<'span>,
+    {
+        let exporter = opentelemetry_otlp::SpanExporter::builder()
+            .with_tonic()
+            .with_endpoint(endpoint)
+            .build()?;
+        let provider = TracerProvider::builder()
+            .with_batch_exporter(exporter, runtime::Tokio)
+            .with_resource(Resource::new([KeyValue::new(
+                "service.name",
+                service_name.to_string(),
+            )]))
+            .build();
+        let tracer = provider.tracer("tvmate");
+        Ok((tracing_opentelemetry::layer().with_tracer(tracer), provider))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn runs_without_collector() {
+        // Nothing listens on port 1, exports fail and only get reported.
+        let telemetry = init(&LoggingConfig {
+            otlp_endpoint: Some("http://127.0.0.1:1".to_string()),
+            ..Default::default()
+        });
+        tracing::info_span!("room", room_id = "abcdef").in_scope(|| tracing::info!("joined"));
+        // Flushing on drop blocks until the exporter gives up.
+        let flushed = tokio::task::spawn_blocking(move || drop(telemetry));
+        tokio::time::timeout(Duration::from_secs(30), flushed)
+            .await
+            .expect("dropping telemetry hangs without a collector")
+            .unwrap();
+    }
+
+    #[cfg(feature = "otel")]
+    #[test]
+    fn rejects_invalid_endpoint() {
+        let layer = otel::layer::<tracing_subscriber::Registry>("not a url", "tvmate");
+        assert!(layer.is_err());
+    }
+
+    #[cfg(feature = "otel")]
+    #[tokio::test(flavor = "multi_thread")]
+    async fn exports_spans_over_otlp() {
+        use opentelemetry_proto::tonic::{
+            collector::trace::v1::{
+                trace_service_server::{TraceService, TraceServiceServer},
+                ExportTraceServiceRequest, ExportTraceServiceResponse,
+            },
+            common::v1::any_value::Value,
+        };
+        use tokio::sync::mpsc;
+        use tonic::{transport::server::TcpIncoming, Request, Response, Status};
+
+        struct Collector(mpsc::UnboundedSender<ExportTraceServiceRequest>);
+
+        #[tonic::async_trait]
+        impl TraceService for Collector {
+            async fn export(
+                &self,
+                request: Request<ExportTraceServiceRequest>,
+            ) -> Result<Response<ExportTraceServiceResponse>, Status> {
+                let _ = self.0.send(request.into_inner());
+                Ok(Response::new(ExportTraceServiceResponse {
+                    partial_success: None,
+                }))
+            }
+        }
+
+        let (tx, mut exported) = mpsc::unbounded_channel();
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let incoming = TcpIncoming::from_listener(listener, true, None).unwrap();
+        tokio::spawn(
+            tonic::transport::Server::builder()
+                .add_service(TraceServiceServer::new(Collector(tx)))
+                .serve_with_incoming(incoming),
+        );
+
+        let (layer, provider) = otel::layer(&format!("http://{addr}"), "tvmate-test").unwrap();
+        let subscriber = tracing_subscriber::registry().with(layer);
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::info_span!("room", room_id = "abcdef").in_scope(|| {});
+        });
+        tokio::task::spawn_blocking(move || provider.shutdown())
+            .await
+            .unwrap()
+            .unwrap();
+
+        let request = tokio::time::timeout(Duration::from_secs(10), exported.recv())
+            .await
+            .expect("no spans exported")
+            .unwrap();
+        let resource_spans = &request.resource_spans[0];
+        let service_name = resource_spans
+            .resource
+            .as_ref()
+            .unwrap()
+            .attributes
+            .iter()
+            .find(|attribute| attribute.key == "service.name")
+            .and_then(|attribute| attribute.value.clone()?.value);
+        assert_eq!(
+            service_name,
+            Some(Value::StringValue("tvmate-test".to_string()))
+        );
+        let span = &resource_spans.scope_spans[0].spans[0];
+        assert_eq!(span.name, "room");
+        let room_id = span
+            .attributes
+            .iter()
+            .find(|attribute| attribute.key == "room_id")
+            .and_then(|attribute| attribute.value.clone()?.value);
+        assert_eq!(room_id, Some(Value::StringValue("abcdef".to_string())));
+    }
+}
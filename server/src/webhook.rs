@@ -0,0 +1,462 @@
+//! Outgoing webhooks for room events.
+//!
+//! Events are queued with [`Webhooks::emit`], which never waits, and delivered by a
+//! background task. Every request carries the JSON payload signed with the target's
+//! secret in `X-TVMate-Signature: sha256=<hex hmac of the body>`. Failed deliveries are
+//! retried with exponential backoff until `webhooks.max_attempts` is reached. Each target
+//! has at most `webhooks.max_in_flight` deliveries running, events beyond that are dropped.
+//!
+//! `playback_ended` is only sent when the pausing user's video reported a duration, receivers
+//! that need an end of session signal should use `room_closed`.
+
+use std::{
+    fmt::Write,
+    sync::Arc,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use common::{UserMeta, UserState};
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use tokio::sync::{mpsc, Semaphore};
+use tracing::{debug, warn};
+use uuid::Uuid;
+
+use crate::{
+    config::{WebhookTarget, WebhooksConfig},
+    metrics,
+};
+
+pub const SIGNATURE_HEADER: &str = "X-TVMate-Signature";
+pub const EVENT_HEADER: &str = "X-TVMate-Event";
+
+const INITIAL_BACKOFF: Duration = Duration::from_secs(1);
+const MAX_BACKOFF: Duration = Duration::from_secs(60);
+/// A pause this close to the end of the video counts as the video having ended.
+const END_TOLERANCE_SECS: f64 = 1.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WebhookEventKind {
+    RoomCreated,
+    UserJoined,
+    UserLeft,
+    PlaybackStarted,
+    PlaybackPaused,
+    PlaybackEnded,
+    RoomClosed,
+    Chat,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RoomCloseReason {
+    /// The last user left a room that is not kept alive.
+    Empty,
+    /// A room created through the API or restored after a restart was never rejoined.
+    Expired,
+    /// Closed by its host through the API.
+    Host,
+    Admin,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "event", content = "data", rename_all = "snake_case")]
+pub enum WebhookEvent {
+    /// `host` is `None` for rooms created through the API.
+    RoomCreated {
+        room_id: String,
+        host: Option<UserMeta>,
+    },
+    UserJoined {
+        room_id: String,
+        user: UserMeta,
+    },
+    UserLeft {
+        room_id: String,
+        user_id: Uuid,
+    },
+    PlaybackStarted {
+        room_id: String,
+        user_id: Uuid,
+        time: f64,
+    },
+    PlaybackPaused {
+        room_id: String,
+        user_id: Uuid,
+        time: f64,
+    },
+    /// Sent after the matching `PlaybackPaused`, see [`reached_end`].
+    PlaybackEnded {
+        room_id: String,
+        user_id: Uuid,
+        time: f64,
+    },
+    RoomClosed {
+        room_id: String,
+        reason: RoomCloseReason,
+    },
+    Chat {
+        room_id: String,
+        user_id: Uuid,
+        message: String,
+    },
+}
+
+impl WebhookEventKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            WebhookEventKind::RoomCreated => "room_created",
+            WebhookEventKind::UserJoined => "user_joined",
+            WebhookEventKind::UserLeft => "user_left",
+            WebhookEventKind::PlaybackStarted => "playback_started",
+            WebhookEventKind::PlaybackPaused => "playback_paused",
+            WebhookEventKind::PlaybackEnded => "playback_ended",
+            WebhookEventKind::RoomClosed => "room_closed",
+            WebhookEventKind::Chat => "chat",
+        }
+    }
+}
+
+impl WebhookEvent {
+    pub fn kind(&self) -> WebhookEventKind {
+        match self {
+            WebhookEvent::RoomCreated { .. } => WebhookEventKind::RoomCreated,
+            WebhookEvent::UserJoined { .. } => WebhookEventKind::UserJoined,
+            WebhookEvent::UserLeft { .. } => WebhookEventKind::UserLeft,
+            WebhookEvent::PlaybackStarted { .. } => WebhookEventKind::PlaybackStarted,
+            WebhookEvent::PlaybackPaused { .. } => WebhookEventKind::PlaybackPaused,
+            WebhookEvent::PlaybackEnded { .. } => WebhookEventKind::PlaybackEnded,
+            WebhookEvent::RoomClosed { .. } => WebhookEventKind::RoomClosed,
+            WebhookEvent::Chat { .. } => WebhookEventKind::Chat,
+        }
+    }
+}
+
+/// Whether pausing at `time` is `user`'s video ending, `false` when its duration is unknown.
+pub fn reached_end(user: &UserMeta, time: f64) -> bool {
+    match &user.state {
+        UserState::VideoSelected(video) => video
+            .duration
+            .is_some_and(|duration| time >= duration - END_TOLERANCE_SECS),
+        UserState::VideoNotSelected => false,
+    }
+}
+
+#[derive(Serialize)]
+struct Payload<'a> {
+    /// Same for every attempt, lets receivers drop duplicate deliveries.
+    id: Uuid,
+    /// Unix seconds at which the event happened.
+    timestamp: u64,
+    #[serde(flatten)]
+    event: &'a WebhookEvent,
+}
+
+/// Cheap to clone handle to the delivery queue, does nothing when no target is configured.
+#[derive(Clone, Default)]
+pub struct Webhooks {
+    tx: Option<mpsc::Sender<WebhookEvent>>,
+}
+
+impl Webhooks {
+    /// Spawns the delivery task, must be called inside the tokio runtime.
+    pub fn start(config: &WebhooksConfig) -> Self {
+        if config.targets.is_empty() {
+            return Self::default();
+        }
+        let client = match reqwest::Client::builder()
+            .timeout(Duration::from_secs(config.timeout_secs))
+            .user_agent(concat!("tvmate/", env!("CARGO_PKG_VERSION")))
+            .build()
+        {
+            Ok(client) => client,
+            Err(err) => {
+                warn!("Cannot build webhook client, webhooks disabled {err:?}");
+                return Self::default();
+            }
+        };
+        let (tx, rx) = mpsc::channel(config.queue_size);
+        let dispatcher = Dispatcher {
+            client,
+            targets: config
+                .targets
+                .iter()
+                .map(|target| Target {
+                    config: Arc::new(target.clone()),
+                    in_flight: Arc::new(Semaphore::new(config.max_in_flight)),
+                })
+                .collect(),
+            max_attempts: config.max_attempts,
+        };
+        tokio::spawn(dispatcher.run(rx));
+        Self { tx: Some(tx) }
+    }
+
+    /// Queues `event` for delivery, dropping it if the queue is full.
+    pub fn emit(&self, event: WebhookEvent) {
+        let Some(tx) = &self.tx else {
+            return;
+        };
+        if let Err(err) = tx.try_send(event) {
+            warn!("Dropping webhook event, {err}");
+            metrics::webhook_dropped("queue_full");
+        }
+    }
+}
+
+struct Dispatcher {
+    client: reqwest::Client,
+    targets: Vec<Target>,
+    max_attempts: u32,
+}
+
+struct Target {
+    config: Arc<WebhookTarget>,
+    /// Permits for deliveries still running, including ones waiting to retry.
+    in_flight: Arc<Semaphore>,
+}
+
+impl Dispatcher {
+    async fn run(self, mut rx: mpsc::Receiver<WebhookEvent>) {
+        while let Some(event) = rx.recv().await {
+            let payload = Payload {
+                id: Uuid::new_v4(),
+                timestamp: SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .map(|since| since.as_secs())
+                    .unwrap_or_default(),
+                event: &event,
+            };
+            let body = match serde_json::to_vec(&payload) {
+                Ok(body) => Arc::new(body),
+                Err(err) => {
+                    warn!("Cannot serialize webhook payload {err:?}");
+                    continue;
+                }
+            };
+            let kind = event.kind();
+            for target in self
+                .targets
+                .iter()
+                .filter(|target| target.config.accepts(kind))
+            {
+                let Ok(permit) = target.in_flight.clone().try_acquire_owned() else {
+                    warn!(
+                        "Dropping {} webhook to {}, too many deliveries in flight",
+                        kind.as_str(),
+                        target.config.url
+                    );
+                    metrics::webhook_dropped("target_busy");
+                    continue;
+                };
+                // Each delivery retries on its own so a slow target does not hold up the rest.
+                let delivery = deliver(
+                    self.client.clone(),
+                    target.config.clone(),
+                    kind,
+                    body.clone(),
+                    self.max_attempts,
+                );
+                tokio::spawn(async move {
+                    delivery.await;
+                    drop(permit);
+                });
+            }
+        }
+    }
+}
+
+impl WebhookTarget {
+    fn accepts(&self, kind: WebhookEventKind) -> bool {
+        self.events.is_empty() || self.events.contains(&kind)
+    }
+}
+
+async fn deliver(
+    client: reqwest::Client,
+    target: Arc<WebhookTarget>,
+    kind: WebhookEventKind,
+    body: Arc<Vec<u8>>,
+    max_attempts: u32,
+) {
+    let signature = format!("sha256={}", sign(&target.secret, &body));
+    let event = kind.as_str();
+    let mut backoff = INITIAL_BACKOFF;
+    for attempt in 1..=max_attempts {
+        let result = client
+            .post(&target.url)
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .header(SIGNATURE_HEADER, &signature)
+            .header(EVENT_HEADER, event)
+            .body(body.as_ref().clone())
+            .send()
+            .await
+            .and_then(reqwest::Response::error_for_status);
+        match result {
+            Ok(_) => {
+                debug!("Delivered {event} webhook to {}", target.url);
+                return;
+            }
+            Err(err) if attempt < max_attempts => {
+                debug!(
+                    "Webhook {event} to {} failed on attempt {attempt}, retrying in {backoff:?} {err}",
+                    target.url
+                );
+                tokio::time::sleep(backoff).await;
+                backoff = (backoff * 2).min(MAX_BACKOFF);
+            }
+            Err(err) => {
+                warn!(
+                    "Giving up on webhook {event} to {} after {attempt} attempts {err}",
+                    target.url
+                );
+            }
+        }
+    }
+}
+
+/// Lowercase hex HMAC-SHA256 of `body`.
+pub fn sign(secret: &str, body: &[u8]) -> String {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("hmac accepts keys of any size");
+    mac.update(body);
+    mac.finalize()
+        .into_bytes()
+        .iter()
+        .fold(String::with_capacity(64), |mut hex, byte| {
+            // Writing to a String cannot fail.
+            let _ = write!(hex, "{byte:02x}");
+            hex
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use axum::{
+        body::Bytes,
+        extract::State,
+        http::{HeaderMap, StatusCode},
+        routing::post,
+        Router,
+    };
+
+    use super::*;
+
+    /// Requests seen by [`receiver`], which fails the first one with a 502.
+    #[derive(Clone)]
+    struct Receiver {
+        requests: mpsc::UnboundedSender<(HeaderMap, Bytes)>,
+        attempts: Arc<AtomicUsize>,
+    }
+
+    async fn receiver(
+        State(receiver): State<Receiver>,
+        headers: HeaderMap,
+        body: Bytes,
+    ) -> StatusCode {
+        receiver.requests.send((headers, body)).unwrap();
+        if receiver.attempts.fetch_add(1, Ordering::SeqCst) == 0 {
+            StatusCode::BAD_GATEWAY
+        } else {
+            StatusCode::OK
+        }
+    }
+
+    #[test]
+    fn ends_only_with_known_duration() {
+        let mut user = UserMeta {
+            id: Uuid::new_v4(),
+            name: "user".to_string(),
+            state: UserState::VideoNotSelected,
+            role: Default::default(),
+            appearance: Default::default(),
+        };
+        assert!(!reached_end(&user, 100.0));
+        user.state = UserState::VideoSelected(common::message::VideoMeta {
+            name: "movie.mkv".to_string(),
+            duration: None,
+        });
+        assert!(!reached_end(&user, 100.0));
+        user.state = UserState::VideoSelected(common::message::VideoMeta {
+            name: "movie.mkv".to_string(),
+            duration: Some(100.0),
+        });
+        assert!(!reached_end(&user, 90.0));
+        assert!(reached_end(&user, 99.5));
+        assert!(reached_end(&user, 100.0));
+    }
+
+    #[test]
+    fn signs_with_hmac_sha256() {
+        // RFC 4231 test case 2.
+        assert_eq!(
+            sign("Jefe", b"what do ya want for nothing?"),
+            "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+        );
+    }
+
+    #[tokio::test]
+    async fn delivers_signed_payload_and_retries() {
+        let (requests, mut received) = mpsc::unbounded_channel();
+        let app = Router::new()
+            .route("/hook", post(receiver))
+            .with_state(Receiver {
+                requests,
+                attempts: Arc::default(),
+            });
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+
+        let webhooks = Webhooks::start(&WebhooksConfig {
+            targets: vec![WebhookTarget {
+                url: format!("http://{addr}/hook"),
+                secret: "secret".to_string(),
+                events: vec![WebhookEventKind::Chat],
+            }],
+            max_attempts: 2,
+            ..Default::default()
+        });
+        let user_id = Uuid::new_v4();
+        // Not subscribed to, so the first request is the chat.
+        webhooks.emit(WebhookEvent::UserLeft {
+            room_id: "abcdef".to_string(),
+            user_id,
+        });
+        webhooks.emit(WebhookEvent::Chat {
+            room_id: "abcdef".to_string(),
+            user_id,
+            message: "hi".to_string(),
+        });
+
+        let (headers, body) = received.recv().await.unwrap();
+        assert_eq!(
+            headers[SIGNATURE_HEADER],
+            format!("sha256={}", sign("secret", &body)).as_str()
+        );
+        assert_eq!(headers[EVENT_HEADER], "chat");
+        assert_eq!(headers["content-type"], "application/json");
+        let payload: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(payload["event"], "chat");
+        assert_eq!(
+            payload["data"],
+            serde_json::json!({
+                "room_id": "abcdef",
+                "user_id": user_id,
+                "message": "hi",
+            })
+        );
+
+        let (retry_headers, retry_body) = received.recv().await.unwrap();
+        assert_eq!(retry_body, body);
+        assert_eq!(retry_headers[SIGNATURE_HEADER], headers[SIGNATURE_HEADER]);
+
+        // Delivered on the retry, nothing more arrives.
+        let more = tokio::time::timeout(INITIAL_BACKOFF * 3, received.recv()).await;
+        assert!(more.is_err());
+    }
+}
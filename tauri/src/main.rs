@@ -47,9 +47,6 @@ fn main() {
 
     set_global_default(subscriber).expect("Failed to set global default subscriber");
 
-    let endpoint = Endpoint {
-        main_endpoint: std::borrow::Cow::Borrowed("wss://tvmate.deepgaurav.com"),
-    };
     let log_provider = LogProvider { logs };
 
     let fullsreen_provider = FullScreenProvider {
@@ -84,7 +81,7 @@ fn main() {
     mount_to_body(|| {
         provide_context(fullsreen_provider);
         provide_context(log_provider);
-        provide_context(endpoint);
+        provide_context(Endpoint::configurable("wss://tvmate.deepgaurav.com"));
         view! { <App /> }
     })
 }